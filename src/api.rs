@@ -0,0 +1,176 @@
+//! Loading and running tasks from Rust code, without the CLI's prompts and
+//! output.
+
+use crate::config::{self, PlzConfig, Task};
+use crate::diagnostic::Diagnostic;
+use crate::healthcheck::{self, CheckResult};
+use crate::runner::{self, EventHandler, Events, Outcome};
+use anyhow::{Result, bail};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// A loaded plz.toml.
+#[derive(Debug)]
+pub struct Plz {
+    config: PlzConfig,
+    root: PathBuf,
+}
+
+/// Options for [`TaskHandle::run`].
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    /// Prompt for missing args and offer `fail_hook` suggestions, like the
+    /// CLI does in a terminal
+    pub interactive: bool,
+    /// Extra arguments, as if given after the task name on the command line
+    pub args: Vec<String>,
+}
+
+/// Options for [`Plz::healthcheck`], like the flags of `plz healthcheck`.
+#[derive(Debug, Clone, Default)]
+pub struct HealthcheckOptions {
+    /// Only check staged files
+    pub staged: bool,
+    /// Run only these checks
+    pub only: Vec<String>,
+    /// Leave out these checks
+    pub skip: Vec<String>,
+}
+
+impl Plz {
+    /// Load the plz.toml in `dir`, or in the nearest parent directory that
+    /// has one (stopping at the git root).
+    pub fn discover(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref();
+        let Some(path) = config::find_config(dir) else {
+            bail!("No plz.toml found in {} or its parents", dir.display());
+        };
+        Self::load(path)
+    }
+
+    /// Load the config at `path`, with the profile `PLZ_PROFILE` or
+    /// `default_profile` picks.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::load_profile(path, None)
+    }
+
+    /// Load the config at `path` with a profile, like `plz --profile`.
+    pub fn load_profile(path: impl AsRef<Path>, profile: Option<&str>) -> Result<Self> {
+        let path = path.as_ref();
+        let config = config::load_profile(path, profile)?;
+        let root = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+        Ok(Plz { config, root })
+    }
+
+    /// Send what running tasks do to `handler` instead of printing it to
+    /// stderr.
+    pub fn with_events(mut self, handler: impl EventHandler + 'static) -> Self {
+        self.config.events = Events(Some(Arc::new(handler)));
+        self
+    }
+
+    pub fn config(&self) -> &PlzConfig {
+        &self.config
+    }
+
+    /// Problems loading found that don't stop tasks from running, such as
+    /// unknown keys. The CLI prints these as warnings.
+    pub fn warnings(&self) -> &[Diagnostic] {
+        &self.config.warnings
+    }
+
+    /// The directory containing plz.toml, which tasks run from.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Every task, as `build` for top-level tasks and `group:task` for
+    /// task groups and workspace members, sorted.
+    pub fn task_names(&self) -> Vec<String> {
+        let groups = self.config.taskgroup.iter().flatten();
+        let mut names: Vec<String> = self
+            .config
+            .tasks
+            .keys()
+            .cloned()
+            .chain(
+                groups.flat_map(|(group, g)| g.tasks.keys().map(move |t| format!("{group}:{t}"))),
+            )
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Look up a task by name: `build`, or `group:task`.
+    pub fn task(&self, name: &str) -> Result<TaskHandle<'_>> {
+        let (group, task_name) = match name.split_once(':') {
+            Some((group, task_name)) => (Some(group), task_name),
+            None => (None, name),
+        };
+        let task = match group {
+            Some(group) => self.config.get_group_task(group, task_name),
+            None => self.config.tasks.get(task_name),
+        };
+        let Some(task) = task else {
+            bail!("\"{name}\" isn't a task");
+        };
+        Ok(TaskHandle {
+            plz: self,
+            group: group.map(str::to_string),
+            name: task_name.to_string(),
+            task,
+        })
+    }
+
+    /// Run the healthcheck on the files under [`Plz::root`], returning each
+    /// check's result rather than printing them.
+    pub fn healthcheck(&self, options: &HealthcheckOptions) -> Result<Vec<CheckResult>> {
+        healthcheck::run_checks(&self.root, options.staged, &options.only, &options.skip)
+    }
+}
+
+/// A task in a [`Plz`] config, ready to run.
+#[derive(Debug)]
+pub struct TaskHandle<'a> {
+    plz: &'a Plz,
+    group: Option<String>,
+    name: String,
+    task: &'a Task,
+}
+
+impl TaskHandle<'_> {
+    /// The task's full name, e.g. `build` or `docs:build`.
+    pub fn name(&self) -> String {
+        match self.group {
+            Some(ref group) => format!("{group}:{}", self.name),
+            None => self.name.clone(),
+        }
+    }
+
+    pub fn task(&self) -> &Task {
+        self.task
+    }
+
+    /// Run the task and its dependencies, returning how the task ended.
+    /// Fails if it, or a command it runs, fails.
+    pub fn run(&self, options: RunOptions) -> Result<Outcome> {
+        let plz = self.plz;
+        match self.group {
+            Some(ref group) => runner::run_group_task_with_args(
+                &plz.config,
+                group,
+                &self.name,
+                &plz.root,
+                options.interactive,
+                &options.args,
+            ),
+            None => runner::run_task_with_args(
+                &plz.config,
+                &self.name,
+                &plz.root,
+                options.interactive,
+                &options.args,
+            ),
+        }
+    }
+}
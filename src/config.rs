@@ -1,13 +1,36 @@
-use anyhow::{Context, Result, bail};
+use crate::diagnostic::{Diagnostic, Location};
+use anyhow::{Context, Result, anyhow, bail};
 use schemars::{JsonSchema, SchemaGenerator, json_schema};
 use serde::Deserialize;
 use serde::de::{self, Deserializer, SeqAccess, Visitor};
 use std::borrow::Cow;
+use std::collections::hash_map::Entry;
 use std::collections::{HashMap, HashSet};
 use std::fmt;
-use std::path::Path;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use toml_edit::DocumentMut;
 
+pub const CONFIG_NAMES: &[&str] = &["plz.toml", ".plz.toml"];
+
+/// Personal, git-ignored overrides merged on top of the config next to them.
+pub const LOCAL_CONFIG_NAMES: &[&str] = &["plz.local.toml", ".plz.local.toml"];
+
+/// Built-in commands that `plz <name>` falls back to. Aliases can't shadow them.
+pub const BUILTIN_COMMANDS: &[&str] = &[
+    "init",
+    "add",
+    "hooks",
+    "schema",
+    "cheatsheet",
+    "update",
+    "help",
+    "healthcheck",
+    "plz",
+];
+
 pub const VALID_GIT_HOOKS: &[&str] = &[
     "applypatch-msg",
     "pre-applypatch",
@@ -37,16 +60,124 @@ pub struct GlobalSettings {
     #[serde(default, rename = "env")]
     #[schemars(rename = "env")]
     pub tool_env: Option<String>,
+    /// Default shell for all tasks (see the task-level `shell`)
+    #[serde(default)]
+    pub shell: Option<Shell>,
     /// Default working directory (relative to plz.toml) for all tasks
     #[serde(default)]
     pub dir: Option<String>,
+    /// Default time limit for each command (see the task-level `timeout`)
+    #[serde(default)]
+    pub timeout: Option<HumanDuration>,
+    /// Environment variables set for all tasks (merged with per-task `environment`)
+    #[serde(default)]
+    pub environment: Option<HashMap<String, VarValue>>,
+    /// Default dotenv file (relative to plz.toml) loaded for all tasks
+    #[serde(default)]
+    pub env_file: Option<String>,
+    /// Default action when a task fails (see the task-level `fail_hook`)
+    #[serde(default)]
+    pub fail_hook: Option<FailHook>,
+    /// Text put in front of every task description (e.g. "[web] ")
+    #[serde(default)]
+    pub description_prefix: Option<String>,
+    /// Hide tasks from interactive pickers and listings unless they set `hide = false`
+    #[serde(default)]
+    pub hide: Option<bool>,
+}
+
+/// Values that can be set to `""` to opt out of an `[extends]` default.
+trait OptOut {
+    fn is_opt_out(&self) -> bool;
+}
+
+impl OptOut for String {
+    fn is_opt_out(&self) -> bool {
+        self.is_empty()
+    }
+}
+
+impl OptOut for FailHook {
+    fn is_opt_out(&self) -> bool {
+        matches!(self, FailHook::Command(cmd) if cmd.is_empty())
+    }
+}
+
+impl OptOut for Shell {
+    fn is_opt_out(&self) -> bool {
+        matches!(self, Shell::Program(argv) if argv.is_empty())
+    }
+}
+
+impl OptOut for HumanDuration {
+    fn is_opt_out(&self) -> bool {
+        self.0.is_zero()
+    }
+}
+
+impl GlobalSettings {
+    /// Fill every setting this one leaves unset from `lower`, a level with
+    /// lower precedence.
+    fn fill_from(&mut self, lower: &GlobalSettings) {
+        macro_rules! fill {
+            ($($field:ident),*) => {
+                $(
+                    if self.$field.is_none() {
+                        self.$field.clone_from(&lower.$field);
+                    }
+                )*
+            };
+        }
+        fill!(
+            tool_env,
+            shell,
+            dir,
+            timeout,
+            env_file,
+            fail_hook,
+            description_prefix,
+            hide
+        );
+        merge_environment(&mut self.environment, lower.environment.as_ref());
+    }
+
+    /// Apply these defaults to `task`: fill every field it leaves unset,
+    /// then drop the empty values that opt out of a default.
+    fn apply_to(&self, task: &mut Task) {
+        macro_rules! cascade {
+            ($($field:ident),*) => {
+                $(
+                    if task.$field.is_none() {
+                        task.$field.clone_from(&self.$field);
+                    }
+                    if task.$field.as_ref().is_some_and(OptOut::is_opt_out) {
+                        task.$field = None;
+                    }
+                )*
+            };
+        }
+        cascade!(tool_env, shell, dir, timeout, env_file, fail_hook);
+        merge_environment(&mut task.environment, self.environment.as_ref());
+        if task.hide_setting.is_none() {
+            task.hide_setting = self.hide;
+        }
+        task.hide = task.hide_setting.unwrap_or(false);
+        if let Some(ref prefix) = self.description_prefix
+            && let Some(ref mut description) = task.description
+        {
+            description.insert_str(0, prefix);
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct TaskGroup {
     pub extends: Option<GlobalSettings>,
-    pub vars: Option<HashMap<String, String>>,
+    pub vars: Option<HashMap<String, VarValue>>,
     pub tasks: HashMap<String, Task>,
+    /// Directory (relative to the root plz.toml) of the workspace member this
+    /// group was loaded from. `None` for groups written as `[taskgroup.X]`.
+    pub workspace_dir: Option<String>,
 }
 
 impl<'de> Deserialize<'de> for TaskGroup {
@@ -75,7 +206,7 @@ impl<'de> Deserialize<'de> for TaskGroup {
                     if key == "extends" {
                         extends = Some(map.next_value::<GlobalSettings>()?);
                     } else if key == "vars" {
-                        vars = Some(map.next_value::<HashMap<String, String>>()?);
+                        vars = Some(map.next_value::<HashMap<String, VarValue>>()?);
                     } else {
                         tasks.insert(key, map.next_value::<Task>()?);
                     }
@@ -85,6 +216,7 @@ impl<'de> Deserialize<'de> for TaskGroup {
                     extends,
                     vars,
                     tasks,
+                    workspace_dir: None,
                 })
             }
         }
@@ -107,7 +239,7 @@ impl JsonSchema for TaskGroup {
                 "vars": {
                     "type": "object",
                     "description": "Variables for {{key}} substitution in task commands within this group",
-                    "additionalProperties": { "type": "string" }
+                    "additionalProperties": generator.subschema_for::<VarValue>()
                 }
             },
             "additionalProperties": generator.subschema_for::<Task>()
@@ -121,6 +253,16 @@ pub struct PlzSection {
     /// Semver version requirement for plz (e.g. ">=0.1.0", "^0.2")
     #[serde(default)]
     pub version: Option<String>,
+    /// Other TOML files whose tasks, taskgroups, vars and extends are merged into this config.
+    /// Paths are relative to the including file; `~/` expands to the home directory.
+    #[serde(default)]
+    pub include: Option<Vec<String>>,
+    /// Profile used when neither `--profile` nor `PLZ_PROFILE` picks one
+    #[serde(default)]
+    pub default_profile: Option<String>,
+    /// Also mask secret values in task output. Output is then piped through plz line by line.
+    #[serde(default)]
+    pub mask_output: bool,
 }
 
 #[derive(Debug, Default, Deserialize, JsonSchema)]
@@ -140,6 +282,24 @@ pub struct HealthcheckSection {
     pub git_hook: Option<StringOrVec>,
 }
 
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct WorkspaceSection {
+    /// Glob patterns of member directories (relative to plz.toml), each with its own plz.toml.
+    /// Each member's tasks are loaded as a task group named after its directory.
+    #[serde(default)]
+    pub members: Vec<String>,
+    /// Glob patterns of directories to leave out, even if they match `members`
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Run `plz --all <task>` in one member at a time instead of in parallel
+    #[serde(default)]
+    pub serial: bool,
+}
+
+/// A dynamic var's value, `None` until it has been evaluated.
+pub type CachedVar = Arc<Mutex<Option<String>>>;
+
 #[derive(Debug, Deserialize, JsonSchema)]
 #[schemars(deny_unknown_fields)]
 pub struct PlzConfig {
@@ -152,15 +312,328 @@ pub struct PlzConfig {
     /// Global defaults that apply to all tasks (can be overridden per-task)
     #[serde(default)]
     pub extends: Option<GlobalSettings>,
+    /// Monorepo members whose plz.toml files are loaded as task groups
+    #[serde(default)]
+    pub workspace: Option<WorkspaceSection>,
     /// Task groups for namespacing related tasks (e.g. [taskgroup.rust.test])
     #[serde(default)]
     pub taskgroup: Option<HashMap<String, TaskGroup>>,
     /// Variables for {{key}} substitution in task commands
     #[serde(default)]
-    pub vars: Option<HashMap<String, String>>,
+    pub vars: Option<HashMap<String, VarValue>>,
     /// Tasks to run, keyed by name (e.g. [tasks.build]). Run with `plz <name>`.
     #[serde(default)]
     pub tasks: HashMap<String, Task>,
+    /// Named sets of vars and environment variables (e.g. [profile.staging.vars]), picked with `plz --profile <name>`
+    #[serde(default)]
+    pub profile: Option<HashMap<String, Profile>>,
+    /// The profile in effect, if any
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub active_profile: Option<String>,
+    /// File each task was defined in, keyed by "task" or "group.task"
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub task_sources: HashMap<String, PathBuf>,
+    /// Output of `{ sh = "..." }` and contents of `{ file = "..." }` vars,
+    /// keyed by directory and source, so each runs at most once. Each entry
+    /// has its own lock, held while the var evaluates.
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub var_cache: Mutex<HashMap<(PathBuf, VarValue), CachedVar>>,
+    /// Values of secret vars and environment entries seen so far
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub secrets: Secrets,
+    /// Where running tasks report what they do
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub events: crate::runner::Events,
+    /// Time limit from `--timeout`, used over every task's own `timeout`
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub timeout: Option<Duration>,
+    /// Limit on commands running at once, from `-j` or `PLZ_JOBS`
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub jobs: Option<Arc<crate::runner::Jobs>>,
+    /// Set by `--fail-fast`, which turns on `fail_fast` for every task
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub fail_fast: bool,
+    /// Problems found while loading that don't stop plz, for the caller to show
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// A `[profile.<name>]` section, layered over the rest of the config when
+/// the profile is active.
+#[derive(Debug, Default, Clone, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct Profile {
+    /// Vars that replace `[vars]` and task group vars of the same name
+    #[serde(default)]
+    pub vars: Option<HashMap<String, VarValue>>,
+    /// Environment variables set for every task, over their own `environment`
+    #[serde(default)]
+    pub environment: Option<HashMap<String, VarValue>>,
+}
+
+/// Which profile a config is loaded with.
+#[derive(Debug, Clone, Copy)]
+enum ProfileChoice<'a> {
+    /// The config's `default_profile`, if it has one
+    Default,
+    /// A profile that must exist
+    Named(&'a str),
+    /// A workspace member takes the root's profile only if it defines it
+    IfDefined(&'a str),
+}
+
+/// A `[vars]` or `environment` entry: a plain string, `{ value = "..." }`,
+/// or `{ sh = "..." }` / `{ file = "..." }` whose trimmed output or contents
+/// become the value the first time a running task needs it. Any of the
+/// tables can add `secret = true`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum VarValue {
+    Static(String),
+    /// Run in `dir`, the directory of the plz.toml that defined it
+    Command {
+        sh: String,
+        dir: Option<PathBuf>,
+    },
+    /// `path` is relative to `dir`, the directory of the plz.toml that
+    /// defined it
+    File {
+        path: String,
+        dir: Option<PathBuf>,
+    },
+    /// Masked as `***` in everything plz prints
+    Secret(Box<VarValue>),
+}
+
+impl VarValue {
+    /// The value without its `secret` marker.
+    pub fn source(&self) -> &VarValue {
+        match self {
+            VarValue::Secret(inner) => inner.source(),
+            other => other,
+        }
+    }
+
+    pub fn is_secret(&self) -> bool {
+        matches!(self, VarValue::Secret(_))
+    }
+
+    /// Whether the value is only known once a task runs.
+    pub fn is_dynamic(&self) -> bool {
+        !matches!(self.source(), VarValue::Static(_))
+    }
+
+    /// The value, command or file path.
+    pub fn text(&self) -> &str {
+        match self {
+            VarValue::Static(text)
+            | VarValue::Command { sh: text, .. }
+            | VarValue::File { path: text, .. } => text,
+            VarValue::Secret(inner) => inner.text(),
+        }
+    }
+
+    fn text_mut(&mut self) -> &mut String {
+        match self {
+            VarValue::Static(text)
+            | VarValue::Command { sh: text, .. }
+            | VarValue::File { path: text, .. } => text,
+            VarValue::Secret(inner) => inner.text_mut(),
+        }
+    }
+
+    /// The directory a dynamic value is run or read in, once loading has
+    /// set it.
+    pub fn dir(&self) -> Option<&Path> {
+        match self {
+            VarValue::Static(_) => None,
+            VarValue::Command { dir, .. } | VarValue::File { dir, .. } => dir.as_deref(),
+            VarValue::Secret(inner) => inner.dir(),
+        }
+    }
+
+    /// Set the directory of a dynamic value that doesn't have one yet.
+    fn set_dir(&mut self, config_dir: &Path) {
+        match self {
+            VarValue::Static(_) => {}
+            VarValue::Command { dir, .. } | VarValue::File { dir, .. } => {
+                dir.get_or_insert_with(|| config_dir.to_path_buf());
+            }
+            VarValue::Secret(inner) => inner.set_dir(config_dir),
+        }
+    }
+}
+
+impl PartialEq<&str> for VarValue {
+    fn eq(&self, other: &&str) -> bool {
+        self.text() == *other
+    }
+}
+
+impl<'de> Deserialize<'de> for VarValue {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct VarValueVisitor;
+
+        impl<'de> Visitor<'de> for VarValueVisitor {
+            type Value = VarValue;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a string or a map with value, sh or file")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> std::result::Result<VarValue, E> {
+                Ok(VarValue::Static(v.to_string()))
+            }
+
+            fn visit_map<M>(self, mut map: M) -> std::result::Result<VarValue, M::Error>
+            where
+                M: de::MapAccess<'de>,
+            {
+                let mut sources = Vec::new();
+                let mut secret = false;
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "value" => sources.push(VarValue::Static(map.next_value()?)),
+                        "sh" => sources.push(VarValue::Command {
+                            sh: map.next_value()?,
+                            dir: None,
+                        }),
+                        "file" => sources.push(VarValue::File {
+                            path: map.next_value()?,
+                            dir: None,
+                        }),
+                        "secret" => secret = map.next_value()?,
+                        other => {
+                            return Err(de::Error::unknown_field(
+                                other,
+                                &["value", "sh", "file", "secret"],
+                            ));
+                        }
+                    }
+                }
+                if sources.len() > 1 {
+                    return Err(de::Error::custom("expected only one of value, sh or file"));
+                }
+                let value = sources
+                    .pop()
+                    .ok_or_else(|| de::Error::missing_field("value, sh or file"))?;
+                Ok(if secret {
+                    VarValue::Secret(Box::new(value))
+                } else {
+                    value
+                })
+            }
+        }
+
+        deserializer.deserialize_any(VarValueVisitor)
+    }
+}
+
+impl JsonSchema for VarValue {
+    fn inline_schema() -> bool {
+        true
+    }
+
+    fn schema_name() -> Cow<'static, str> {
+        Cow::Borrowed("VarValue")
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> schemars::Schema {
+        let secret = json_schema!({
+            "type": "boolean",
+            "description": "Mask the value as *** in commands, errors and summaries plz prints"
+        });
+        let source = |key: &str, description: &str| {
+            json_schema!({
+                "type": "object",
+                "properties": {
+                    key: { "type": "string", "description": description },
+                    "secret": secret.clone()
+                },
+                "required": [key],
+                "additionalProperties": false
+            })
+        };
+        json_schema!({
+            "oneOf": [
+                {
+                    "type": "string",
+                    "description": "Static value"
+                },
+                source("value", "Static value"),
+                source("sh", "Shell command whose output is the value, run only when a task using it runs"),
+                source("file", "File (relative to plz.toml) whose contents are the value, read only when a task using it runs")
+            ]
+        })
+    }
+}
+
+/// Values of secret vars and environment entries, masked as `***` wherever
+/// plz prints a command, error or summary.
+#[derive(Debug, Default)]
+pub struct Secrets {
+    values: Mutex<Vec<String>>,
+    /// Also mask task output (`[plz] mask_output`)
+    pub mask_output: bool,
+}
+
+impl Secrets {
+    pub fn add(&self, value: &str) {
+        if value.is_empty() {
+            return;
+        }
+        let mut values = self.values.lock().unwrap_or_else(|e| e.into_inner());
+        if !values.iter().any(|v| v == value) {
+            values.push(value.to_string());
+            // Longest first, so a secret containing another is masked whole
+            values.sort_by_key(|v| std::cmp::Reverse(v.len()));
+        }
+    }
+
+    pub fn mask(&self, text: &str) -> String {
+        let values = self.values.lock().unwrap_or_else(|e| e.into_inner());
+        let mut text = text.to_string();
+        for value in values.iter() {
+            if text.contains(value.as_str()) {
+                text = text.replace(value.as_str(), "***");
+            }
+        }
+        text
+    }
+
+    /// A copy of the values known now, for threads that outlive the borrow.
+    pub fn snapshot(&self) -> Secrets {
+        Secrets {
+            values: Mutex::new(
+                self.values
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .clone(),
+            ),
+            mask_output: self.mask_output,
+        }
+    }
+
+    /// Whether task output has to be piped through `mask`.
+    pub fn masks_output(&self) -> bool {
+        self.mask_output
+            && !self
+                .values
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .is_empty()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -219,28 +692,157 @@ impl JsonSchema for StringOrVec {
     }
 }
 
-#[derive(Debug, Deserialize, JsonSchema)]
+/// Platform names accepted in `platforms` and per-platform tables, as
+/// reported by `std::env::consts::OS`.
+pub const PLATFORMS: &[&str] = &["linux", "macos", "windows", "freebsd"];
+
+const PLATFORM_TABLE_KEYS: &[&str] = &["linux", "macos", "windows", "freebsd", "default"];
+
+/// Deserialize a field that takes either a plain value or a per-platform table
+/// like `{ linux = "...", macos = "...", default = "..." }`, keeping only the
+/// value for the current platform.
+fn for_platform<'de, D, T>(deserializer: D) -> std::result::Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    struct PlatformVisitor<T>(PhantomData<T>);
+
+    impl<'de, T: Deserialize<'de>> Visitor<'de> for PlatformVisitor<T> {
+        type Value = Option<T>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a value or a table of per-platform values")
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> std::result::Result<Option<T>, E> {
+            T::deserialize(de::value::StrDeserializer::new(v)).map(Some)
+        }
+
+        fn visit_seq<S: SeqAccess<'de>>(self, seq: S) -> std::result::Result<Option<T>, S::Error> {
+            T::deserialize(de::value::SeqAccessDeserializer::new(seq)).map(Some)
+        }
+
+        fn visit_map<M>(self, mut map: M) -> std::result::Result<Option<T>, M::Error>
+        where
+            M: de::MapAccess<'de>,
+        {
+            let mut values: HashMap<String, T> = HashMap::new();
+            while let Some(key) = map.next_key::<String>()? {
+                if !PLATFORM_TABLE_KEYS.contains(&key.as_str()) {
+                    return Err(de::Error::unknown_field(&key, PLATFORM_TABLE_KEYS));
+                }
+                values.insert(key, map.next_value()?);
+            }
+            Ok(values
+                .remove(std::env::consts::OS)
+                .or_else(|| values.remove("default")))
+        }
+    }
+
+    deserializer.deserialize_any(PlatformVisitor(PhantomData))
+}
+
+/// Schema for fields deserialized with [`for_platform`].
+pub struct PlatformSpecific<T>(PhantomData<T>);
+
+impl<T: JsonSchema> JsonSchema for PlatformSpecific<T> {
+    fn inline_schema() -> bool {
+        true
+    }
+
+    fn schema_name() -> Cow<'static, str> {
+        Cow::Owned(format!("PlatformSpecific_{}", T::schema_name()))
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> schemars::Schema {
+        let value = generator.subschema_for::<T>();
+        let properties: serde_json::Map<String, serde_json::Value> = PLATFORM_TABLE_KEYS
+            .iter()
+            .map(|key| (key.to_string(), value.clone().into()))
+            .collect();
+        json_schema!({
+            "oneOf": [
+                value,
+                {
+                    "type": "object",
+                    "description": "Per-platform values. `default` applies to platforms that aren't listed.",
+                    "properties": properties,
+                    "additionalProperties": false
+                }
+            ]
+        })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 #[schemars(deny_unknown_fields)]
 pub struct Task {
-    /// A shell command (or list of commands to run serially) to run
+    /// Inherit every field this task doesn't set from another task ("task" or "group.task")
     #[serde(default)]
+    pub extends: Option<String>,
+    /// A shell command (or list of commands to run serially) to run. Also accepts a per-platform table.
+    #[serde(default, deserialize_with = "for_platform")]
+    #[schemars(with = "Option<PlatformSpecific<StringOrVec>>")]
     pub run: Option<StringOrVec>,
-    /// Multiple commands to run one after another (stops on first failure)
-    #[serde(default)]
+    /// Multiple commands to run one after another (stops on first failure). Also accepts a per-platform table.
+    #[serde(default, deserialize_with = "for_platform")]
+    #[schemars(with = "Option<PlatformSpecific<Vec<String>>>")]
     pub run_serial: Option<Vec<String>>,
-    /// Multiple commands to run concurrently
-    #[serde(default)]
+    /// Multiple commands to run concurrently. Also accepts a per-platform table.
+    #[serde(default, deserialize_with = "for_platform")]
+    #[schemars(with = "Option<PlatformSpecific<Vec<String>>>")]
     pub run_parallel: Option<Vec<String>>,
+    /// Inline script, written to a temp file and run with `interpreter` (or its `#!` line). Extra arguments are passed to it.
+    #[serde(default)]
+    pub script: Option<String>,
+    /// Program that runs `script` (e.g. "python3", "node", "bash -e")
+    #[serde(default)]
+    pub interpreter: Option<String>,
     /// Prerequisite tasks to run before this task. Use dot notation for group tasks (e.g. "group.task").
     #[serde(default)]
     pub depends: Option<StringOrVec>,
-    /// Tool environment wrapper: "pnpm" (uses `pnpm exec`), "npm" (uses `npx`), "uv" (uses `uv run`), or "uvx" (uses `uvx`)
-    #[serde(default, rename = "env")]
-    #[schemars(rename = "env")]
+    /// Tool environment wrapper: "pnpm" (uses `pnpm exec`), "npm" (uses `npx`), "uv" (uses `uv run`), or "uvx" (uses `uvx`). Also accepts a per-platform table.
+    #[serde(default, rename = "env", deserialize_with = "for_platform")]
+    #[schemars(rename = "env", with = "Option<PlatformSpecific<String>>")]
     pub tool_env: Option<String>,
-    /// Working directory (relative to plz.toml)
+    /// Shell that runs the commands: a program ("bash" runs `bash -c`), a full prefix (["zsh", "-c"]), or false to run each command directly
     #[serde(default)]
+    pub shell: Option<Shell>,
+    /// Working directory (relative to plz.toml). Also accepts a per-platform table.
+    #[serde(default, deserialize_with = "for_platform")]
+    #[schemars(with = "Option<PlatformSpecific<String>>")]
     pub dir: Option<String>,
+    /// Only run on these platforms (e.g. ["linux", "macos"]). Elsewhere the task is hidden and skipped.
+    #[serde(default)]
+    pub platforms: Option<Vec<String>>,
+    /// Only run when this condition holds, otherwise skip (e.g. { file = "pnpm-lock.yaml" })
+    #[serde(default)]
+    pub run_if: Option<Condition>,
+    /// Skip when this condition holds (e.g. { ci = true })
+    #[serde(default)]
+    pub skip_if: Option<Condition>,
+    /// Kill a command that runs longer than this, along with any processes it started (e.g. "30s", "10m", "1h30m")
+    #[serde(default)]
+    pub timeout: Option<HumanDuration>,
+    /// How run_parallel shows each command's output: "prefixed" (the default) labels every line, "grouped" prints each command's output in one block when it finishes, "raw" lets commands write to the terminal directly
+    #[serde(default)]
+    pub output: Option<OutputMode>,
+    /// Start at most this many run_parallel commands at once, queueing the rest
+    #[serde(default)]
+    pub max_parallel: Option<usize>,
+    /// Stop the other run_parallel commands as soon as one fails
+    #[serde(default)]
+    pub fail_fast: Option<bool>,
+    /// Run the task again when it fails, before its fail_hook (e.g. { attempts = 3, delay = "2s", backoff = 2.0 })
+    #[serde(default)]
+    pub retry: Option<Retry>,
+    /// Environment variables to set when running this task (e.g. { RUST_LOG = "debug" }). Values also take { sh = "..." }, { file = "..." } and secret = true, like vars.
+    #[serde(default)]
+    pub environment: Option<HashMap<String, VarValue>>,
+    /// Dotenv file (relative to plz.toml) to load environment variables from
+    #[serde(default)]
+    pub env_file: Option<String>,
     /// Action to take when the task fails: a command string, { suggest_command = "..." }, or { message = "..." }
     #[serde(default)]
     pub fail_hook: Option<FailHook>,
@@ -251,11 +853,115 @@ pub struct Task {
     #[serde(default)]
     pub git_hook: Option<String>,
     /// Hide this task from interactive pickers and listings
-    #[serde(default)]
+    #[serde(default, rename = "hide")]
+    #[schemars(rename = "hide")]
+    hide_setting: Option<bool>,
+    /// Whether the task is hidden, after `[extends]` defaults and `platforms`
+    #[serde(skip)]
+    #[schemars(skip)]
     pub hide: bool,
+    /// Short names that also run this task (e.g. ["t"])
+    #[serde(default)]
+    pub aliases: Option<Vec<String>>,
+    /// Positional arguments, referenced as {{args.name}} (e.g. [{ name = "env", choices = ["staging", "prod"] }])
+    #[serde(default)]
+    pub args: Option<Vec<TaskArg>>,
+    /// Dynamic vars in scope for this task (`sh` and `file` vars), resolved when it runs
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub dynamic_vars: HashMap<String, VarValue>,
+    /// Fields inherited through `extends`, mapped to the task that defined them
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub inherited: HashMap<&'static str, String>,
+    /// Why the task can't run here (e.g. an unset `{{env.X}}`). Reported
+    /// when it, or a task depending on it, runs.
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub unavailable: Option<Diagnostic>,
 }
 
-#[derive(Debug)]
+/// A `run_if` / `skip_if` condition. When several keys are set, all of them
+/// must hold.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct Condition {
+    /// Path (relative to the task's dir) that must exist
+    #[serde(default)]
+    pub file: Option<String>,
+    /// Environment variable that must be set and non-empty
+    #[serde(default)]
+    pub env: Option<String>,
+    /// Shell command that must exit successfully
+    #[serde(default)]
+    pub sh: Option<String>,
+    /// Whether plz must be running in CI (true) or outside of it (false)
+    #[serde(default)]
+    pub ci: Option<bool>,
+}
+
+/// How `run_parallel` shows the output of the commands it runs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputMode {
+    /// Each line starts with a colored label for its command
+    #[default]
+    Prefixed,
+    /// Labelled lines, held back until the command finishes
+    Grouped,
+    /// Commands write straight to the terminal
+    Raw,
+}
+
+/// A task's `retry` setting.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct Retry {
+    /// How many times to run the task in all, including the first time
+    pub attempts: u32,
+    /// How long to wait before the second attempt (e.g. "2s")
+    #[serde(default)]
+    pub delay: Option<HumanDuration>,
+    /// What the delay is multiplied by after each attempt
+    #[serde(default = "Retry::default_backoff")]
+    pub backoff: f64,
+}
+
+impl Retry {
+    fn default_backoff() -> f64 {
+        1.0
+    }
+
+    /// How long to wait after the failed `attempt` (1-based).
+    pub fn delay_after(&self, attempt: u32) -> Duration {
+        let delay = self.delay.map_or(Duration::ZERO, |d| d.0);
+        let factor = self
+            .backoff
+            .powi(attempt.saturating_sub(1).try_into().unwrap_or(i32::MAX));
+        Duration::try_from_secs_f64(delay.as_secs_f64() * factor).unwrap_or(Duration::MAX)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct TaskArg {
+    /// Argument name, referenced as {{args.name}}
+    pub name: String,
+    /// Allowed values
+    #[serde(default)]
+    pub choices: Option<Vec<String>>,
+    /// Value used when the argument isn't given
+    #[serde(default)]
+    pub default: Option<String>,
+    /// Collect all remaining arguments (only allowed on the last argument)
+    #[serde(default)]
+    pub variadic: bool,
+    /// Description shown in the task's --help
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone)]
 pub enum FailHook {
     Command(String),
     Suggest { suggest_command: String },
@@ -354,62 +1060,468 @@ impl<'de> Deserialize<'de> for FailHook {
     }
 }
 
-fn substitute_vars(input: &str, vars: &HashMap<String, String>) -> Result<String> {
-    let mut result = input.to_string();
-    let mut keys: Vec<&String> = vars.keys().collect();
-    keys.sort();
-    for key in keys {
-        result = result.replace(&format!("{{{{{key}}}}}"), &vars[key]);
-    }
-    // Check for unresolved {{...}} patterns
-    if let Some(start) = result.find("{{") {
-        if let Some(end) = result[start + 2..].find("}}") {
-            let unresolved = &result[start + 2..start + 2 + end];
-            bail!("Unresolved variable \"{{{{{unresolved}}}}}\"");
-        }
+/// A duration written like `30s`, `10m` or `1h30m`. `""` is zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HumanDuration(pub Duration);
+
+impl fmt::Display for HumanDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&crate::utils::format_duration(self.0))
     }
-    Ok(result)
 }
 
-fn substitute_task_vars(task: &mut Task, vars: &HashMap<String, String>) -> Result<()> {
-    if let Some(ref mut run) = task.run {
-        for cmd in &mut run.0 {
-            *cmd = substitute_vars(cmd, vars)?;
-        }
-    }
-    if let Some(ref mut cmds) = task.run_serial {
-        for cmd in cmds {
-            *cmd = substitute_vars(cmd, vars)?;
-        }
-    }
-    if let Some(ref mut cmds) = task.run_parallel {
-        for cmd in cmds {
-            *cmd = substitute_vars(cmd, vars)?;
-        }
+impl<'de> Deserialize<'de> for HumanDuration {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let text = String::deserialize(deserializer)?;
+        crate::utils::parse_duration(&text)
+            .map(HumanDuration)
+            .ok_or_else(|| {
+                de::Error::custom(format!(
+                    "invalid duration \"{text}\". Use a number and a unit: ms, s, m or h (e.g. \"30s\", \"1h30m\")"
+                ))
+            })
     }
-    Ok(())
 }
 
-fn format_location(path: &str) -> String {
-    if path.is_empty() {
-        "plz.toml".to_string()
-    } else {
+impl JsonSchema for HumanDuration {
+    fn inline_schema() -> bool {
+        true
+    }
+
+    fn schema_name() -> Cow<'static, str> {
+        Cow::Borrowed("HumanDuration")
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> schemars::Schema {
+        json_schema!({
+            "type": "string",
+            "pattern": "^(|0|([0-9]+(\\.[0-9]+)?(ms|s|m|h))+)$",
+            "description": "A duration such as \"30s\", \"10m\" or \"1h30m\""
+        })
+    }
+}
+
+/// The program a task's commands run under.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Shell {
+    /// A program and its leading arguments; each command is passed as the
+    /// last argument. Empty means the default, `/bin/sh -c`.
+    Program(Vec<String>),
+    /// `shell = false`: split each command into arguments and run it directly.
+    Direct,
+}
+
+impl Default for Shell {
+    fn default() -> Self {
+        Shell::Program(Vec::new())
+    }
+}
+
+impl<'de> Deserialize<'de> for Shell {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct ShellVisitor;
+
+        impl<'de> Visitor<'de> for ShellVisitor {
+            type Value = Shell;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a shell name, an array of arguments, or false")
+            }
+
+            // `"bash"` runs `bash -c`, and `""` opts out of an `[extends]` default
+            fn visit_str<E: de::Error>(self, v: &str) -> std::result::Result<Shell, E> {
+                let mut argv = shlex::split(v).ok_or_else(|| {
+                    E::custom(format!("can't split shell \"{v}\" into arguments"))
+                })?;
+                if !argv.is_empty() {
+                    argv.push("-c".to_string());
+                }
+                Ok(Shell::Program(argv))
+            }
+
+            fn visit_seq<S: SeqAccess<'de>>(
+                self,
+                mut seq: S,
+            ) -> std::result::Result<Shell, S::Error> {
+                let mut argv = Vec::new();
+                while let Some(arg) = seq.next_element::<String>()? {
+                    argv.push(arg);
+                }
+                if argv.is_empty() {
+                    return Err(de::Error::invalid_length(0, &"a program to run"));
+                }
+                Ok(Shell::Program(argv))
+            }
+
+            fn visit_bool<E: de::Error>(self, v: bool) -> std::result::Result<Shell, E> {
+                if v {
+                    return Err(E::invalid_value(de::Unexpected::Bool(true), &self));
+                }
+                Ok(Shell::Direct)
+            }
+        }
+
+        deserializer.deserialize_any(ShellVisitor)
+    }
+}
+
+impl JsonSchema for Shell {
+    fn inline_schema() -> bool {
+        true
+    }
+
+    fn schema_name() -> Cow<'static, str> {
+        Cow::Borrowed("Shell")
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> schemars::Schema {
+        json_schema!({
+            "oneOf": [
+                {
+                    "type": "string",
+                    "description": "Program to run commands with `-c` (e.g. \"bash\"), or \"\" for the default"
+                },
+                {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "minItems": 1,
+                    "description": "Program and arguments; the command is passed as the last argument (e.g. [\"zsh\", \"-c\"])"
+                },
+                {
+                    "const": false,
+                    "description": "Run each command directly, split into arguments like a shell would, without a shell"
+                }
+            ]
+        })
+    }
+}
+
+/// What `{{...}}` placeholders can refer to besides `env.X`.
+struct Interpolation<'a> {
+    vars: &'a HashMap<String, VarValue>,
+    /// Directory containing the plz.toml being loaded
+    root: &'a Path,
+    /// Name of the task being substituted, as passed to `plz`
+    task: &'a str,
+    /// Names of the task's declared `args`, left as `{{args.name}}` until it runs
+    args: &'a [String],
+}
+
+/// Expand every `{{name}}` in `input`. Static vars are expanded recursively
+/// (cycles are rejected at load time); dynamic vars stay in place until the
+/// task runs.
+fn substitute_vars(input: &str, ctx: &Interpolation) -> Result<String> {
+    let mut result = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(start) = rest.find("{{")
+        && let Some(end) = rest[start + 2..].find("}}")
+    {
+        result.push_str(&rest[..start]);
+        let name = &rest[start + 2..start + 2 + end];
+        result.push_str(&resolve_placeholder(name, ctx)?);
+        rest = &rest[start + 2 + end + 2..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+fn resolve_placeholder(name: &str, ctx: &Interpolation) -> Result<String> {
+    if let Some(var) = name.strip_prefix("env.") {
+        // Like the shell's ${VAR:-default}, the default also covers empty values
+        let (key, default) = match var.split_once(":-") {
+            Some((key, default)) => (key, Some(default)),
+            None => (var, None),
+        };
+        return match (std::env::var(key), default) {
+            (Ok(value), None) => Ok(value),
+            (Ok(value), Some(_)) if !value.is_empty() => Ok(value),
+            (_, Some(default)) => Ok(default.to_string()),
+            (Err(_), None) => Err(Unavailable(format!(
+                "Environment variable \"{key}\" is not set. Use {{{{env.{key}:-default}}}} to provide a fallback."
+            ))
+            .into()),
+        };
+    }
+    if let Some(builtin) = name.strip_prefix("plz.") {
+        return builtin_var(builtin, ctx);
+    }
+    if let Some(arg) = name.strip_prefix("args.") {
+        if !ctx.args.iter().any(|a| a == arg) {
+            bail!(
+                "Unresolved variable \"{{{{{name}}}}}\": the task declares no argument \"{arg}\""
+            );
+        }
+        return Ok(format!("{{{{{name}}}}}"));
+    }
+    match ctx.vars.get(name) {
+        Some(var) if var.is_dynamic() => Ok(format!("{{{{{name}}}}}")),
+        Some(var) => substitute_vars(var.text(), ctx).with_context(|| format!("In var \"{name}\"")),
+        None => bail!("Unresolved variable \"{{{{{name}}}}}\""),
+    }
+}
+
+fn builtin_var(name: &str, ctx: &Interpolation) -> Result<String> {
+    Ok(match name {
+        "root" => ctx.root.display().to_string(),
+        "git_root" => find_git_root(ctx.root)
+            .ok_or_else(|| Unavailable("{{plz.git_root}} used outside a git repository".into()))?
+            .display()
+            .to_string(),
+        "os" => std::env::consts::OS.to_string(),
+        "arch" => std::env::consts::ARCH.to_string(),
+        "cpus" => std::thread::available_parallelism()
+            .map_or(1, |n| n.get())
+            .to_string(),
+        "task" => ctx.task.to_string(),
+        _ => bail!(
+            "Unknown built-in variable \"{{{{plz.{name}}}}}\". Available: root, git_root, os, arch, cpus, task"
+        ),
+    })
+}
+
+/// A placeholder that can't be resolved where plz is running: an unset
+/// `{{env.X}}` or `{{plz.git_root}}` outside a repository. Only the tasks
+/// using it fail, when they run.
+#[derive(Debug)]
+struct Unavailable(String);
+
+impl fmt::Display for Unavailable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Unavailable {}
+
+fn find_git_root(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .map(Path::to_path_buf)
+}
+
+/// Names of other vars referenced by `{{name}}` in a var's value or command.
+fn var_references(value: &VarValue, vars: &HashMap<String, VarValue>) -> Vec<String> {
+    let mut refs = Vec::new();
+    let mut rest = value.text();
+    while let Some(start) = rest.find("{{")
+        && let Some(end) = rest[start + 2..].find("}}")
+    {
+        let name = &rest[start + 2..start + 2 + end];
+        if vars.contains_key(name) {
+            refs.push(name.to_string());
+        }
+        rest = &rest[start + 2 + end + 2..];
+    }
+    refs
+}
+
+/// Check `vars`, defined under `table` in `file`, for vars referencing each
+/// other in a loop.
+fn detect_var_cycles(vars: &HashMap<String, VarValue>, file: &Path, table: &[&str]) -> Result<()> {
+    let adj: HashMap<String, Vec<String>> = vars
+        .iter()
+        .map(|(name, value)| (name.clone(), var_references(value, vars)))
+        .collect();
+
+    if let Some(cycle) = find_cycle(&adj) {
+        let keys: Vec<&str> = table.iter().copied().chain([cycle[0].as_str()]).collect();
+        return Err(error_at(
+            file,
+            &keys,
+            format!("Circular variable reference: {}", cycle.join(" → ")),
+        ));
+    }
+    Ok(())
+}
+
+fn substitute_task_vars(task: &mut Task, ctx: &Interpolation) -> Result<(), FieldError> {
+    // Keep dynamic vars the task already carries (e.g. from a workspace member)
+    let mut vars = ctx.vars.clone();
+    for (name, var) in &task.dynamic_vars {
+        vars.entry(name.clone()).or_insert_with(|| var.clone());
+    }
+    let arg_names: Vec<String> = task.args.iter().flatten().map(|a| a.name.clone()).collect();
+    let ctx = &Interpolation {
+        vars: &vars,
+        root: ctx.root,
+        task: ctx.task,
+        args: &arg_names,
+    };
+    let substitute = |input: &str, field: &[&str]| {
+        substitute_vars(input, ctx)
+            .map_err(|e| field_error(field, e.context(format!("In task \"{}\"", ctx.task))))
+    };
+
+    let run_fields = [
+        ("run", task.run.as_mut().map(|r| &mut r.0)),
+        ("run_serial", task.run_serial.as_mut()),
+        ("run_parallel", task.run_parallel.as_mut()),
+    ];
+    for (field, cmds) in run_fields {
+        for (i, cmd) in cmds.into_iter().flatten().enumerate() {
+            *cmd = substitute(cmd, &[field, &i.to_string()])?;
+        }
+    }
+    if let Some(ref mut script) = task.script {
+        *script = substitute(script, &["script"])?;
+    }
+    if let Some(ref mut environment) = task.environment {
+        for (key, value) in environment.iter_mut() {
+            *value.text_mut() = substitute(value.text(), &["environment", key])?;
+        }
+    }
+    if let Some(ref mut dir) = task.dir {
+        *dir = substitute(dir, &["dir"])?;
+    }
+    if let Some(ref mut hook) = task.fail_hook {
+        match hook {
+            FailHook::Command(cmd) => *cmd = substitute(cmd, &["fail_hook"])?,
+            FailHook::Suggest { suggest_command } => {
+                *suggest_command = substitute(suggest_command, &["fail_hook", "suggest_command"])?;
+            }
+            FailHook::Message(msg) => *msg = substitute(msg, &["fail_hook", "message"])?,
+        }
+    }
+
+    for (name, value) in &vars {
+        if value.is_dynamic() {
+            let mut var = value.clone();
+            *var.text_mut() = substitute_vars(value.text(), ctx)
+                .with_context(|| format!("In var \"{name}\""))
+                .map_err(|e| field_error(&[], e.context(format!("In task \"{}\"", ctx.task))))?;
+            task.dynamic_vars.insert(name.clone(), var);
+        }
+    }
+    Ok(())
+}
+
+/// A problem with one field of a task, with the key path below the task's
+/// table (e.g. `["depends", "1"]`) so the error can point at it.
+struct FieldError {
+    field: Vec<String>,
+    error: anyhow::Error,
+}
+
+fn field_error(field: &[&str], error: anyhow::Error) -> FieldError {
+    FieldError {
+        field: field.iter().map(|s| s.to_string()).collect(),
+        error,
+    }
+}
+
+/// Report a failed substitution in the task `key`. When it only failed
+/// because of an [`Unavailable`] placeholder, the task keeps the error to
+/// fail with when it runs, so unrelated tasks still work.
+fn substitution_error(
+    sources: &HashMap<String, PathBuf>,
+    key: &str,
+    task: &mut Task,
+    error: FieldError,
+    diagnostics: &mut Diagnostics,
+) -> Result<()> {
+    let unavailable = error.error.downcast_ref::<Unavailable>().is_some();
+    let error = task_error(sources, key, task, error);
+    if unavailable {
+        task.unavailable = Some(Diagnostic::from_error(&error));
+        return Ok(());
+    }
+    diagnostics.error(error)
+}
+
+/// Find `keys` in a config file. Only counts when at least `required` keys
+/// matched, so a task missing from the file doesn't point at `[tasks]`.
+fn find_location(file: &Path, keys: &[&str], required: usize) -> Option<Location> {
+    let content = std::fs::read_to_string(file).ok()?;
+    Location::find(file, &content, keys)
+        .filter(|(_, depth)| *depth >= required)
+        .map(|(location, _)| location)
+}
+
+/// An error pointing at `keys` in `file`.
+fn error_at(file: &Path, keys: &[&str], message: String) -> anyhow::Error {
+    Diagnostic::new(message, find_location(file, keys, 1)).into()
+}
+
+/// An error about a field of the task `key` ("task" or "group.task"),
+/// pointing at where the field is set.
+fn task_error(
+    sources: &HashMap<String, PathBuf>,
+    key: &str,
+    task: &Task,
+    FieldError { field, error }: FieldError,
+) -> anyhow::Error {
+    let location = task_location(sources, key, task, &field);
+    Diagnostic::new(format!("{error:#}"), location).into()
+}
+
+/// Where `field` of the task `key` is set, or the task's table for an empty
+/// `field`. Inherited fields point at the task they came from.
+fn task_location(
+    sources: &HashMap<String, PathBuf>,
+    key: &str,
+    task: &Task,
+    field: &[String],
+) -> Option<Location> {
+    let key = field
+        .first()
+        .and_then(|f| task.inherited.get(f.as_str()))
+        .map_or(key, String::as_str);
+    // Workspace members define their tasks under [tasks] in their own file
+    let tables = match key.split_once('.') {
+        Some((group, name)) => vec![vec!["taskgroup", group, name], vec!["tasks", name]],
+        None => vec![vec!["tasks", key]],
+    };
+    let file = sources.get(key)?;
+    tables.into_iter().find_map(|mut keys| {
+        let required = keys.len();
+        keys.extend(field.iter().map(String::as_str));
+        find_location(file, &keys, required)
+    })
+}
+
+/// Fill in environment variables from `defaults` that aren't already set.
+fn merge_environment(
+    environment: &mut Option<HashMap<String, VarValue>>,
+    defaults: Option<&HashMap<String, VarValue>>,
+) {
+    let Some(defaults) = defaults else {
+        return;
+    };
+    let environment = environment.get_or_insert_with(HashMap::new);
+    for (key, value) in defaults {
+        environment
+            .entry(key.clone())
+            .or_insert_with(|| value.clone());
+    }
+}
+
+fn format_location(path: &str) -> String {
+    if path.is_empty() {
+        "plz.toml".to_string()
+    } else {
         let parts: Vec<&str> = path.split('/').filter(|s: &&str| !s.is_empty()).collect();
         format!("[{}]", parts.join("."))
     }
 }
 
 fn collect_additional_properties_errors<'a>(
-    warnings: &mut Vec<String>,
+    unknown: &mut Vec<(String, String)>,
     errors: impl Iterator<Item = jsonschema::ValidationError<'a>>,
 ) {
     use jsonschema::error::ValidationErrorKind;
     for error in errors {
         match error.kind() {
             ValidationErrorKind::AdditionalProperties { unexpected } => {
-                let location = format_location(&error.instance_path().to_string());
+                let table = error.instance_path().to_string();
                 for key in unexpected {
-                    warnings.push(format!("unknown key \"{key}\" in {location}"));
+                    unknown.push((table.clone(), key.clone()));
                 }
             }
             ValidationErrorKind::AnyOf { context } => {
@@ -419,125 +1531,1282 @@ fn collect_additional_properties_errors<'a>(
                         if let ValidationErrorKind::AdditionalProperties { unexpected } =
                             inner.kind()
                         {
-                            let location = format_location(&inner.instance_path().to_string());
+                            let table = inner.instance_path().to_string();
                             for key in unexpected {
-                                warnings.push(format!("unknown key \"{key}\" in {location}"));
+                                unknown.push((table.clone(), key.clone()));
                             }
                         }
                     }
                 }
             }
-            _ => {}
+            _ => {}
+        }
+    }
+}
+
+/// Warnings for keys plz doesn't know in `doc`, such as `unknown key "oops"
+/// in [tasks.hello]`. Loading reports these with their location in
+/// [`PlzConfig::warnings`].
+pub fn warn_unknown_keys(doc: &DocumentMut) -> Vec<String> {
+    unknown_keys(doc)
+        .into_iter()
+        .map(|(table, key)| unknown_key_message(&table, &key))
+        .collect()
+}
+
+fn unknown_key_message(table: &str, key: &str) -> String {
+    format!("unknown key \"{key}\" in {}", format_location(table))
+}
+
+/// Keys the schema doesn't know, as (JSON pointer to their table, key).
+fn unknown_keys(doc: &DocumentMut) -> Vec<(String, String)> {
+    let schema = schemars::schema_for!(PlzConfig);
+    let schema_json = serde_json::to_value(&schema).expect("schema serializes to JSON");
+    let Ok(validator) = jsonschema::validator_for(&schema_json) else {
+        return Vec::new();
+    };
+
+    let toml_str = doc.to_string();
+    let json_value: serde_json::Value = match toml_edit::de::from_str(&toml_str) {
+        Ok(v) => v,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut unknown = Vec::new();
+    collect_additional_properties_errors(&mut unknown, validator.iter_errors(&json_value));
+    unknown
+}
+
+/// Find the nearest plz.toml by walking up from `start`, like cargo does for
+/// Cargo.toml. The search stops at the git root (a directory containing
+/// `.git`) or the filesystem root.
+pub fn find_config(start: &Path) -> Option<PathBuf> {
+    let mut dir = start;
+    loop {
+        for name in CONFIG_NAMES {
+            let path = dir.join(name);
+            if path.is_file() {
+                return Some(path);
+            }
+        }
+        if dir.join(".git").exists() {
+            return None;
+        }
+        dir = dir.parent()?;
+    }
+}
+
+/// Read a single TOML file into a config, without resolving includes or
+/// applying any of the load-time cascades.
+fn parse_file(path: &Path, is_include: bool, diagnostics: &mut Diagnostics) -> Result<PlzConfig> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "plz.toml".to_string());
+    let doc: DocumentMut = content.parse().map_err(|e: toml_edit::TomlError| {
+        let location = e.span().map(|span| Location::new(path, &content, span));
+        Diagnostic::new(
+            format!("Failed to parse {file_name}: {}", e.message().trim_end()),
+            location,
+        )
+    })?;
+
+    // Deserialize from the source text rather than `doc`, which has no spans
+    let mut config: PlzConfig = toml_edit::de::from_str(&content).map_err(|e| {
+        let location = e.span().map(|span| Location::new(path, &content, span));
+        Diagnostic::new(
+            format!("Failed to deserialize config: {}", e.message().trim_end()),
+            location,
+        )
+    })?;
+
+    // Warn about unknown keys
+    for (table, key) in unknown_keys(&doc) {
+        let mut message = unknown_key_message(&table, &key);
+        let mut keys: Vec<&str> = table.split('/').filter(|s| !s.is_empty()).collect();
+        keys.push(&key);
+        let location = Location::find_key(path, &content, &keys);
+        if is_include && location.is_none() {
+            message = format!("{message} ({})", path.display());
+        }
+        diagnostics.warn(Diagnostic::warning(message, location));
+    }
+
+    // Extract comments above [tasks.*] as descriptions (fallback when no explicit description)
+    if let Some(tasks_table) = doc.get("tasks").and_then(|v| v.as_table()) {
+        for (key, item) in tasks_table.iter() {
+            if let Some(task) = config.tasks.get_mut(key)
+                && task.description.is_none()
+                && let Some(decor) = item.as_table().map(|t| t.decor())
+                && let Some(prefix) = decor.prefix().and_then(|p| p.as_str())
+            {
+                task.description = extract_comment(prefix);
+            }
+        }
+    }
+
+    // Extract comments from taskgroup tables
+    if let Some(ref mut groups) = config.taskgroup {
+        for (group_name, group) in groups.iter_mut() {
+            if let Some(group_table) = doc
+                .get("taskgroup")
+                .and_then(|v| v.as_table())
+                .and_then(|t| t.get(group_name.as_str()))
+                .and_then(|v| v.as_table())
+            {
+                for (key, item) in group_table.iter() {
+                    if key == "extends" || key == "vars" {
+                        continue;
+                    }
+                    if let Some(task) = group.tasks.get_mut(key)
+                        && task.description.is_none()
+                        && let Some(decor) = item.as_table().map(|t| t.decor())
+                        && let Some(prefix) = decor.prefix().and_then(|p| p.as_str())
+                    {
+                        task.description = extract_comment(prefix);
+                    }
+                }
+            }
+        }
+    }
+
+    mark_missing_platform_commands(&mut config, &doc, path, &content);
+
+    for name in config.tasks.keys() {
+        config.task_sources.insert(name.clone(), path.to_path_buf());
+    }
+    if let Some(ref groups) = config.taskgroup {
+        for (group_name, group) in groups {
+            for task_name in group.tasks.keys() {
+                config
+                    .task_sources
+                    .insert(format!("{group_name}.{task_name}"), path.to_path_buf());
+            }
+        }
+    }
+
+    Ok(config)
+}
+
+/// Mark tasks whose only commands are per-platform tables with no entry for
+/// this platform and no `default`, so they fail when run here instead of
+/// doing nothing.
+fn mark_missing_platform_commands(
+    config: &mut PlzConfig,
+    doc: &DocumentMut,
+    path: &Path,
+    content: &str,
+) {
+    let mut tables: Vec<(Vec<&str>, &mut Task)> = config
+        .tasks
+        .iter_mut()
+        .map(|(name, task)| (vec!["tasks", name.as_str()], task))
+        .collect();
+    for (group_name, group) in config.taskgroup.iter_mut().flatten() {
+        for (name, task) in group.tasks.iter_mut() {
+            tables.push((vec!["taskgroup", group_name.as_str(), name.as_str()], task));
+        }
+    }
+    for (keys, task) in tables {
+        if task.run.is_some()
+            || task.run_serial.is_some()
+            || task.run_parallel.is_some()
+            || task.script.is_some()
+        {
+            continue;
+        }
+        let Some(table) = keys
+            .iter()
+            .try_fold(doc.as_item(), |item, key| item.get(key))
+            .and_then(|item| item.as_table_like())
+        else {
+            continue;
+        };
+        let Some(field) = ["run", "run_serial", "run_parallel"]
+            .into_iter()
+            .find(|field| table.get(field).is_some_and(|v| v.is_table_like()))
+        else {
+            continue;
+        };
+        let name = keys[1..].join(":");
+        let message = format!(
+            "Task \"{name}\" has no command for platform \"{}\"",
+            std::env::consts::OS
+        );
+        let mut keys = keys;
+        keys.push(field);
+        let location = Location::find(path, content, &keys).map(|(location, _)| location);
+        task.unavailable = Some(Diagnostic::new(message, location));
+    }
+}
+
+/// Resolve an `include` entry relative to the directory of the including file.
+fn resolve_include_path(include: &str, including_file: &Path) -> PathBuf {
+    if let Some(rest) = include.strip_prefix("~/")
+        && let Some(home) = dirs::home_dir()
+    {
+        return home.join(rest);
+    }
+    including_file
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join(include)
+}
+
+/// Merge everything listed in `[plz] include` into `config`, recursively.
+///
+/// Precedence: the including file wins over anything it includes, and later
+/// includes win over earlier ones for vars and extends. A task (or group task)
+/// defined by two different included files is an error, since neither is
+/// obviously the one to keep.
+fn resolve_includes(
+    config: &mut PlzConfig,
+    path: &Path,
+    stack: &mut Vec<PathBuf>,
+    loaded: &mut HashSet<PathBuf>,
+    diagnostics: &mut Diagnostics,
+) -> Result<()> {
+    let includes = config
+        .plz
+        .as_ref()
+        .and_then(|p| p.include.clone())
+        .unwrap_or_default();
+    if includes.is_empty() {
+        return Ok(());
+    }
+
+    let mut merged: Option<PlzConfig> = None;
+    for (i, include) in includes.iter().enumerate() {
+        let include_keys = ["plz", "include", &i.to_string()];
+        let include_path = resolve_include_path(include, path);
+        let canonical = include_path.canonicalize().map_err(|e| {
+            error_at(
+                path,
+                &include_keys,
+                format!(
+                    "Failed to read {} (included from {}): {e}",
+                    include_path.display(),
+                    path.display()
+                ),
+            )
+        })?;
+        if let Some(start) = stack.iter().position(|p| p == &canonical) {
+            let mut cycle: Vec<String> = stack[start..]
+                .iter()
+                .map(|p| p.display().to_string())
+                .collect();
+            cycle.push(canonical.display().to_string());
+            return Err(error_at(
+                path,
+                &include_keys,
+                format!("Circular include: {}", cycle.join(" → ")),
+            ));
+        }
+        // A file reachable through more than one include is only merged once
+        if !loaded.insert(canonical.clone()) {
+            continue;
+        }
+
+        let mut included = parse_file(&canonical, true, diagnostics)
+            .with_context(|| format!("In file included from {}", path.display()))?;
+        stack.push(canonical.clone());
+        resolve_includes(&mut included, &canonical, stack, loaded, diagnostics)?;
+        stack.pop();
+
+        merged = Some(match merged {
+            None => included,
+            Some(earlier) => merge_sibling_includes(earlier, included)?,
+        });
+    }
+
+    if let Some(included) = merged {
+        merge_under(config, included);
+    }
+    Ok(())
+}
+
+fn describe_source(sources: &HashMap<String, PathBuf>, key: &str) -> String {
+    sources
+        .get(key)
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|| "plz.toml".to_string())
+}
+
+/// Merge two included files. `later` wins for vars and extends; tasks
+/// defined in both are a conflict.
+fn merge_sibling_includes(mut earlier: PlzConfig, later: PlzConfig) -> Result<PlzConfig> {
+    for (name, task) in later.tasks {
+        if earlier.tasks.contains_key(&name) {
+            let message = format!(
+                "Task \"{name}\" is defined in both {} and {}",
+                describe_source(&earlier.task_sources, &name),
+                describe_source(&later.task_sources, &name),
+            );
+            let location = later
+                .task_sources
+                .get(&name)
+                .and_then(|file| find_location(file, &["tasks", &name], 2));
+            return Err(Diagnostic::new(message, location).into());
+        }
+        earlier.tasks.insert(name, task);
+    }
+    for (group_name, group) in later.taskgroup.unwrap_or_default() {
+        let groups = earlier.taskgroup.get_or_insert_with(HashMap::new);
+        match groups.get_mut(&group_name) {
+            None => {
+                groups.insert(group_name, group);
+            }
+            Some(existing) => {
+                for (task_name, task) in group.tasks {
+                    let key = format!("{group_name}.{task_name}");
+                    if existing.tasks.contains_key(&task_name) {
+                        let message = format!(
+                            "Task \"{group_name}:{task_name}\" is defined in both {} and {}",
+                            describe_source(&earlier.task_sources, &key),
+                            describe_source(&later.task_sources, &key),
+                        );
+                        let location = later.task_sources.get(&key).and_then(|file| {
+                            find_location(file, &["taskgroup", &group_name, &task_name], 3)
+                        });
+                        return Err(Diagnostic::new(message, location).into());
+                    }
+                    existing.tasks.insert(task_name, task);
+                }
+                let mut extends = group.extends;
+                fill_global_settings(&mut extends, existing.extends.take());
+                existing.extends = extends;
+                let mut vars = group.vars;
+                fill_vars(&mut vars, existing.vars.take());
+                existing.vars = vars;
+            }
+        }
+    }
+    earlier.task_sources.extend(later.task_sources);
+
+    let mut vars = later.vars;
+    fill_vars(&mut vars, earlier.vars.take());
+    earlier.vars = vars;
+    let mut extends = later.extends;
+    fill_global_settings(&mut extends, earlier.extends.take());
+    earlier.extends = extends;
+    Ok(earlier)
+}
+
+/// Merge `included` into `config`, keeping everything `config` already defines.
+fn merge_under(config: &mut PlzConfig, included: PlzConfig) {
+    for (name, task) in included.tasks {
+        if let Entry::Vacant(entry) = config.tasks.entry(name) {
+            if let Some(source) = included.task_sources.get(entry.key()) {
+                config
+                    .task_sources
+                    .insert(entry.key().clone(), source.clone());
+            }
+            entry.insert(task);
+        }
+    }
+    for (group_name, group) in included.taskgroup.unwrap_or_default() {
+        let groups = config.taskgroup.get_or_insert_with(HashMap::new);
+        let existing = groups
+            .entry(group_name.clone())
+            .or_insert_with(|| TaskGroup {
+                extends: None,
+                vars: None,
+                tasks: HashMap::new(),
+                workspace_dir: None,
+            });
+        for (task_name, task) in group.tasks {
+            if let Entry::Vacant(entry) = existing.tasks.entry(task_name) {
+                let key = format!("{group_name}.{}", entry.key());
+                if let Some(source) = included.task_sources.get(&key) {
+                    config.task_sources.insert(key, source.clone());
+                }
+                entry.insert(task);
+            }
+        }
+        fill_global_settings(&mut existing.extends, group.extends);
+        fill_vars(&mut existing.vars, group.vars);
+    }
+    fill_vars(&mut config.vars, included.vars);
+    fill_global_settings(&mut config.extends, included.extends);
+}
+
+/// Fill in vars from `lower` that `vars` doesn't already define.
+fn fill_vars(
+    vars: &mut Option<HashMap<String, VarValue>>,
+    lower: Option<HashMap<String, VarValue>>,
+) {
+    let Some(lower) = lower else {
+        return;
+    };
+    let vars = vars.get_or_insert_with(HashMap::new);
+    for (key, value) in lower {
+        vars.entry(key).or_insert(value);
+    }
+}
+
+/// Fill in extends fields from `lower` that `settings` doesn't already set.
+fn fill_global_settings(settings: &mut Option<GlobalSettings>, lower: Option<GlobalSettings>) {
+    if let Some(lower) = lower {
+        settings.get_or_insert_default().fill_from(&lower);
+    }
+}
+
+/// Merge the `plz.local.toml` next to `path`, if there is one, on top of
+/// `config`:
+///
+/// - tasks and group tasks it also defines get every field it sets, with
+///   `environment` merged by key; new tasks are added
+/// - vars and `[extends]` fields it sets win
+/// - `[plz]`, `[workspace]` and `[healthcheck]` are ignored
+///
+/// When checking, each override is reported as a note.
+fn merge_local_config(
+    config: &mut PlzConfig,
+    path: &Path,
+    diagnostics: &mut Diagnostics,
+) -> Result<()> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let Some(local_path) = LOCAL_CONFIG_NAMES
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|p| p.is_file())
+    else {
+        return Ok(());
+    };
+    let local = parse_file(&local_path, true, diagnostics)?;
+    let file_name = local_path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    for (section, set) in [
+        ("plz", local.plz.is_some()),
+        ("workspace", local.workspace.is_some()),
+        ("healthcheck", local.healthcheck.is_some()),
+    ] {
+        if set {
+            let location = find_location(&local_path, &[section], 1);
+            diagnostics.warn(Diagnostic::warning(
+                format!("[{section}] is ignored in {file_name}. Set it in plz.toml instead."),
+                location,
+            ));
+        }
+    }
+
+    let mut note = |keys: &[&str], message: String| {
+        if !diagnostics.collect {
+            return;
+        }
+        let location = find_location(&local_path, keys, keys.len());
+        diagnostics.note(Diagnostic::note(format!("{file_name} {message}"), location));
+    };
+
+    for (name, task) in local.tasks {
+        match config.tasks.entry(name) {
+            Entry::Occupied(mut entry) => {
+                let fields = overlay_task(entry.get_mut(), task);
+                let name = entry.key();
+                note(
+                    &["tasks", name],
+                    format!("overrides task \"{name}\": {}", fields.join(", ")),
+                );
+            }
+            Entry::Vacant(entry) => {
+                note(
+                    &["tasks", entry.key()],
+                    format!("adds task \"{}\"", entry.key()),
+                );
+                config
+                    .task_sources
+                    .insert(entry.key().clone(), local_path.clone());
+                entry.insert(task);
+            }
+        }
+    }
+
+    for (group_name, group) in local.taskgroup.unwrap_or_default() {
+        let groups = config.taskgroup.get_or_insert_with(HashMap::new);
+        let existing = groups
+            .entry(group_name.clone())
+            .or_insert_with(|| TaskGroup {
+                extends: None,
+                vars: None,
+                tasks: HashMap::new(),
+                workspace_dir: None,
+            });
+        for (task_name, task) in group.tasks {
+            let keys = ["taskgroup", group_name.as_str(), task_name.as_str()];
+            let display = format!("{group_name}:{task_name}");
+            match existing.tasks.entry(task_name.clone()) {
+                Entry::Occupied(mut entry) => {
+                    let fields = overlay_task(entry.get_mut(), task);
+                    note(
+                        &keys,
+                        format!("overrides task \"{display}\": {}", fields.join(", ")),
+                    );
+                }
+                Entry::Vacant(entry) => {
+                    note(&keys, format!("adds task \"{display}\""));
+                    config
+                        .task_sources
+                        .insert(format!("{group_name}.{task_name}"), local_path.clone());
+                    entry.insert(task);
+                }
+            }
+        }
+        if group.extends.is_some() {
+            note(
+                &["taskgroup", &group_name, "extends"],
+                format!("overrides [taskgroup.{group_name}.extends]"),
+            );
+        }
+        let mut extends = group.extends;
+        fill_global_settings(&mut extends, existing.extends.take());
+        existing.extends = extends;
+        for name in group.vars.iter().flat_map(HashMap::keys) {
+            note(
+                &["taskgroup", &group_name, "vars", name],
+                format!("sets var \"{name}\" in task group \"{group_name}\""),
+            );
+        }
+        let mut vars = group.vars;
+        fill_vars(&mut vars, existing.vars.take());
+        existing.vars = vars;
+    }
+
+    for name in local.vars.iter().flat_map(HashMap::keys) {
+        note(&["vars", name], format!("sets var \"{name}\""));
+    }
+    let mut vars = local.vars;
+    fill_vars(&mut vars, config.vars.take());
+    config.vars = vars;
+
+    if local.extends.is_some() {
+        note(&["extends"], "overrides [extends]".to_string());
+    }
+    let mut extends = local.extends;
+    fill_global_settings(&mut extends, config.extends.take());
+    config.extends = extends;
+    Ok(())
+}
+
+/// Pick the profile to load and layer its vars over `[vars]` and task group
+/// vars. Its environment is applied once `[extends]` has been.
+fn select_profile(
+    config: &mut PlzConfig,
+    path: &Path,
+    choice: ProfileChoice,
+    diagnostics: &mut Diagnostics,
+) -> Result<()> {
+    let default = config.plz.as_ref().and_then(|p| p.default_profile.clone());
+    let (name, keys): (&str, &[&str]) = match choice {
+        ProfileChoice::Named(name) | ProfileChoice::IfDefined(name) => (name, &[]),
+        ProfileChoice::Default => match default {
+            Some(ref name) => (name, &["plz", "default_profile"]),
+            None => return Ok(()),
+        },
+    };
+    let Some(profile) = config.profile.as_ref().and_then(|p| p.get(name)).cloned() else {
+        if matches!(choice, ProfileChoice::IfDefined(_)) {
+            return Ok(());
+        }
+        let mut names: Vec<&str> = config
+            .profile
+            .iter()
+            .flatten()
+            .map(|(n, _)| n.as_str())
+            .collect();
+        names.sort();
+        let defined = if names.is_empty() {
+            "No profiles are defined".to_string()
+        } else {
+            format!("Profiles: {}", names.join(", "))
+        };
+        let message = format!("Unknown profile \"{name}\". {defined}");
+        let location = find_location(path, keys, keys.len());
+        return diagnostics.error(Diagnostic::new(message, location).into());
+    };
+
+    if let Some(vars) = profile.vars {
+        for group in config.taskgroup.iter_mut().flat_map(HashMap::values_mut) {
+            if let Some(ref mut group_vars) = group.vars {
+                group_vars.retain(|key, _| !vars.contains_key(key));
+            }
+        }
+        config.vars.get_or_insert_default().extend(vars);
+    }
+    config.active_profile = Some(name.to_string());
+    Ok(())
+}
+
+/// Record `dir`, the directory of the config being loaded, on every `sh`
+/// and `file` value it defines. Values from workspace members keep their
+/// own directory.
+fn set_var_dirs(config: &mut PlzConfig, dir: &Path) {
+    let set = |vars: Option<&mut HashMap<String, VarValue>>| {
+        for var in vars.into_iter().flat_map(HashMap::values_mut) {
+            var.set_dir(dir);
+        }
+    };
+    set(config.vars.as_mut());
+    set(config.extends.as_mut().and_then(|e| e.environment.as_mut()));
+    for profile in config.profile.iter_mut().flat_map(HashMap::values_mut) {
+        set(profile.vars.as_mut());
+        set(profile.environment.as_mut());
+    }
+    for group in config.taskgroup.iter_mut().flat_map(HashMap::values_mut) {
+        set(group.vars.as_mut());
+        set(group.extends.as_mut().and_then(|e| e.environment.as_mut()));
+        for task in group.tasks.values_mut() {
+            set(task.environment.as_mut());
+        }
+    }
+    for task in config.tasks.values_mut() {
+        set(task.environment.as_mut());
+    }
+}
+
+/// Set the active profile's `environment` on every task, over their own.
+fn apply_profile_environment(config: &mut PlzConfig) {
+    let Some(environment) = config
+        .active_profile
+        .as_ref()
+        .and_then(|name| config.profile.as_ref()?.get(name)?.environment.clone())
+    else {
+        return;
+    };
+    let groups = config.taskgroup.iter_mut().flat_map(HashMap::values_mut);
+    let tasks = config
+        .tasks
+        .values_mut()
+        .chain(groups.flat_map(|g| g.tasks.values_mut()));
+    for task in tasks {
+        task.environment
+            .get_or_insert_default()
+            .extend(environment.clone());
+    }
+}
+
+/// Replace every field of `task` that `local` sets, merging `environment`
+/// by key. Returns the fields it set.
+fn overlay_task(task: &mut Task, local: Task) -> Vec<&'static str> {
+    // Commands and a script don't mix, so setting one replaces all of them
+    if local.run.is_some()
+        || local.run_serial.is_some()
+        || local.run_parallel.is_some()
+        || local.script.is_some()
+    {
+        task.run = None;
+        task.run_serial = None;
+        task.run_parallel = None;
+        task.script = None;
+        task.unavailable = local.unavailable.clone();
+    }
+    let mut fields = Vec::new();
+    macro_rules! overlay {
+        ($($field:ident),*) => {
+            $(
+                if local.$field.is_some() {
+                    fields.push(stringify!($field));
+                    task.$field = local.$field;
+                }
+            )*
+        };
+    }
+    overlay!(
+        extends,
+        run,
+        run_serial,
+        run_parallel,
+        script,
+        interpreter,
+        depends,
+        tool_env,
+        shell,
+        dir,
+        timeout,
+        retry,
+        output,
+        max_parallel,
+        fail_fast,
+        platforms,
+        run_if,
+        skip_if,
+        env_file,
+        fail_hook,
+        description,
+        git_hook,
+        aliases,
+        args
+    );
+    if local.hide_setting.is_some() {
+        fields.push("hide");
+        task.hide_setting = local.hide_setting;
+    }
+    if local.environment.is_some() {
+        fields.push("environment");
+        let mut environment = local.environment;
+        merge_environment(&mut environment, task.environment.as_ref());
+        task.environment = environment;
+    }
+    // `git_hook = ""` takes a task out of the git hooks on this machine
+    if task.git_hook.as_deref() == Some("") {
+        task.git_hook = None;
+    }
+    fields
+}
+
+/// Load each `[workspace] members` directory's plz.toml as a task group named
+/// after the directory, with `dir` pointing at the member.
+fn load_workspace_members(
+    config: &mut PlzConfig,
+    path: &Path,
+    diagnostics: &mut Diagnostics,
+) -> Result<()> {
+    let Some(ref workspace) = config.workspace else {
+        return Ok(());
+    };
+    let base_dir = path.parent().unwrap_or(Path::new("."));
+    let escaped_base = glob::Pattern::escape(&base_dir.to_string_lossy());
+    let excludes = workspace
+        .exclude
+        .iter()
+        .map(|p| {
+            glob::Pattern::new(p)
+                .with_context(|| format!("[workspace]: invalid exclude pattern \"{p}\""))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut member_dirs: Vec<String> = Vec::new();
+    for pattern in &workspace.members {
+        let full = format!("{escaped_base}/{pattern}");
+        let entries = glob::glob(&full)
+            .with_context(|| format!("[workspace]: invalid members pattern \"{pattern}\""))?;
+        for entry in entries.flatten() {
+            if !entry.is_dir() {
+                continue;
+            }
+            let Ok(rel) = entry.strip_prefix(base_dir) else {
+                continue;
+            };
+            let rel = rel.to_string_lossy().into_owned();
+            if excludes.iter().any(|p| p.matches(&rel)) || member_dirs.contains(&rel) {
+                continue;
+            }
+            member_dirs.push(rel);
+        }
+    }
+    member_dirs.sort();
+
+    let mut member_sources: HashMap<String, String> = HashMap::new();
+    for rel in member_dirs {
+        let member_dir = base_dir.join(&rel);
+        let Some(member_path) = CONFIG_NAMES
+            .iter()
+            .map(|name| member_dir.join(name))
+            .find(|p| p.is_file())
+        else {
+            continue;
+        };
+        let group_name = member_dir
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| rel.clone());
+
+        if let Some(other) = member_sources.get(&group_name) {
+            return Err(error_at(
+                path,
+                &["workspace", "members"],
+                format!(
+                    "[workspace]: members \"{other}\" and \"{rel}\" would both be named \"{group_name}\""
+                ),
+            ));
+        }
+        if config.get_group(&group_name).is_some() {
+            return Err(error_at(
+                path,
+                &["workspace", "members"],
+                format!(
+                    "[workspace]: member \"{rel}\" conflicts with existing [taskgroup.{group_name}]"
+                ),
+            ));
+        }
+
+        let profile = match config.active_profile {
+            Some(ref name) => ProfileChoice::IfDefined(name),
+            None => ProfileChoice::Default,
+        };
+        // The root's [extends] sits below the member's own. Members always
+        // run in their own directory, and the root's env_file stays relative
+        // to the root.
+        let mut defaults = config.extends.clone().unwrap_or_default();
+        defaults.dir = None;
+        if let Some(ref mut env_file) = defaults.env_file
+            && !env_file.is_empty()
+        {
+            let up = Path::new(&rel).components().map(|_| "..");
+            *env_file = up.chain([env_file.as_str()]).collect::<Vec<_>>().join("/");
+        }
+        let member = load_with(&member_path, profile, Some(&defaults), diagnostics)
+            .with_context(|| format!("In workspace member {}", member_path.display()))?;
+        if member.taskgroup.as_ref().is_some_and(|g| !g.is_empty()) {
+            diagnostics.warn(Diagnostic::warning(
+                format!("task groups in workspace member \"{rel}\" are ignored"),
+                find_location(&member_path, &["taskgroup"], 1),
+            ));
+        }
+
+        let mut tasks = HashMap::new();
+        for (task_name, mut task) in member.tasks {
+            task.dir = Some(join_relative(&rel, task.dir.as_deref()));
+            if let Some(ref env_file) = task.env_file {
+                task.env_file = Some(join_relative(&rel, Some(env_file)));
+            }
+            // Refer to sibling tasks through the group they now live in
+            if let Some(ref mut deps) = task.depends {
+                for dep in &mut deps.0 {
+                    if !dep.contains('.') {
+                        *dep = format!("{group_name}.{dep}");
+                    }
+                }
+            }
+            if let Some(ref mut parent) = task.extends
+                && !parent.contains('.')
+            {
+                *parent = format!("{group_name}.{parent}");
+            }
+            for source in task.inherited.values_mut() {
+                if !source.contains('.') {
+                    *source = format!("{group_name}.{source}");
+                }
+            }
+            for cmds in [
+                task.run.as_mut().map(|r| &mut r.0),
+                task.run_serial.as_mut(),
+                task.run_parallel.as_mut(),
+            ]
+            .into_iter()
+            .flatten()
+            {
+                for cmd in cmds {
+                    if let Some(name) = cmd.strip_prefix("plz:")
+                        && !name.contains(':')
+                    {
+                        *cmd = format!("plz:{group_name}:{name}");
+                    }
+                }
+            }
+            if let Some(source) = member.task_sources.get(&task_name) {
+                config
+                    .task_sources
+                    .insert(format!("{group_name}.{task_name}"), source.clone());
+            }
+            tasks.insert(task_name, task);
+        }
+
+        config.taskgroup.get_or_insert_with(HashMap::new).insert(
+            group_name.clone(),
+            TaskGroup {
+                extends: None,
+                vars: None,
+                tasks,
+                workspace_dir: Some(rel.clone()),
+            },
+        );
+        member_sources.insert(group_name, rel);
+    }
+    Ok(())
+}
+
+fn task_by_key<'a>(config: &'a PlzConfig, key: &str) -> Option<&'a Task> {
+    match key.split_once('.') {
+        Some((group, task)) => config.get_group_task(group, task),
+        None => config.tasks.get(key),
+    }
+}
+
+fn task_by_key_mut<'a>(config: &'a mut PlzConfig, key: &str) -> Option<&'a mut Task> {
+    match key.split_once('.') {
+        Some((group, task)) => config
+            .taskgroup
+            .as_mut()?
+            .get_mut(group)?
+            .tasks
+            .get_mut(task),
+        None => config.tasks.get_mut(key),
+    }
+}
+
+/// Apply task-level `extends`: each task inherits every field it doesn't set
+/// from its parent, after the parent has inherited from its own.
+fn resolve_task_extends(config: &mut PlzConfig, diagnostics: &mut Diagnostics) -> Result<()> {
+    let mut parents: HashMap<String, Vec<String>> = HashMap::new();
+    let mut keys: Vec<String> = config.tasks.keys().cloned().collect();
+    for (group_name, group) in config.taskgroup.iter().flatten() {
+        keys.extend(group.tasks.keys().map(|t| format!("{group_name}.{t}")));
+    }
+    keys.sort();
+    for key in &keys {
+        if let Some(task) = task_by_key(config, key)
+            && let Some(ref parent) = task.extends
+        {
+            if task_by_key(config, parent).is_none() {
+                let error =
+                    anyhow!("Task \"{key}\" extends \"{parent}\", but no task \"{parent}\" exists");
+                diagnostics.error(task_error(
+                    &config.task_sources,
+                    key,
+                    task,
+                    field_error(&["extends"], error),
+                ))?;
+                continue;
+            }
+            parents.insert(key.clone(), vec![parent.clone()]);
         }
     }
+
+    if let Some(cycle) = find_cycle(&parents)
+        && let Some(task) = task_by_key(config, &cycle[0])
+    {
+        let error = anyhow!("Circular extends: {}", cycle.join(" → "));
+        diagnostics.error(task_error(
+            &config.task_sources,
+            &cycle[0],
+            task,
+            field_error(&["extends"], error),
+        ))?;
+        // Inheriting around the cycle would never finish
+        return Ok(());
+    }
+
+    let mut done = HashSet::new();
+    for key in &keys {
+        inherit_task(config, key, &parents, &mut done);
+    }
+    Ok(())
 }
 
-pub fn warn_unknown_keys(doc: &DocumentMut) -> Vec<String> {
-    let schema = schemars::schema_for!(PlzConfig);
-    let schema_json = serde_json::to_value(&schema).expect("schema serializes to JSON");
-    let Ok(validator) = jsonschema::validator_for(&schema_json) else {
-        return Vec::new();
+fn inherit_task(
+    config: &mut PlzConfig,
+    key: &str,
+    parents: &HashMap<String, Vec<String>>,
+    done: &mut HashSet<String>,
+) {
+    if !done.insert(key.to_string()) {
+        return;
+    }
+    let Some(parent_key) = parents.get(key).and_then(|p| p.first()) else {
+        return;
+    };
+    inherit_task(config, parent_key, parents, done);
+    let Some(parent) = task_by_key(config, parent_key).cloned() else {
+        return;
+    };
+    let Some(task) = task_by_key_mut(config, key) else {
+        return;
     };
 
-    let toml_str = doc.to_string();
-    let json_value: serde_json::Value = match toml_edit::de::from_str(&toml_str) {
-        Ok(v) => v,
-        Err(_) => return Vec::new(),
+    // Record the task that originally defined each inherited field
+    let mut inherit = |field: &'static str, missing: bool| {
+        if missing {
+            let source = parent
+                .inherited
+                .get(field)
+                .cloned()
+                .unwrap_or_else(|| parent_key.clone());
+            task.inherited.insert(field, source);
+        }
+        missing
     };
+    macro_rules! inherit_fields {
+        ($($field:ident),*) => {
+            $(
+                if inherit(stringify!($field), task.$field.is_none() && parent.$field.is_some()) {
+                    task.$field.clone_from(&parent.$field);
+                }
+            )*
+        };
+    }
+    // Commands and a script don't mix, so a task that sets one doesn't inherit the other
+    if task.script.is_none() {
+        inherit_fields!(run, run_serial, run_parallel);
+    }
+    if task.run.is_none() && task.run_serial.is_none() && task.run_parallel.is_none() {
+        inherit_fields!(script, interpreter);
+    }
+    inherit_fields!(
+        depends,
+        tool_env,
+        shell,
+        dir,
+        timeout,
+        retry,
+        output,
+        max_parallel,
+        fail_fast,
+        platforms,
+        run_if,
+        skip_if,
+        env_file,
+        fail_hook,
+        description,
+        git_hook,
+        args
+    );
+    if parent.environment.is_some() {
+        inherit("environment", task.environment.is_none());
+        merge_environment(&mut task.environment, parent.environment.as_ref());
+    }
+}
+
+fn join_relative(base: &str, path: Option<&str>) -> String {
+    match path {
+        Some(p) if !p.is_empty() && p != "." => {
+            Path::new(base).join(p).to_string_lossy().into_owned()
+        }
+        _ => base.to_string(),
+    }
+}
+
+/// Where problems found while loading go: `load` keeps warnings and stops
+/// at the first error, `check` collects everything.
+#[derive(Default)]
+struct Diagnostics {
+    collect: bool,
+    found: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    fn warn(&mut self, warning: Diagnostic) {
+        self.found.push(warning);
+    }
+
+    /// Notes are only worth showing when checking.
+    fn note(&mut self, note: Diagnostic) {
+        if self.collect {
+            self.found.push(note);
+        }
+    }
 
-    let mut warnings = Vec::new();
-    collect_additional_properties_errors(&mut warnings, validator.iter_errors(&json_value));
-    warnings
+    /// Record an error that loading can continue past. Only returns it when
+    /// not collecting.
+    fn error(&mut self, err: anyhow::Error) -> Result<()> {
+        if !self.collect {
+            return Err(err);
+        }
+        self.found.push(Diagnostic::from_error(&err));
+        Ok(())
+    }
+
+    /// Like `error`, for a check's result. `Ok(false)` when the check failed
+    /// and the error was collected.
+    fn record(&mut self, result: Result<()>) -> Result<bool> {
+        let passed = result.is_ok();
+        if let Err(err) = result {
+            self.error(err)?;
+        }
+        Ok(passed)
+    }
 }
 
+/// Load the config at `path`, with the profile named by `PLZ_PROFILE` or
+/// `default_profile`.
 pub fn load(path: &Path) -> Result<PlzConfig> {
-    let content = std::fs::read_to_string(path)
-        .with_context(|| format!("Failed to read {}", path.display()))?;
+    load_profile(path, None)
+}
 
-    let doc: DocumentMut = content
-        .parse()
-        .with_context(|| "Failed to parse plz.toml")?;
+/// Load the config at `path` with the named profile, falling back to
+/// `PLZ_PROFILE` and then `default_profile` when `profile` is `None`.
+pub fn load_profile(path: &Path, profile: Option<&str>) -> Result<PlzConfig> {
+    let from_env = std::env::var("PLZ_PROFILE").ok();
+    let mut diagnostics = Diagnostics::default();
+    let mut config = load_with(
+        path,
+        profile_choice(profile, from_env.as_deref()),
+        None,
+        &mut diagnostics,
+    )?;
+    warn_version(&config, path, &mut diagnostics);
+    config.warnings = diagnostics.found;
+    Ok(config)
+}
 
-    let mut config: PlzConfig = toml_edit::de::from_document(doc.clone())
-        .with_context(|| "Failed to deserialize config")?;
+/// `profile`, or else a non-empty `PLZ_PROFILE`, or else `default_profile`.
+fn profile_choice<'a>(profile: Option<&'a str>, from_env: Option<&'a str>) -> ProfileChoice<'a> {
+    match profile.or(from_env.filter(|p| !p.is_empty())) {
+        Some(name) => ProfileChoice::Named(name),
+        None => ProfileChoice::Default,
+    }
+}
 
-    // Warn about unknown keys
-    for warning in warn_unknown_keys(&doc) {
-        eprintln!("\x1b[33mwarning:\x1b[0m {warning}");
+/// Load the config at `path` with a profile chosen like [`load_profile`] and
+/// report every problem with it, rather than stopping at the first error.
+/// Also flags things that only fail when a task runs: `dir` paths that don't
+/// exist and tasks with nothing to run.
+pub fn check(path: &Path, profile: Option<&str>) -> Vec<Diagnostic> {
+    let mut diagnostics = Diagnostics {
+        collect: true,
+        found: Vec::new(),
+    };
+    let from_env = std::env::var("PLZ_PROFILE").ok();
+    match load_with(
+        path,
+        profile_choice(profile, from_env.as_deref()),
+        None,
+        &mut diagnostics,
+    ) {
+        Ok(config) => check_tasks(&config, path, &mut diagnostics),
+        Err(err) => diagnostics.found.push(Diagnostic::from_error(&err)),
     }
+    let mut found = diagnostics.found;
+    // Loading walks tasks in hash order; report in file order instead
+    found.sort_by_key(|d| {
+        d.location
+            .as_ref()
+            .map(|l| (l.file.clone(), l.line, l.column))
+    });
+    found
+}
 
-    // Extract comments above [tasks.*] as descriptions (fallback when no explicit description)
-    if let Some(tasks_table) = doc.get("tasks").and_then(|v| v.as_table()) {
-        for (key, item) in tasks_table.iter() {
-            if let Some(task) = config.tasks.get_mut(key)
-                && task.description.is_none()
-                && let Some(decor) = item.as_table().map(|t| t.decor())
-                && let Some(prefix) = decor.prefix().and_then(|p| p.as_str())
-            {
-                task.description = extract_comment(prefix);
-            }
+fn warn_version(config: &PlzConfig, path: &Path, diagnostics: &mut Diagnostics) {
+    if let Some(warning) = config.version_warning() {
+        diagnostics.warn(Diagnostic::warning(
+            warning,
+            find_location(path, &["plz", "version"], 2),
+        ));
+    }
+}
+
+fn check_tasks(config: &PlzConfig, path: &Path, diagnostics: &mut Diagnostics) {
+    warn_version(config, path, diagnostics);
+    let root_dir = path.parent().unwrap_or(Path::new("."));
+    let mut keys: Vec<String> = config.tasks.keys().cloned().collect();
+    for (group_name, group) in config.taskgroup.iter().flatten() {
+        keys.extend(group.tasks.keys().map(|t| format!("{group_name}.{t}")));
+    }
+    keys.sort();
+    for key in &keys {
+        let Some(task) = task_by_key(config, key) else {
+            continue;
+        };
+        let name = key.replacen('.', ":", 1);
+        // Only fails if the task runs, so not an error
+        if let Some(ref unavailable) = task.unavailable {
+            diagnostics.warn(Diagnostic::warning(
+                unavailable.message.clone(),
+                unavailable.location.clone(),
+            ));
+        }
+        // Placeholders left for run time can't be checked yet
+        if let Some(ref dir) = task.dir
+            && !dir.contains("{{")
+            && !root_dir.join(dir).is_dir()
+        {
+            let error = anyhow!("Task \"{name}\" has dir \"{dir}\", which doesn't exist");
+            let _ = diagnostics.error(task_error(
+                &config.task_sources,
+                key,
+                task,
+                field_error(&["dir"], error),
+            ));
+        }
+        if task.run.is_none()
+            && task.run_serial.is_none()
+            && task.run_parallel.is_none()
+            && task.script.is_none()
+            && task.depends.is_none()
+            && task.unavailable.is_none()
+            && task.runs_on_current_platform()
+        {
+            diagnostics.warn(Diagnostic::warning(
+                format!("Task \"{name}\" has nothing to run: set run, run_serial, run_parallel or script"),
+                task_location(&config.task_sources, key, task, &[]),
+            ));
         }
     }
+}
+
+/// Load the config at `path`. `defaults` fill whatever its `[extends]`
+/// leaves unset, for a workspace member under the root's `[extends]`.
+fn load_with(
+    path: &Path,
+    profile: ProfileChoice,
+    defaults: Option<&GlobalSettings>,
+    diagnostics: &mut Diagnostics,
+) -> Result<PlzConfig> {
+    let mut config = parse_file(path, false, diagnostics)?;
+
+    let root = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let mut stack = vec![root.clone()];
+    let mut loaded = HashSet::from([root]);
+    resolve_includes(&mut config, path, &mut stack, &mut loaded, diagnostics)?;
+    merge_local_config(&mut config, path, diagnostics)?;
+    select_profile(&mut config, path, profile, diagnostics)?;
+    set_var_dirs(&mut config, path.parent().unwrap_or(Path::new(".")));
+    fill_global_settings(&mut config.extends, defaults.cloned());
+    load_workspace_members(&mut config, path, diagnostics)?;
+    resolve_task_extends(&mut config, diagnostics)?;
 
     // Apply global defaults from [extends] to tasks.
     // Empty string means "explicitly no value" (opt out of extends).
-    if let Some(ref extends) = config.extends {
-        for task in config.tasks.values_mut() {
-            if task.tool_env.is_none() {
-                task.tool_env.clone_from(&extends.tool_env);
-            }
-            if task.dir.is_none() {
-                task.dir.clone_from(&extends.dir);
-            }
-        }
-    }
+    let top_settings = config.extends.clone().unwrap_or_default();
     for task in config.tasks.values_mut() {
-        if task.tool_env.as_deref() == Some("") {
-            task.tool_env = None;
-        }
-        if task.dir.as_deref() == Some("") {
-            task.dir = None;
-        }
+        top_settings.apply_to(task);
     }
 
     if config.tasks.contains_key("plz") {
-        bail!("\"plz\" is a reserved name and cannot be used as a task name.");
+        let file = config
+            .task_sources
+            .get("plz")
+            .map_or(path, PathBuf::as_path);
+        diagnostics.error(error_at(
+            file,
+            &["tasks", "plz"],
+            "\"plz\" is a reserved name and cannot be used as a task name.".to_string(),
+        ))?;
     }
 
-    // Validate git_hook values
     for (name, task) in &config.tasks {
-        if let Some(ref hook) = task.git_hook {
-            if !VALID_GIT_HOOKS.contains(&hook.as_str()) {
-                bail!(
-                    "Task \"{name}\" has invalid git_hook \"{hook}\". Valid hooks: {}",
-                    VALID_GIT_HOOKS.join(", ")
-                );
-            }
-        }
+        validate_task(name, task)
+            .or_else(|e| diagnostics.error(task_error(&config.task_sources, name, task, e)))?;
     }
 
     // Validate [healthcheck] section
     if let Some(ref hc) = config.healthcheck {
         if hc.enable.is_some() && hc.disable.is_some() {
-            bail!("[healthcheck]: specify either `enable` or `disable`, not both");
+            diagnostics.error(error_at(
+                path,
+                &["healthcheck", "disable"],
+                "[healthcheck]: specify either `enable` or `disable`, not both".to_string(),
+            ))?;
         }
-        for list in [&hc.enable, &hc.disable].into_iter().flatten() {
-            for name in list {
+        for (field, list) in [("enable", &hc.enable), ("disable", &hc.disable)] {
+            for (i, name) in list.iter().flatten().enumerate() {
                 if !crate::healthcheck::ALL_CHECKS.contains(&name.as_str()) {
-                    bail!(
-                        "[healthcheck]: unknown check \"{name}\". Valid checks: {}",
-                        crate::healthcheck::ALL_CHECKS.join(", ")
-                    );
+                    diagnostics.error(error_at(
+                        path,
+                        &["healthcheck", field, &i.to_string()],
+                        format!(
+                            "[healthcheck]: unknown check \"{name}\". Valid checks: {}",
+                            crate::healthcheck::ALL_CHECKS.join(", ")
+                        ),
+                    ))?;
                 }
             }
         }
         if let Some(ref hooks) = hc.git_hook {
-            for stage in &hooks.0 {
+            for (i, stage) in hooks.0.iter().enumerate() {
                 if !VALID_GIT_HOOKS.contains(&stage.as_str()) {
-                    bail!(
-                        "[healthcheck] has invalid git_hook \"{stage}\". Valid hooks: {}",
-                        VALID_GIT_HOOKS.join(", ")
-                    );
+                    diagnostics.error(error_at(
+                        path,
+                        &["healthcheck", "git_hook", &i.to_string()],
+                        format!(
+                            "[healthcheck] has invalid git_hook \"{stage}\". Valid hooks: {}",
+                            VALID_GIT_HOOKS.join(", ")
+                        ),
+                    ))?;
                 }
             }
         }
@@ -547,104 +2816,356 @@ pub fn load(path: &Path) -> Result<PlzConfig> {
     // top-level [extends] → [taskgroup.X.extends] → per-task values
     if let Some(ref mut groups) = config.taskgroup {
         for (group_name, group) in groups.iter_mut() {
-            // Compute effective extends: top-level, then group overrides
-            let effective_env = group
-                .extends
-                .as_ref()
-                .and_then(|e| e.tool_env.clone())
-                .or_else(|| config.extends.as_ref().and_then(|e| e.tool_env.clone()));
-            let effective_dir = group
-                .extends
-                .as_ref()
-                .and_then(|e| e.dir.clone())
-                .or_else(|| config.extends.as_ref().and_then(|e| e.dir.clone()));
-
-            for task in group.tasks.values_mut() {
-                if task.tool_env.is_none() {
-                    task.tool_env.clone_from(&effective_env);
-                }
-                if task.dir.is_none() {
-                    task.dir.clone_from(&effective_dir);
-                }
-            }
-
-            // Clear empty-string opt-outs
-            for task in group.tasks.values_mut() {
-                if task.tool_env.as_deref() == Some("") {
-                    task.tool_env = None;
-                }
-                if task.dir.as_deref() == Some("") {
-                    task.dir = None;
+            // Group settings win; the rest come from the top level. Workspace
+            // members already got the top level as their defaults.
+            if group.workspace_dir.is_none() {
+                let mut settings = group.extends.clone().unwrap_or_default();
+                settings.fill_from(&top_settings);
+                for task in group.tasks.values_mut() {
+                    settings.apply_to(task);
                 }
             }
 
-            // Validate git_hook values in group tasks
             for (task_name, task) in &group.tasks {
-                if let Some(ref hook) = task.git_hook {
-                    if !VALID_GIT_HOOKS.contains(&hook.as_str()) {
-                        bail!(
-                            "Task \"{group_name}:{task_name}\" has invalid git_hook \"{hook}\". Valid hooks: {}",
-                            VALID_GIT_HOOKS.join(", ")
-                        );
-                    }
-                }
+                validate_task(&format!("{group_name}:{task_name}"), task).or_else(|e| {
+                    diagnostics.error(task_error(
+                        &config.task_sources,
+                        &format!("{group_name}.{task_name}"),
+                        task,
+                        e,
+                    ))
+                })?;
             }
+        }
+    }
 
-            // Extract comments from taskgroup tables
-            if let Some(group_table) = doc
-                .get("taskgroup")
-                .and_then(|v| v.as_table())
-                .and_then(|t| t.get(group_name.as_str()))
-                .and_then(|v| v.as_table())
-            {
-                for (key, item) in group_table.iter() {
-                    if key == "extends" || key == "vars" {
-                        continue;
-                    }
-                    if let Some(task) = group.tasks.get_mut(key)
-                        && task.description.is_none()
-                        && let Some(decor) = item.as_table().map(|t| t.decor())
-                        && let Some(prefix) = decor.prefix().and_then(|p| p.as_str())
-                    {
-                        task.description = extract_comment(prefix);
-                    }
-                }
+    apply_profile_environment(&mut config);
+
+    validate_aliases(&config, diagnostics)?;
+
+    // Hide tasks limited to other platforms; the runner skips them
+    for (name, task) in config.tasks.iter_mut() {
+        apply_platforms(name, task)
+            .or_else(|e| diagnostics.error(task_error(&config.task_sources, name, task, e)))?;
+    }
+    if let Some(ref mut groups) = config.taskgroup {
+        for (group_name, group) in groups.iter_mut() {
+            for (task_name, task) in group.tasks.iter_mut() {
+                apply_platforms(&format!("{group_name}:{task_name}"), task).or_else(|e| {
+                    diagnostics.error(task_error(
+                        &config.task_sources,
+                        &format!("{group_name}.{task_name}"),
+                        task,
+                        e,
+                    ))
+                })?;
             }
         }
     }
 
-    // Apply [vars] substitution to top-level tasks
-    if let Some(ref vars) = config.vars {
+    // Apply [vars], env and built-in substitution to top-level tasks
+    let root_dir = path.parent().unwrap_or(Path::new("."));
+    let root_dir = root_dir
+        .canonicalize()
+        .unwrap_or_else(|_| root_dir.to_path_buf());
+    let top_vars = config.vars.clone().unwrap_or_default();
+    // Substituting vars that reference each other in a loop would never finish
+    let top_vars_ok = diagnostics.record(detect_var_cycles(&top_vars, path, &["vars"]))?;
+    if top_vars_ok {
         for (name, task) in config.tasks.iter_mut() {
-            substitute_task_vars(task, vars).with_context(|| format!("In task \"{name}\""))?;
+            let ctx = Interpolation {
+                vars: &top_vars,
+                root: &root_dir,
+                task: name,
+                args: &[],
+            };
+            substitute_task_vars(task, &ctx).or_else(|e| {
+                substitution_error(&config.task_sources, name, task, e, diagnostics)
+            })?;
         }
     }
 
     // Apply vars to taskgroup tasks (merge: top-level vars + group vars)
-    if let Some(ref mut groups) = config.taskgroup {
+    if let Some(ref mut groups) = config.taskgroup
+        && top_vars_ok
+    {
         for (group_name, group) in groups.iter_mut() {
-            let mut merged_vars: HashMap<String, String> = config.vars.clone().unwrap_or_default();
+            let mut merged_vars = top_vars.clone();
             if let Some(ref group_vars) = group.vars {
                 merged_vars.extend(group_vars.clone());
-            }
-            if !merged_vars.is_empty() {
-                for (task_name, task) in group.tasks.iter_mut() {
-                    substitute_task_vars(task, &merged_vars)
-                        .with_context(|| format!("In task \"{group_name}:{task_name}\""))?;
+                let cycles =
+                    detect_var_cycles(&merged_vars, path, &["taskgroup", group_name, "vars"])
+                        .with_context(|| format!("In [taskgroup.{group_name}.vars]"));
+                if !diagnostics.record(cycles)? {
+                    continue;
                 }
             }
+            for (task_name, task) in group.tasks.iter_mut() {
+                let display = format!("{group_name}:{task_name}");
+                let ctx = Interpolation {
+                    vars: &merged_vars,
+                    root: &root_dir,
+                    task: &display,
+                    args: &[],
+                };
+                substitute_task_vars(task, &ctx).or_else(|e| {
+                    let key = format!("{group_name}.{task_name}");
+                    substitution_error(&config.task_sources, &key, task, e, diagnostics)
+                })?;
+            }
         }
     }
 
+    collect_secrets(&mut config, &root_dir);
+
     // Validate depends references exist
-    validate_depends(&config)?;
+    validate_depends(&config, diagnostics)?;
 
     // Detect circular dependencies
-    detect_cycles(&config)?;
+    detect_cycles(&config, diagnostics)?;
 
     Ok(config)
 }
 
+/// Register the secret values known at load time. Secret `sh` and `file`
+/// values are added when a task first needs them.
+fn collect_secrets(config: &mut PlzConfig, root: &Path) {
+    config.secrets.mask_output = config.plz.as_ref().is_some_and(|p| p.mask_output);
+    let top_vars = config.vars.clone().unwrap_or_default();
+    let mut scopes = vec![top_vars.clone()];
+    for group in config.taskgroup.iter().flatten().map(|(_, g)| g) {
+        let mut vars = top_vars.clone();
+        vars.extend(group.vars.clone().unwrap_or_default());
+        scopes.push(vars);
+    }
+    for vars in &scopes {
+        let ctx = Interpolation {
+            vars,
+            root,
+            task: "",
+            args: &[],
+        };
+        for var in vars.values() {
+            if var.is_secret()
+                && !var.is_dynamic()
+                && let Ok(value) = substitute_vars(var.text(), &ctx)
+            {
+                config.secrets.add(&value);
+            }
+        }
+    }
+    let groups = config.taskgroup.iter().flatten().map(|(_, g)| g);
+    let tasks = config
+        .tasks
+        .values()
+        .chain(groups.flat_map(|g| g.tasks.values()));
+    for task in tasks {
+        for var in task.environment.iter().flat_map(HashMap::values) {
+            if var.is_secret() && !var.is_dynamic() {
+                config.secrets.add(var.text());
+            }
+        }
+    }
+}
+
+fn apply_platforms(name: &str, task: &mut Task) -> Result<(), FieldError> {
+    if let Some(ref platforms) = task.platforms {
+        for (i, platform) in platforms.iter().enumerate() {
+            if !PLATFORMS.contains(&platform.as_str()) {
+                let error = anyhow!(
+                    "Task \"{name}\" has unknown platform \"{platform}\". Valid platforms: {}",
+                    PLATFORMS.join(", ")
+                );
+                return Err(field_error(&["platforms", &i.to_string()], error));
+            }
+        }
+    }
+    if !task.runs_on_current_platform() {
+        task.hide = true;
+    }
+    Ok(())
+}
+
+/// Check that aliases don't collide with task names, group names, built-in
+/// commands or each other.
+fn validate_aliases(config: &PlzConfig, diagnostics: &mut Diagnostics) -> Result<()> {
+    check_aliases(config, &config.tasks, None, diagnostics, |alias| {
+        if BUILTIN_COMMANDS.contains(&alias) {
+            Some("the built-in command".to_string())
+        } else if config.get_group(alias).is_some() {
+            Some("the task group".to_string())
+        } else {
+            None
+        }
+    })?;
+    if let Some(ref groups) = config.taskgroup {
+        for (group_name, group) in groups {
+            check_aliases(config, &group.tasks, Some(group_name), diagnostics, |_| {
+                None
+            })?;
+        }
+    }
+    Ok(())
+}
+
+fn check_aliases(
+    config: &PlzConfig,
+    tasks: &HashMap<String, Task>,
+    group: Option<&str>,
+    diagnostics: &mut Diagnostics,
+    reserved: impl Fn(&str) -> Option<String>,
+) -> Result<()> {
+    let prefix = group.map(|g| format!("{g}:")).unwrap_or_default();
+    let mut owners: HashMap<&str, &str> = HashMap::new();
+    let mut names: Vec<&String> = tasks.keys().collect();
+    names.sort();
+    for name in names {
+        let task = &tasks[name];
+        for (i, alias) in task.aliases.iter().flatten().enumerate() {
+            let error = if tasks.contains_key(alias) {
+                anyhow!(
+                    "Alias \"{alias}\" of task \"{prefix}{name}\" conflicts with the task \"{prefix}{alias}\""
+                )
+            } else if let Some(what) = reserved(alias) {
+                anyhow!(
+                    "Alias \"{alias}\" of task \"{prefix}{name}\" conflicts with {what} \"{alias}\""
+                )
+            } else if let Some(other) = owners.insert(alias, name) {
+                anyhow!(
+                    "Alias \"{alias}\" is used by both \"{prefix}{other}\" and \"{prefix}{name}\""
+                )
+            } else {
+                continue;
+            };
+            let key = group.map_or_else(|| name.clone(), |g| format!("{g}.{name}"));
+            diagnostics.error(task_error(
+                &config.task_sources,
+                &key,
+                task,
+                field_error(&["aliases", &i.to_string()], error),
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+/// Check a task's git_hook, args and conditions. `name` is the display name.
+fn validate_task(name: &str, task: &Task) -> Result<(), FieldError> {
+    if let Some(ref hook) = task.git_hook
+        && !VALID_GIT_HOOKS.contains(&hook.as_str())
+    {
+        let error = anyhow!(
+            "Task \"{name}\" has invalid git_hook \"{hook}\"{}. Valid hooks: {}",
+            task.inherited_note("git_hook"),
+            VALID_GIT_HOOKS.join(", ")
+        );
+        return Err(field_error(&["git_hook"], error));
+    }
+    validate_script(name, task)?;
+    validate_retry(name, task)?;
+    if task.max_parallel == Some(0) {
+        let error = anyhow!("Task \"{name}\" has max_parallel = 0. Use 1 or more.");
+        return Err(field_error(&["max_parallel"], error));
+    }
+    validate_args(name, task)?;
+    validate_conditions(name, task)
+}
+
+fn validate_script(name: &str, task: &Task) -> Result<(), FieldError> {
+    let Some(ref script) = task.script else {
+        if task.interpreter.is_some() {
+            let error = anyhow!("Task \"{name}\" sets interpreter but has no script");
+            return Err(field_error(&["interpreter"], error));
+        }
+        return Ok(());
+    };
+    let commands = [
+        ("run", task.run.is_some()),
+        ("run_serial", task.run_serial.is_some()),
+        ("run_parallel", task.run_parallel.is_some()),
+    ];
+    if let Some((field, _)) = commands.iter().find(|(_, set)| *set) {
+        let error = anyhow!("Task \"{name}\" sets both script and {field}. Use one or the other.");
+        return Err(field_error(&["script"], error));
+    }
+    if task.interpreter.is_none() && !script.trim_start().starts_with("#!") {
+        let error = anyhow!(
+            "Task \"{name}\" has a script but no interpreter. Set `interpreter` (e.g. \"python3\") or start the script with a #! line."
+        );
+        return Err(field_error(&["script"], error));
+    }
+    Ok(())
+}
+
+fn validate_retry(name: &str, task: &Task) -> Result<(), FieldError> {
+    let Some(ref retry) = task.retry else {
+        return Ok(());
+    };
+    if retry.attempts == 0 {
+        let error = anyhow!("Task \"{name}\" has retry attempts = 0. Use 1 or more.");
+        return Err(field_error(&["retry", "attempts"], error));
+    }
+    if !retry.backoff.is_finite() || retry.backoff < 1.0 {
+        let error = anyhow!(
+            "Task \"{name}\" has retry backoff = {}. Use 1.0 or more.",
+            retry.backoff
+        );
+        return Err(field_error(&["retry", "backoff"], error));
+    }
+    Ok(())
+}
+
+fn validate_conditions(name: &str, task: &Task) -> Result<(), FieldError> {
+    for (field, condition) in [("run_if", &task.run_if), ("skip_if", &task.skip_if)] {
+        if let Some(c) = condition
+            && c.file.is_none()
+            && c.env.is_none()
+            && c.sh.is_none()
+            && c.ci.is_none()
+        {
+            let error =
+                anyhow!("Task \"{name}\": {field} needs at least one of file, env, sh or ci");
+            return Err(field_error(&[field], error));
+        }
+    }
+    Ok(())
+}
+
+fn validate_args(name: &str, task: &Task) -> Result<(), FieldError> {
+    let Some(ref args) = task.args else {
+        return Ok(());
+    };
+    let mut seen = HashSet::new();
+    for (i, arg) in args.iter().enumerate() {
+        let index = i.to_string();
+        if !seen.insert(arg.name.as_str()) {
+            let error = anyhow!(
+                "Task \"{name}\" declares argument \"{}\" more than once",
+                arg.name
+            );
+            return Err(field_error(&["args", &index, "name"], error));
+        }
+        if arg.variadic && i + 1 != args.len() {
+            let error = anyhow!(
+                "Task \"{name}\": only the last argument can be variadic, not \"{}\"",
+                arg.name
+            );
+            return Err(field_error(&["args", &index, "variadic"], error));
+        }
+        if let (Some(default), Some(choices)) = (&arg.default, &arg.choices)
+            && !choices.contains(default)
+        {
+            let error = anyhow!(
+                "Task \"{name}\": default \"{default}\" for argument \"{}\" isn't one of its choices ({})",
+                arg.name,
+                choices.join(", ")
+            );
+            return Err(field_error(&["args", &index, "default"], error));
+        }
+    }
+    Ok(())
+}
+
 fn resolve_dep_ref(config: &PlzConfig, dep: &str) -> bool {
     if let Some((group, task)) = dep.split_once('.') {
         config.get_group_task(group, task).is_some()
@@ -653,35 +3174,35 @@ fn resolve_dep_ref(config: &PlzConfig, dep: &str) -> bool {
     }
 }
 
-fn validate_depends(config: &PlzConfig) -> Result<()> {
-    for (name, task) in &config.tasks {
-        if let Some(ref deps) = task.depends {
-            for dep in &deps.0 {
-                if !resolve_dep_ref(config, dep) {
-                    bail!("Task \"{name}\" has depends \"{dep}\", but no task \"{dep}\" exists");
-                }
-            }
+fn validate_depends(config: &PlzConfig, diagnostics: &mut Diagnostics) -> Result<()> {
+    let mut tasks: Vec<(String, &Task)> = config
+        .tasks
+        .iter()
+        .map(|(name, task)| (name.clone(), task))
+        .collect();
+    for (group_name, group) in config.taskgroup.iter().flatten() {
+        for (task_name, task) in &group.tasks {
+            tasks.push((format!("{group_name}.{task_name}"), task));
         }
     }
-    if let Some(ref groups) = config.taskgroup {
-        for (group_name, group) in groups {
-            for (task_name, task) in &group.tasks {
-                if let Some(ref deps) = task.depends {
-                    for dep in &deps.0 {
-                        if !resolve_dep_ref(config, dep) {
-                            bail!(
-                                "Task \"{group_name}.{task_name}\" has depends \"{dep}\", but no task \"{dep}\" exists"
-                            );
-                        }
-                    }
-                }
+    tasks.sort_by(|a, b| a.0.cmp(&b.0));
+    for (key, task) in tasks {
+        let deps = task.depends.iter().flat_map(|d| &d.0);
+        for (i, dep) in deps.enumerate() {
+            if !resolve_dep_ref(config, dep) {
+                let error = anyhow!(
+                    "Task \"{key}\" has depends \"{dep}\"{}, but no task \"{dep}\" exists",
+                    task.inherited_note("depends")
+                );
+                let error = field_error(&["depends", &i.to_string()], error);
+                diagnostics.error(task_error(&config.task_sources, &key, task, error))?;
             }
         }
     }
     Ok(())
 }
 
-fn detect_cycles(config: &PlzConfig) -> Result<()> {
+fn detect_cycles(config: &PlzConfig, diagnostics: &mut Diagnostics) -> Result<()> {
     // Build adjacency list: node_id -> [dep_ids]
     // node_id for top-level: task_name, for group: "group.task"
     let mut adj: HashMap<String, Vec<String>> = HashMap::new();
@@ -702,17 +3223,42 @@ fn detect_cycles(config: &PlzConfig) -> Result<()> {
         }
     }
 
+    if let Some(cycle) = find_cycle(&adj)
+        && let Some(task) = task_by_key(config, &cycle[0])
+    {
+        let error = anyhow!("Circular dependency: {}", cycle.join(" → "));
+        let index = task
+            .depends
+            .iter()
+            .flat_map(|d| &d.0)
+            .position(|d| *d == cycle[1]);
+        let index = index.unwrap_or_default().to_string();
+        diagnostics.error(task_error(
+            &config.task_sources,
+            &cycle[0],
+            task,
+            field_error(&["depends", &index], error),
+        ))?;
+    }
+
+    Ok(())
+}
+
+/// The first cycle in `adj`, as the path around it (`a → b → a`).
+fn find_cycle(adj: &HashMap<String, Vec<String>>) -> Option<Vec<String>> {
+    let mut nodes: Vec<&String> = adj.keys().collect();
+    nodes.sort();
     let mut visited = HashSet::new();
     let mut in_stack = HashSet::new();
     let mut path = Vec::new();
-
-    for node in adj.keys() {
-        if !visited.contains(node) {
-            dfs_cycle(node, &adj, &mut visited, &mut in_stack, &mut path)?;
+    for node in nodes {
+        if !visited.contains(node)
+            && let Some(cycle) = dfs_cycle(node, adj, &mut visited, &mut in_stack, &mut path)
+        {
+            return Some(cycle);
         }
     }
-
-    Ok(())
+    None
 }
 
 fn dfs_cycle(
@@ -721,7 +3267,7 @@ fn dfs_cycle(
     visited: &mut HashSet<String>,
     in_stack: &mut HashSet<String>,
     path: &mut Vec<String>,
-) -> Result<()> {
+) -> Option<Vec<String>> {
     visited.insert(node.to_string());
     in_stack.insert(node.to_string());
     path.push(node.to_string());
@@ -731,18 +3277,61 @@ fn dfs_cycle(
             if in_stack.contains(dep) {
                 path.push(dep.clone());
                 let cycle_start = path.iter().position(|n| n == dep).unwrap();
-                let cycle = path[cycle_start..].join(" → ");
-                bail!("Circular dependency: {cycle}");
+                return Some(path[cycle_start..].to_vec());
             }
-            if !visited.contains(dep) {
-                dfs_cycle(dep, adj, visited, in_stack, path)?;
+            if !visited.contains(dep)
+                && let Some(cycle) = dfs_cycle(dep, adj, visited, in_stack, path)
+            {
+                return Some(cycle);
             }
         }
     }
 
     path.pop();
     in_stack.remove(node);
-    Ok(())
+    None
+}
+
+impl Task {
+    /// ` (inherited from "x")` when `field` came from a task this one extends.
+    pub fn inherited_note(&self, field: &str) -> String {
+        match self.inherited.get(field) {
+            Some(source) => format!(" (inherited from \"{source}\")"),
+            None => String::new(),
+        }
+    }
+
+    /// Whether `platforms` (if set) includes the platform plz is running on.
+    pub fn runs_on_current_platform(&self) -> bool {
+        self.platforms
+            .as_ref()
+            .is_none_or(|p| p.iter().any(|p| p == std::env::consts::OS))
+    }
+}
+
+impl TaskArg {
+    /// How the argument appears in a usage line, e.g. `<env>`, `[env]` or `[rest...]`
+    pub fn usage(&self) -> String {
+        match (self.variadic, self.default.is_some()) {
+            (true, _) => format!("[{}...]", self.name),
+            (false, true) => format!("[{}]", self.name),
+            (false, false) => format!("<{}>", self.name),
+        }
+    }
+}
+
+impl TaskGroup {
+    /// Name of the task in this group that has `alias` in its `aliases`.
+    pub fn task_for_alias(&self, alias: &str) -> Option<&str> {
+        find_alias(&self.tasks, alias)
+    }
+}
+
+fn find_alias<'a>(tasks: &'a HashMap<String, Task>, alias: &str) -> Option<&'a str> {
+    tasks
+        .iter()
+        .find(|(_, task)| task.aliases.iter().flatten().any(|a| a == alias))
+        .map(|(name, _)| name.as_str())
 }
 
 impl PlzConfig {
@@ -754,26 +3343,39 @@ impl PlzConfig {
         self.get_group(group)?.tasks.get(task)
     }
 
-    pub fn check_version(&self) {
-        let req_str = match self.plz.as_ref().and_then(|p| p.version.as_deref()) {
-            Some(v) => v,
-            None => return,
-        };
+    /// Name of the top-level task that has `alias` in its `aliases`.
+    pub fn task_for_alias(&self, alias: &str) -> Option<&str> {
+        find_alias(&self.tasks, alias)
+    }
+
+    /// Names of task groups loaded from `[workspace] members`, sorted.
+    pub fn workspace_members(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self
+            .taskgroup
+            .iter()
+            .flatten()
+            .filter(|(_, group)| group.workspace_dir.is_some())
+            .map(|(name, _)| name.as_str())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Why `[plz] version` doesn't accept this build of plz, if it doesn't.
+    pub fn version_warning(&self) -> Option<String> {
+        let req_str = self.plz.as_ref().and_then(|p| p.version.as_deref())?;
         let current = env!("CARGO_PKG_VERSION");
-        let Ok(version) = semver::Version::parse(current) else {
-            return;
-        };
+        let version = semver::Version::parse(current).ok()?;
         let Ok(req) = semver::VersionReq::parse(req_str) else {
-            eprintln!(
-                "\x1b[33mwarning:\x1b[0m [plz] version \"{req_str}\" is not a valid semver requirement"
-            );
-            return;
+            return Some(format!(
+                "[plz] version \"{req_str}\" is not a valid semver requirement"
+            ));
         };
-        if !req.matches(&version) {
-            eprintln!(
-                "\x1b[33mwarning:\x1b[0m plz {current} does not match version requirement \"{req_str}\" in plz.toml. Run `plz update` to update."
-            );
-        }
+        (!req.matches(&version)).then(|| {
+            format!(
+                "plz {current} does not match version requirement \"{req_str}\" in plz.toml. Run `plz update` to update."
+            )
+        })
     }
 }
 
@@ -0,0 +1,248 @@
+//! Config errors and warnings that point at `plz.toml:LINE:COL` with a
+//! rustc-style excerpt of the offending line.
+
+use serde::Serialize;
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+
+/// A position in a config file, with the source line for the excerpt.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Location {
+    /// The file, relative to the current directory when it's inside it.
+    pub file: String,
+    /// 1-based line number.
+    pub line: usize,
+    /// 1-based column, in characters.
+    pub column: usize,
+    #[serde(skip)]
+    source_line: String,
+    #[serde(skip)]
+    width: usize,
+}
+
+impl Location {
+    /// The location of the byte range `span` in `content`, read from `path`.
+    pub fn new(path: &Path, content: &str, span: Range<usize>) -> Self {
+        let start = span.start.min(content.len());
+        let line_start = content[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = content[start..]
+            .find('\n')
+            .map_or(content.len(), |i| start + i);
+        let source_line = content[line_start..line_end].trim_end_matches('\r');
+        let end = span.end.clamp(start, line_start + source_line.len());
+        Location {
+            file: display_path(path),
+            line: content[..start].matches('\n').count() + 1,
+            column: content[line_start..start].chars().count() + 1,
+            source_line: source_line.to_string(),
+            width: content[start..end].chars().count().max(1),
+        }
+    }
+
+    /// Find the value at `keys` (e.g. `["tasks", "build", "depends", "1"]`)
+    /// in the TOML `content`. Numeric keys index into arrays. When the full
+    /// path doesn't exist, points at the deepest part that does. Returns the
+    /// location and how many keys matched, or `None` if not even the first
+    /// one did.
+    pub fn find(path: &Path, content: &str, keys: &[&str]) -> Option<(Self, usize)> {
+        let doc = toml_edit::Document::parse(content).ok()?;
+        let mut node = Node::Item(doc.as_item());
+        let mut found = None;
+        for (depth, key) in keys.iter().enumerate() {
+            let Some((key_span, next)) = node.get(key) else {
+                break;
+            };
+            if let Some(span) = next.span().or(key_span) {
+                found = Some((span, depth + 1));
+            }
+            node = next;
+        }
+        found.map(|(span, depth)| (Location::new(path, content, span), depth))
+    }
+
+    /// Find the key at the end of `keys` rather than its value, for pointing
+    /// at keys that shouldn't be there.
+    pub fn find_key(path: &Path, content: &str, keys: &[&str]) -> Option<Self> {
+        let (last, parents) = keys.split_last()?;
+        let doc = toml_edit::Document::parse(content).ok()?;
+        let mut node = Node::Item(doc.as_item());
+        for key in parents {
+            node = node.get(key)?.1;
+        }
+        let (span, _) = node.get(last)?;
+        Some(Location::new(path, content, span?))
+    }
+}
+
+/// A step in a key path: table entries are items, array elements are values.
+#[derive(Clone, Copy)]
+enum Node<'a> {
+    Item(&'a toml_edit::Item),
+    Value(&'a toml_edit::Value),
+}
+
+impl<'a> Node<'a> {
+    fn get(self, key: &str) -> Option<(Option<Range<usize>>, Node<'a>)> {
+        let table = match self {
+            Node::Item(item) => item.as_table_like(),
+            Node::Value(value) => value
+                .as_inline_table()
+                .map(|t| t as &dyn toml_edit::TableLike),
+        };
+        if let Some(table) = table {
+            let (key, item) = table.get_key_value(key)?;
+            return Some((key.span(), Node::Item(item)));
+        }
+        let array = match self {
+            Node::Item(item) => item.as_array(),
+            Node::Value(value) => value.as_array(),
+        }?;
+        let value = array.get(key.parse().ok()?)?;
+        Some((None, Node::Value(value)))
+    }
+
+    /// Values span their text, tables their `[header]`.
+    fn span(self) -> Option<Range<usize>> {
+        match self {
+            Node::Item(toml_edit::Item::Value(value)) | Node::Value(value) => value.span(),
+            Node::Item(item) => item.span(),
+        }
+    }
+}
+
+/// `path` relative to the current directory when it's inside it, so editors
+/// and terminals can open `plz.toml:3:7` directly.
+fn display_path(path: &Path) -> String {
+    let relative = std::env::current_dir()
+        .ok()
+        .and_then(|cwd| path.strip_prefix(cwd).ok().map(Path::to_path_buf));
+    relative.as_deref().unwrap_or(path).display().to_string()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+/// A config error or warning, optionally pointing at where in a file it is.
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    #[serde(flatten)]
+    pub location: Option<Location>,
+}
+
+impl Diagnostic {
+    pub fn new(message: impl Into<String>, location: Option<Location>) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            message: message.into(),
+            location,
+        }
+    }
+
+    pub fn warning(message: impl Into<String>, location: Option<Location>) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::new(message, location)
+        }
+    }
+
+    pub fn note(message: impl Into<String>, location: Option<Location>) -> Self {
+        Diagnostic {
+            severity: Severity::Note,
+            ..Diagnostic::new(message, location)
+        }
+    }
+
+    /// Flatten an error chain into one diagnostic, keeping the location of
+    /// the first diagnostic in it.
+    pub fn from_error(err: &anyhow::Error) -> Self {
+        let mut location = None;
+        let parts: Vec<String> = err
+            .chain()
+            .map(|e| match e.downcast_ref::<Diagnostic>() {
+                Some(d) => {
+                    if location.is_none() {
+                        location.clone_from(&d.location);
+                    }
+                    d.message.clone()
+                }
+                None => e.to_string(),
+            })
+            .collect();
+        Diagnostic::new(parts.join(": "), location)
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    /// ```text
+    /// Task "build" has invalid git_hook "pre-comit". Valid hooks: ...
+    ///  --> plz.toml:3:12
+    ///   |
+    /// 3 | git_hook = "pre-comit"
+    ///   |            ^^^^^^^^^^^
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        let Some(ref loc) = self.location else {
+            return Ok(());
+        };
+        let gutter = " ".repeat(loc.line.to_string().len());
+        // Keep tabs so the caret lines up under the same text
+        let indent: String = loc
+            .source_line
+            .chars()
+            .take(loc.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        write!(
+            f,
+            "\n{gutter}--> {}:{}:{}\n{gutter} |\n{} | {}\n{gutter} | {indent}{}",
+            loc.file,
+            loc.line,
+            loc.column,
+            loc.line,
+            loc.source_line,
+            "^".repeat(loc.width)
+        )
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_excerpt_with_caret() {
+        let content = "[tasks.a]\n\trun = \"x\"\n";
+        let path = Path::new("/nowhere/plz.toml");
+        let location = Location::find(path, content, &["tasks", "a", "run"])
+            .unwrap()
+            .0;
+        assert_eq!((location.line, location.column), (2, 8));
+        let diagnostic = Diagnostic::new("bad run", Some(location));
+        assert_eq!(
+            diagnostic.to_string(),
+            "bad run\n --> /nowhere/plz.toml:2:8\n  |\n2 | \trun = \"x\"\n  | \t      ^^^"
+        );
+    }
+
+    #[test]
+    fn falls_back_to_deepest_existing_key() {
+        let content = "[tasks.a]\nrun = \"x\"\n";
+        let path = Path::new("plz.toml");
+        let (location, depth) = Location::find(path, content, &["tasks", "a", "dir"]).unwrap();
+        assert_eq!(depth, 2);
+        assert_eq!((location.line, location.column), (1, 1));
+        assert!(Location::find(path, content, &["vars", "a"]).is_none());
+        assert!(Location::find_key(path, content, &["tasks", "a", "dir"]).is_none());
+    }
+}
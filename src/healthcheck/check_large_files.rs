@@ -11,13 +11,13 @@ pub fn run(base_dir: &Path, files: &[FileEntry]) -> Result<CheckResult> {
 
     for file in files {
         let path = base_dir.join(&file.path);
-        if let Ok(meta) = std::fs::metadata(&path) {
-            if meta.len() > MAX_SIZE {
-                findings.push(Finding {
-                    file: file.path.clone(),
-                    detail: format!("{}KB", meta.len() / 1024),
-                });
-            }
+        if let Ok(meta) = std::fs::metadata(&path)
+            && meta.len() > MAX_SIZE
+        {
+            findings.push(Finding {
+                file: file.path.clone(),
+                detail: format!("{}KB", meta.len() / 1024),
+            });
         }
     }
 
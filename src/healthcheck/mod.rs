@@ -24,11 +24,13 @@ pub const ALL_CHECKS: &[&str] = &[
     mixed_line_ending::NAME,
 ];
 
+#[derive(Debug, Clone)]
 pub struct Finding {
     pub file: String,
     pub detail: String,
 }
 
+#[derive(Debug, Clone)]
 pub struct CheckResult {
     pub name: &'static str,
     pub description: &'static str,
@@ -142,11 +144,7 @@ fn is_binary(path: &Path) -> bool {
 }
 
 pub fn load_section(base_dir: &Path) -> Result<Option<HealthcheckSection>> {
-    let Some(config_path) = ["plz.toml", ".plz.toml"]
-        .iter()
-        .map(|n| base_dir.join(n))
-        .find(|p| p.exists())
-    else {
+    let Some(config_path) = config::find_config(base_dir) else {
         return Ok(None);
     };
     Ok(config::load(&config_path)?.healthcheck)
@@ -306,19 +304,35 @@ pub fn print_results(results: &[CheckResult]) {
     }
 }
 
-pub fn run_healthcheck(
+/// Run the checks `[healthcheck]`, `only` and `skip` select on the files in
+/// `base_dir`, or just the staged ones.
+pub fn run_checks(
     base_dir: &Path,
     staged_only: bool,
     only: &[String],
     skip: &[String],
-) -> Result<()> {
+) -> Result<Vec<CheckResult>> {
     let section = load_section(base_dir)?;
     let active = resolve_active_checks(section.as_ref(), only, skip)?;
-    let results = run_all_checks(base_dir, staged_only, section.as_ref(), &active)?;
+    run_all_checks(base_dir, staged_only, section.as_ref(), &active)
+}
+
+/// `plz healthcheck`: print the results, failing if any check failed.
+pub fn run_healthcheck(
+    base_dir: &Path,
+    staged_only: bool,
+    only: &[String],
+    skip: &[String],
+) -> Result<()> {
+    let results = run_checks(base_dir, staged_only, only, skip)?;
     print_results(&results);
-    let any_failed = results.iter().any(|r| !r.passed);
-    if any_failed {
-        std::process::exit(1);
+    let failed: Vec<&str> = results
+        .iter()
+        .filter(|r| !r.passed)
+        .map(|r| r.name)
+        .collect();
+    if !failed.is_empty() {
+        bail!("Healthcheck failed: {}", failed.join(", "));
     }
     Ok(())
 }
@@ -7,7 +7,7 @@ use std::path::{Path, PathBuf};
 use toml_edit::DocumentMut;
 
 const MANAGED_MARKER: &str = "# plz:managed - do not edit";
-const HOOKS_VERSION: u32 = 3;
+const HOOKS_VERSION: u32 = 4;
 
 /// Synthetic task label used in `tasks_by_stage` to represent the healthcheck
 /// hook configured via `[healthcheck].git_hook`. Dispatched specially by
@@ -77,11 +77,16 @@ fn generate_hook_script(stage: &str) -> String {
          # plz:hooks_version={HOOKS_VERSION}\n\
          [ \"${{PLZ_SKIP_HOOKS}}\" = \"1\" ] && exit 0\n\
          command -v plz >/dev/null 2>&1 || {{ echo \"plz not found in PATH, skipping {stage} hook\" >&2; exit 0; }}\n\
-         if [ ! -f plz.toml ] && [ ! -f .plz.toml ]; then\n\
-           echo \"plz: no plz.toml found, skipping {stage} hook\" >&2\n\
-           echo \"plz: to remove this hook, delete .git/hooks/{stage}\" >&2\n\
-           exit 0\n\
-         fi\n\
+         # Find plz.toml the same way plz does: walk up to the git root\n\
+         dir=\"$PWD\"\n\
+         while [ ! -f \"$dir/plz.toml\" ] && [ ! -f \"$dir/.plz.toml\" ]; do\n\
+           if [ -e \"$dir/.git\" ] || [ \"$dir\" = \"/\" ]; then\n\
+             echo \"plz: no plz.toml found, skipping {stage} hook\" >&2\n\
+             echo \"plz: to remove this hook, delete .git/hooks/{stage}\" >&2\n\
+             exit 0\n\
+           fi\n\
+           dir=$(dirname \"$dir\")\n\
+         done\n\
          plz --no-interactive hooks run {stage}\n"
     )
 }
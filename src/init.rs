@@ -6,7 +6,7 @@ use anyhow::{Result, bail};
 use std::env;
 use std::fmt::Write as _;
 use std::io::IsTerminal;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use toml_edit::DocumentMut;
 
 const PREAMBLE: &str = "\
@@ -19,7 +19,10 @@ fn config_dir() -> Option<PathBuf> {
     settings::config_dir()
 }
 
-pub fn extract_tasks(doc: &DocumentMut) -> Vec<(String, Option<String>)> {
+/// Task names with the description from the comment above each one.
+type TaskList = Vec<(String, Option<String>)>;
+
+pub fn extract_tasks(doc: &DocumentMut) -> TaskList {
     let Some(tasks_table) = doc.get("tasks").and_then(|t| t.as_table()) else {
         return Vec::new();
     };
@@ -36,7 +39,7 @@ pub fn extract_tasks(doc: &DocumentMut) -> Vec<(String, Option<String>)> {
         .collect()
 }
 
-pub fn parse_default(toml: &str) -> Option<(DocumentMut, Vec<(String, Option<String>)>)> {
+pub fn parse_default(toml: &str) -> Option<(DocumentMut, TaskList)> {
     let doc: DocumentMut = toml.parse().ok()?;
     doc.get("tasks").and_then(|t| t.as_table())?;
     let tasks = extract_tasks(&doc);
@@ -113,6 +116,43 @@ pub fn convert_to_taskgroup(
     result
 }
 
+/// Whether `.gitignore` in `dir` already ignores `plz.local.toml`.
+pub fn local_config_ignored(dir: &Path) -> bool {
+    std::fs::read_to_string(dir.join(".gitignore")).is_ok_and(|content| {
+        content
+            .lines()
+            .any(|line| matches!(line.trim(), "plz.local.toml" | "/plz.local.toml"))
+    })
+}
+
+/// Add the personal override files to `.gitignore` in `dir`, creating it if needed.
+pub fn ignore_local_config(dir: &Path) -> Result<()> {
+    let path = dir.join(".gitignore");
+    let mut content = std::fs::read_to_string(&path).unwrap_or_default();
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    for name in config::LOCAL_CONFIG_NAMES {
+        writeln!(content, "{name}")?;
+    }
+    std::fs::write(&path, content)?;
+    Ok(())
+}
+
+/// Offer to git-ignore plz.local.toml, unless it already is.
+fn offer_ignore_local_config(dir: &Path) -> Result<()> {
+    if local_config_ignored(dir) {
+        return Ok(());
+    }
+    let ignore: bool = cliclack::confirm("Add plz.local.toml (personal overrides) to .gitignore?")
+        .initial_value(true)
+        .interact()?;
+    if ignore {
+        ignore_local_config(dir)?;
+    }
+    Ok(())
+}
+
 pub fn run() -> Result<()> {
     let cwd = env::current_dir()?;
     let config_path = cwd.join("plz.toml");
@@ -122,9 +162,20 @@ pub fn run() -> Result<()> {
         let in_git_repo = hooks::find_git_hooks_dir(&cwd).is_ok();
         let content = std::fs::read_to_string(&config_path)?;
         let has_git_hooks = content.contains("git_hook");
+        let offer_ignore = in_git_repo
+            && std::io::stdin().is_terminal()
+            && !is_ci::cached()
+            && !local_config_ignored(&cwd);
 
-        if in_git_repo && has_git_hooks {
+        if offer_ignore && !has_git_hooks {
+            cliclack::intro("plz init")?;
+            offer_ignore_local_config(&cwd)?;
+            cliclack::outro("plz.toml already exists. Run \x1b[1mplz\x1b[0m to see all commands.")?;
+        } else if in_git_repo && has_git_hooks {
             cliclack::intro("plz init")?;
+            if offer_ignore {
+                offer_ignore_local_config(&cwd)?;
+            }
             let install_hooks: bool = cliclack::confirm("Install git hooks?")
                 .initial_value(true)
                 .interact()?;
@@ -309,6 +360,9 @@ pub fn run() -> Result<()> {
     } else {
         std::fs::write(&config_path, output.trim_end())?;
     }
+    if in_git_repo {
+        offer_ignore_local_config(&cwd)?;
+    }
 
     cliclack::outro("Created plz.toml".to_string())?;
     print_templates_hint(&cfg_dir);
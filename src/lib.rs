@@ -1,4 +1,29 @@
+//! plz as a library: load a plz.toml and run its tasks from Rust.
+//!
+//! ```no_run
+//! use plzplz::{Event, Plz, RunOptions};
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let plz = Plz::discover(".")?.with_events(|event: &Event| {
+//!     if let Event::CommandStarted { command, .. } = event {
+//!         println!("running {command}");
+//!     }
+//! });
+//! plz.task("build")?.run(RunOptions {
+//!     interactive: false,
+//!     ..Default::default()
+//! })?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Events go to stderr unless a handler is set with [`Plz::with_events`].
+//! The other modules are what the `plz` binary is built from, and may change
+//! between releases.
+
+mod api;
 pub mod config;
+pub mod diagnostic;
 pub mod healthcheck;
 pub mod hooks;
 pub mod init;
@@ -7,3 +32,8 @@ pub mod settings;
 pub mod templates;
 pub mod update_check;
 pub mod utils;
+
+pub use api::{HealthcheckOptions, Plz, RunOptions, TaskHandle};
+pub use diagnostic::{Diagnostic, Severity};
+pub use healthcheck::{CheckResult, Finding};
+pub use runner::{Event, EventHandler, Outcome, Stream};
@@ -1,18 +1,14 @@
-mod config;
-mod healthcheck;
-mod hooks;
-mod init;
-mod runner;
-mod settings;
-mod templates;
-mod update_check;
-mod utils;
+use plzplz::{config, diagnostic, healthcheck, hooks, init, runner, update_check, utils};
 
 use anyhow::{Result, bail};
-use clap::{CommandFactory, Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use diagnostic::Severity;
+use runner::{Jobs, Outcome};
 use std::env;
 use std::io::IsTerminal;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(name = "plz")]
@@ -27,6 +23,39 @@ struct Cli {
     /// Disable interactive prompts (auto-detected in CI)
     #[arg(long)]
     no_interactive: bool,
+
+    /// Run the task in every workspace member that defines it
+    #[arg(long)]
+    all: bool,
+
+    /// Load this [profile.<name>] (defaults to $PLZ_PROFILE, then default_profile)
+    #[arg(long)]
+    profile: Option<String>,
+
+    /// Kill any command that runs longer than this (e.g. 30s, 10m), over tasks' own `timeout`
+    #[arg(long, value_parser = parse_timeout)]
+    timeout: Option<Duration>,
+
+    /// Run at most this many commands at once, across nested tasks (defaults to $PLZ_JOBS)
+    #[arg(short = 'j', long, value_parser = parse_jobs)]
+    jobs: Option<usize>,
+
+    /// Stop the rest of a run_parallel list as soon as one command fails
+    #[arg(long)]
+    fail_fast: bool,
+}
+
+fn parse_jobs(value: &str) -> Result<usize, String> {
+    value
+        .parse()
+        .ok()
+        .filter(|&n| n > 0)
+        .ok_or_else(|| format!("expected a number above 0, got \"{value}\""))
+}
+
+fn parse_timeout(value: &str) -> Result<Duration, String> {
+    utils::parse_duration(value)
+        .ok_or_else(|| format!("invalid duration \"{value}\" (e.g. 30s, 10m, 1h30m)"))
 }
 
 #[derive(Subcommand)]
@@ -70,6 +99,18 @@ enum PlzCommand {
         #[arg(long, value_delimiter = ',')]
         skip: Vec<String>,
     },
+    /// Validate plz.toml and report every problem with it
+    Check {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = CheckFormat::Text)]
+        format: CheckFormat,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum CheckFormat {
+    Text,
+    Json,
 }
 
 #[derive(Subcommand)]
@@ -114,17 +155,9 @@ fn is_interactive(cli: &Cli) -> bool {
     true
 }
 
-const CONFIG_NAMES: &[&str] = &["plz.toml", ".plz.toml"];
-
 fn find_config() -> Option<PathBuf> {
     let cwd = env::current_dir().ok()?;
-    for name in CONFIG_NAMES {
-        let path = cwd.join(name);
-        if path.exists() {
-            return Some(path);
-        }
-    }
-    None
+    config::find_config(&cwd)
 }
 
 struct HelpEntry {
@@ -173,6 +206,10 @@ const HELP_COMMANDS: &[HelpEntry] = &[
         usage: "healthcheck [--staged] [--only <checks>] [--skip <checks>]",
         description: "Run code health checks on your repo (use --staged for staged files, --only/--skip to filter)",
     },
+    HelpEntry {
+        usage: "check [--format json]",
+        description: "Validate plz.toml and report every problem with it",
+    },
     HelpEntry {
         usage: "plz",
         description: "Manage global defaults",
@@ -184,6 +221,26 @@ const HELP_OPTIONS: &[HelpEntry] = &[
         usage: "--no-interactive",
         description: "Disable interactive prompts (auto-detected in CI)",
     },
+    HelpEntry {
+        usage: "--all",
+        description: "Run the task in every workspace member that defines it",
+    },
+    HelpEntry {
+        usage: "--profile <name>",
+        description: "Use a [profile.<name>] (or set PLZ_PROFILE)",
+    },
+    HelpEntry {
+        usage: "--timeout <duration>",
+        description: "Kill commands that run longer than this (e.g. 10m)",
+    },
+    HelpEntry {
+        usage: "-j, --jobs <n>",
+        description: "Run at most n commands at once (or set PLZ_JOBS)",
+    },
+    HelpEntry {
+        usage: "--fail-fast",
+        description: "Stop parallel commands as soon as one fails",
+    },
     HelpEntry {
         usage: "-h, --help",
         description: "Print help",
@@ -237,22 +294,55 @@ fn entries_to_pick_items(
     entries
         .iter()
         .map(|(label, resolved)| {
-            let desc = match resolved {
-                ResolvedTask::Task(n) => config.tasks[n].description.clone().unwrap_or_default(),
-                ResolvedTask::GroupTask(g, t) => config
-                    .get_group_task(g, t)
-                    .and_then(|task| task.description.clone())
-                    .unwrap_or_default(),
+            let task = match resolved {
+                ResolvedTask::Task(n) => config.tasks.get(n),
+                ResolvedTask::GroupTask(g, t) => config.get_group_task(g, t),
             };
             utils::PickItem {
                 label: label.clone(),
-                description: desc,
+                description: task.map(task_summary).unwrap_or_default(),
                 preview: None,
             }
         })
         .collect()
 }
 
+/// Description for pickers and help, prefixed with the task's aliases.
+fn task_summary(task: &config::Task) -> String {
+    let description = task.description.as_deref().unwrap_or_default();
+    match task.aliases {
+        Some(ref aliases) if !aliases.is_empty() => {
+            format!("({}) {description}", aliases.join(", "))
+                .trim_end()
+                .to_string()
+        }
+        _ => description.to_string(),
+    }
+}
+
+/// The "Tasks:" section of `plz --help` when run inside a project.
+fn format_task_list(config: &config::PlzConfig) -> String {
+    let dim = "\x1b[2m";
+    let bold = "\x1b[1m";
+    let reset = "\x1b[0m";
+
+    let entries = all_task_entries(config);
+    if entries.is_empty() {
+        return String::new();
+    }
+    let items = entries_to_pick_items(&entries, config);
+    let max_label = items.iter().map(|i| i.label.len()).max().unwrap_or(0);
+    let mut out = format!("\n{dim}Tasks:{reset}\n");
+    for item in items {
+        let padding = " ".repeat(max_label - item.label.len() + 2);
+        out.push_str(&format!(
+            "  {bold}{}{reset}{padding}{}\n",
+            item.label, item.description
+        ));
+    }
+    out
+}
+
 pub fn format_help() -> String {
     let dim = "\x1b[2m";
     let bold = "\x1b[1m";
@@ -268,6 +358,9 @@ pub fn format_help() -> String {
     out.push_str(&format!(
         "{bold}plz{reset} [group] [task] [args...]  Run a task from a task group\n"
     ));
+    out.push_str(&format!(
+        "{bold}plz{reset} --all [task]              Run a task in every workspace member\n"
+    ));
     out.push_str(&format!(
         "{bold}plz{reset}                           Choose a task interactively\n"
     ));
@@ -312,6 +405,12 @@ fn main() -> Result<()> {
         let args: Vec<String> = env::args().collect();
         if args.len() == 2 && (args[1] == "--help" || args[1] == "-h" || args[1] == "help") {
             print!("{}", format_help());
+            if let Some(path) = find_config()
+                && let Ok(config) = config::load(&path)
+            {
+                print_warnings(&config);
+                print!("{}", format_task_list(&config));
+            }
             return Ok(());
         }
         if args.len() == 2 && (args[1] == "--version" || args[1] == "-V") {
@@ -337,11 +436,15 @@ fn main() -> Result<()> {
                 let base_dir = std::env::current_dir()?;
                 return healthcheck::run_healthcheck(&base_dir, *staged, only, skip);
             }
+            Some(PlzCommand::Check { format }) => {
+                return check_config(*format, cli.profile.as_deref());
+            }
             Some(PlzCommand::Hooks { hook_command }) => {
                 let config_path =
                     find_config().ok_or_else(|| anyhow::anyhow!("No plz.toml found"))?;
-                let config = config::load(&config_path)?;
-                config.check_version();
+                let mut config = config::load_profile(&config_path, cli.profile.as_deref())?;
+                apply_cli_overrides(&mut config, &cli)?;
+                print_warnings(&config);
                 let base_dir = config_path.parent().unwrap().to_path_buf();
                 let interactive = is_interactive(&cli);
                 match hook_command {
@@ -381,8 +484,9 @@ fn main() -> Result<()> {
             bail!("No plz.toml found. Run `plz init` to create one.");
         }
     };
-    let config = config::load(&config_path)?;
-    config.check_version();
+    let mut config = config::load_profile(&config_path, cli.profile.as_deref())?;
+    apply_cli_overrides(&mut config, &cli)?;
+    print_warnings(&config);
     let base_dir = config_path.parent().unwrap().to_path_buf();
 
     if cli.task.is_empty() {
@@ -397,14 +501,18 @@ fn main() -> Result<()> {
         let items = entries_to_pick_items(&pick_entries, &config);
         match utils::pick_from_list(&items, "Enter to run · Esc to cancel")? {
             Some(idx) => {
-                match &pick_entries[idx].1 {
-                    ResolvedTask::Task(name) => {
-                        runner::run_task(&config, name, &base_dir, interactive)?;
-                    }
-                    ResolvedTask::GroupTask(g, t) => {
-                        runner::run_group_task(&config, g, t, &base_dir, interactive)?;
-                    }
-                }
+                print_profile(&config);
+                let (name, outcome) = match &pick_entries[idx].1 {
+                    ResolvedTask::Task(name) => (
+                        name.clone(),
+                        runner::run_task(&config, name, &base_dir, interactive)?,
+                    ),
+                    ResolvedTask::GroupTask(g, t) => (
+                        format!("{g}:{t}"),
+                        runner::run_group_task(&config, g, t, &base_dir, interactive)?,
+                    ),
+                };
+                print_retried(&name, outcome);
                 hooks::hint_uninstalled_hooks(&config, &base_dir);
                 update_check::maybe_print_update_hint();
                 return Ok(());
@@ -418,6 +526,17 @@ fn main() -> Result<()> {
 
     let input = &cli.task[0];
 
+    if cli.all {
+        if cli.task.len() > 1 {
+            bail!("Extra arguments can't be passed to tasks run with --all");
+        }
+        print_profile(&config);
+        runner::run_task_in_members(&config, input, &base_dir, interactive)?;
+        hooks::hint_uninstalled_hooks(&config, &base_dir);
+        update_check::maybe_print_update_hint();
+        return Ok(());
+    }
+
     // Fall through to built-in subcommands if no task matches
     if !config.tasks.contains_key(input)
         && let Some(result) = try_plz_subcommand(&cli.task)
@@ -426,19 +545,29 @@ fn main() -> Result<()> {
     }
 
     let resolved = resolve_task(&config, input, &cli.task[1..], interactive)?;
-    match resolved {
+    if let Some(help) = task_help(&config, &resolved, input, &cli.task) {
+        print!("{help}");
+        return Ok(());
+    }
+    print_profile(&config);
+    let (name, outcome) = match resolved {
         ResolvedTask::Task(task_name) => {
             let extra_args = &cli.task[1..];
-            runner::run_task_with_args(&config, &task_name, &base_dir, interactive, extra_args)?;
+            let outcome = runner::run_task_with_args(
+                &config,
+                &task_name,
+                &base_dir,
+                interactive,
+                extra_args,
+            )?;
+            (task_name, outcome)
         }
         ResolvedTask::GroupTask(group, task) => {
-            // For group tasks, args start at [2] (task[0]=group, task[1]=task_name)
-            let extra_args = if cli.task.len() > 2 {
-                &cli.task[2..]
-            } else {
-                &[]
-            };
-            runner::run_group_task_with_args(
+            // `plz group task` uses two words; `plz group:task` (or a fuzzy
+            // match on the full label) uses one
+            let args_start = if group == *input { 2 } else { 1 };
+            let extra_args = cli.task.get(args_start..).unwrap_or(&[]);
+            let outcome = runner::run_group_task_with_args(
                 &config,
                 &group,
                 &task,
@@ -446,14 +575,126 @@ fn main() -> Result<()> {
                 interactive,
                 extra_args,
             )?;
+            (format!("{group}:{task}"), outcome)
         }
-    }
+    };
+    print_retried(&name, outcome);
     hooks::hint_uninstalled_hooks(&config, &base_dir);
     update_check::maybe_print_update_hint();
 
     Ok(())
 }
 
+/// Show what loading the config found wrong that didn't stop it.
+fn print_warnings(config: &config::PlzConfig) {
+    for warning in &config.warnings {
+        eprintln!("\x1b[33mwarning:\x1b[0m {warning}");
+    }
+}
+
+/// Point out a task that only passed after being retried, since the failed
+/// attempts scrolled by above.
+fn print_retried(name: &str, outcome: Outcome) {
+    if let Outcome::PassedOnAttempt { attempt, attempts } = outcome {
+        eprintln!("\n\x1b[33m✓ {name} passed on attempt {attempt}/{attempts}\x1b[0m");
+    }
+}
+
+/// Apply `--timeout`, `-j` (or `PLZ_JOBS`) and `--fail-fast` to a config
+/// loaded with the `--profile` profile.
+fn apply_cli_overrides(config: &mut config::PlzConfig, cli: &Cli) -> Result<()> {
+    config.timeout = cli.timeout.filter(|t| !t.is_zero());
+    if let Some(jobs) = jobs_limit(cli)? {
+        config.jobs = Some(Arc::new(Jobs::new(jobs)));
+    }
+    config.fail_fast = cli.fail_fast;
+    Ok(())
+}
+
+/// `-j`, or else a non-empty `PLZ_JOBS`.
+fn jobs_limit(cli: &Cli) -> Result<Option<usize>> {
+    if cli.jobs.is_some() {
+        return Ok(cli.jobs);
+    }
+    match env::var("PLZ_JOBS") {
+        Ok(jobs) if !jobs.is_empty() => match parse_jobs(&jobs) {
+            Ok(limit) => Ok(Some(limit)),
+            Err(_) => bail!("PLZ_JOBS must be a number above 0, got \"{jobs}\""),
+        },
+        _ => Ok(None),
+    }
+}
+
+/// Show which profile is active before running anything. Nested `plz` calls
+/// inherit it, so only the outermost one says so.
+fn print_profile(config: &config::PlzConfig) {
+    if let Some(ref profile) = config.active_profile
+        && !is_nested()
+    {
+        eprintln!("\x1b[2mprofile: {profile}\x1b[0m");
+    }
+}
+
+/// `plz <task> --help` for tasks that declare `args`. Tasks without `args`
+/// pass `--help` through to their command.
+fn task_help(
+    config: &config::PlzConfig,
+    resolved: &ResolvedTask,
+    input: &str,
+    words: &[String],
+) -> Option<String> {
+    let (task, name, args_start) = match resolved {
+        ResolvedTask::Task(name) => (config.tasks.get(name)?, name.clone(), 1),
+        ResolvedTask::GroupTask(group, task) => (
+            config.get_group_task(group, task)?,
+            format!("{group}:{task}"),
+            if group == input { 2 } else { 1 },
+        ),
+    };
+    let args = task.args.as_ref()?;
+    let extra = words.get(args_start..).unwrap_or(&[]);
+    if !matches!(extra, [flag] if flag == "--help" || flag == "-h") {
+        return None;
+    }
+    Some(format_task_help(&name, task, args))
+}
+
+fn format_task_help(name: &str, task: &config::Task, args: &[config::TaskArg]) -> String {
+    let dim = "\x1b[2m";
+    let bold = "\x1b[1m";
+    let reset = "\x1b[0m";
+
+    let mut out = String::new();
+    if let Some(ref description) = task.description {
+        out.push_str(&format!("{description}\n\n"));
+    }
+    let usage: Vec<String> = args.iter().map(|a| a.usage()).collect();
+    out.push_str(&format!(
+        "{dim}Usage:{reset} {bold}plz{reset} {name} {}\n\n",
+        usage.join(" ")
+    ));
+    if let Some(ref aliases) = task.aliases
+        && !aliases.is_empty()
+    {
+        out.push_str(&format!("{dim}Aliases:{reset} {}\n\n", aliases.join(", ")));
+    }
+
+    let max_name = args.iter().map(|a| a.name.len()).max().unwrap_or(0);
+    out.push_str(&format!("{dim}Arguments:{reset}\n"));
+    for arg in args {
+        let padding = " ".repeat(max_name - arg.name.len() + 2);
+        let mut line = arg.description.clone().unwrap_or_default();
+        if let Some(ref choices) = arg.choices {
+            line.push_str(&format!(" {dim}[choices: {}]{reset}", choices.join(", ")));
+        }
+        if let Some(ref default) = arg.default {
+            line.push_str(&format!(" {dim}[default: {default}]{reset}"));
+        }
+        out.push_str(&format!("  {}{padding}{}\n", arg.name, line.trim_start()));
+    }
+    out
+}
+
 fn hooks_no_subcommand(
     config: &config::PlzConfig,
     base_dir: &std::path::Path,
@@ -467,7 +708,7 @@ fn hooks_no_subcommand(
                 .interact()?;
             if should_add {
                 let config_path = base_dir.join(
-                    CONFIG_NAMES
+                    config::CONFIG_NAMES
                         .iter()
                         .find(|name| base_dir.join(name).exists())
                         .unwrap_or(&"plz.toml"),
@@ -492,6 +733,56 @@ fn hooks_no_subcommand(
     hooks::interactive_install(config, base_dir, interactive)
 }
 
+fn check_config(format: CheckFormat, profile: Option<&str>) -> Result<()> {
+    let config_path = find_config().ok_or_else(|| anyhow::anyhow!("No plz.toml found"))?;
+    let diagnostics = config::check(&config_path, profile);
+    let errors = diagnostics
+        .iter()
+        .filter(|d| d.severity == Severity::Error)
+        .count();
+    let warnings = diagnostics
+        .iter()
+        .filter(|d| d.severity == Severity::Warning)
+        .count();
+
+    match format {
+        CheckFormat::Json => {
+            let report = serde_json::json!({
+                "valid": errors == 0,
+                "errors": errors,
+                "warnings": warnings,
+                "diagnostics": diagnostics,
+            });
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        CheckFormat::Text => {
+            for diagnostic in &diagnostics {
+                let label = match diagnostic.severity {
+                    Severity::Error => "\x1b[31merror:\x1b[0m",
+                    Severity::Warning => "\x1b[33mwarning:\x1b[0m",
+                    Severity::Note => "\x1b[36mnote:\x1b[0m",
+                };
+                eprintln!("{label} {diagnostic}\n");
+            }
+            let counts = format!(
+                "{errors} error{}, {warnings} warning{}",
+                if errors == 1 { "" } else { "s" },
+                if warnings == 1 { "" } else { "s" }
+            );
+            if errors == 0 {
+                eprintln!("\x1b[32m✓\x1b[0m plz.toml is valid \x1b[2m({counts})\x1b[0m");
+            } else {
+                eprintln!("\x1b[31m✗\x1b[0m {counts}");
+            }
+        }
+    }
+
+    if errors > 0 {
+        bail!("plz.toml is invalid");
+    }
+    Ok(())
+}
+
 fn try_plz_subcommand(task: &[String]) -> Option<Result<()>> {
     let input = task.first()?.as_str();
     match input {
@@ -562,7 +853,7 @@ fn try_plz_subcommand(task: &[String]) -> Option<Result<()>> {
                 Ok(c) => c,
                 Err(e) => return Some(Err(e)),
             };
-            config.check_version();
+            print_warnings(&config);
             let base_dir = config_path.parent().unwrap().to_path_buf();
             let sub = task.get(1).map(|s| s.as_str());
             let interactive = !is_ci::cached()
@@ -600,12 +891,33 @@ fn resolve_task(
     rest: &[String],
     interactive: bool,
 ) -> Result<ResolvedTask> {
-    // 1. Exact match on top-level task (top-level wins)
+    // 1. Exact match on top-level task (top-level wins), then its aliases
     if config.tasks.contains_key(input) {
         return Ok(ResolvedTask::Task(input.to_string()));
     }
+    if let Some(name) = config.task_for_alias(input) {
+        return Ok(ResolvedTask::Task(name.to_string()));
+    }
+
+    // 2. Exact match on "group:task" or "group:alias"
+    if let Some((group_name, task)) = input.split_once(':')
+        && let Some(group) = config.get_group(group_name)
+    {
+        if group.tasks.contains_key(task) {
+            return Ok(ResolvedTask::GroupTask(
+                group_name.to_string(),
+                task.to_string(),
+            ));
+        }
+        if let Some(name) = group.task_for_alias(task) {
+            return Ok(ResolvedTask::GroupTask(
+                group_name.to_string(),
+                name.to_string(),
+            ));
+        }
+    }
 
-    // 2. Check if input matches a taskgroup name
+    // 3. Check if input matches a taskgroup name
     if let Some(group) = config.get_group(input) {
         if rest.is_empty() {
             // `plz <group>` with no task — interactive picker within group
@@ -640,7 +952,7 @@ fn resolve_task(
                 .iter()
                 .map(|name| utils::PickItem {
                     label: name.to_string(),
-                    description: group.tasks[*name].description.clone().unwrap_or_default(),
+                    description: task_summary(&group.tasks[*name]),
                     preview: None,
                 })
                 .collect();
@@ -657,13 +969,16 @@ fn resolve_task(
 
         let task_input = &rest[0];
 
-        // Exact match within group
+        // Exact match within group, then aliases
         if group.tasks.contains_key(task_input.as_str()) {
             return Ok(ResolvedTask::GroupTask(
                 input.to_string(),
                 task_input.clone(),
             ));
         }
+        if let Some(name) = group.task_for_alias(task_input) {
+            return Ok(ResolvedTask::GroupTask(input.to_string(), name.to_string()));
+        }
 
         // Fuzzy match within group
         if !interactive {
@@ -713,7 +1028,7 @@ fn resolve_task(
         }
     }
 
-    // 3. Fall through to fuzzy match on top-level tasks + group tasks
+    // 4. Fall through to fuzzy match on top-level tasks + group tasks
     if !interactive {
         bail!("\"{input}\" isn't a task. Run `plz` to see all commands.");
     }
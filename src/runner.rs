@@ -1,13 +1,485 @@
-use crate::config::{FailHook, PlzConfig, Task};
-use anyhow::{Result, bail};
+use crate::config::{Condition, FailHook, OutputMode, PlzConfig, Shell, Task, VarValue};
+use crate::utils::format_duration;
+use anyhow::{Context, Result, bail};
 use std::cell::RefCell;
-use std::collections::HashSet;
-use std::path::Path;
-use std::process::Command;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
 use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 
 type CompletedDeps = Rc<RefCell<HashSet<String>>>;
 
+/// How a task or command ended. A skipped task counts as done for `depends`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Passed,
+    Skipped,
+    Failed,
+    /// Killed for running past its `timeout`
+    TimedOut(Duration),
+    /// Passed after failing and being retried
+    PassedOnAttempt {
+        attempt: u32,
+        attempts: u32,
+    },
+    /// Stopped, or never started, because another `fail_fast` entry failed
+    Cancelled,
+}
+
+impl Outcome {
+    /// How a task that returned `error` ended.
+    fn of_error(error: &anyhow::Error) -> Outcome {
+        match error.downcast_ref::<TimedOut>() {
+            Some(timed_out) => Outcome::TimedOut(timed_out.after),
+            None if is_cancelled(error) => Outcome::Cancelled,
+            None => Outcome::Failed,
+        }
+    }
+}
+
+/// The error for a command that was killed for running past its `timeout`.
+#[derive(Debug)]
+pub struct TimedOut {
+    pub after: Duration,
+    pub command: String,
+}
+
+impl std::fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Command timed out after {}: {}",
+            format_duration(self.after),
+            self.command
+        )
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+/// The error for a command that a `fail_fast` `run_parallel` stopped, or
+/// never started, after another of its entries failed.
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Cancelled after another command failed")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+fn is_cancelled(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<Cancelled>().is_some()
+}
+
+/// Which stream a line of [`Event::Output`] was written to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// Something that happened while running tasks. Secret values are already
+/// masked in every string.
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// A task didn't run because of its `platforms`, `run_if` or `skip_if`
+    TaskSkipped { task: String, reason: String },
+    /// A command is about to run. Background commands are `run_parallel` entries.
+    CommandStarted { command: String, background: bool },
+    /// A command exited. `exit_code` is `None` if a signal killed it.
+    CommandFinished {
+        command: String,
+        exit_code: Option<i32>,
+    },
+    /// A line a command wrote, without its newline. Only sent to handlers
+    /// that capture output.
+    Output {
+        command: String,
+        stream: Stream,
+        line: String,
+    },
+    /// How each entry of a `run_serial` or `run_parallel` list ended, when
+    /// one of them failed or only passed after a retry
+    Summary { results: Vec<(String, Outcome)> },
+    /// A task failed. `task` names the entry of a `run_serial` or
+    /// `run_parallel` list that failed.
+    TaskFailed { task: Option<String>, error: String },
+    /// A task failed with `error` and will run again after `delay`. `attempt`
+    /// is the one about to start, counting from 1.
+    Retrying {
+        task: String,
+        error: String,
+        attempt: u32,
+        attempts: u32,
+        delay: Duration,
+    },
+    /// A `fail_hook` command is about to run
+    FailHookStarted { command: String },
+    /// A `fail_hook` message
+    FailHookMessage { message: String },
+    /// A `fail_hook` suggests running this command
+    FixSuggested { command: String },
+    /// The suggested command was run and failed
+    FixFailed { command: String },
+}
+
+/// Receives [`Event`]s in place of plz printing them to stderr. Closures
+/// taking `&Event` implement it.
+pub trait EventHandler: Send + Sync {
+    fn event(&self, event: &Event);
+
+    /// Whether to pipe command output and send it as [`Event::Output`]
+    /// instead of letting commands write to the terminal.
+    fn captures_output(&self) -> bool {
+        false
+    }
+}
+
+impl<F: Fn(&Event) + Send + Sync> EventHandler for F {
+    fn event(&self, event: &Event) {
+        self(event)
+    }
+}
+
+/// Where a config's events go: stderr, or the handler set on it.
+#[derive(Clone, Default)]
+pub struct Events(pub Option<Arc<dyn EventHandler>>);
+
+impl Events {
+    fn emit(&self, event: Event) {
+        match self.0 {
+            Some(ref handler) => handler.event(&event),
+            None => print_event(&event),
+        }
+    }
+
+    fn captures_output(&self) -> bool {
+        self.0.as_ref().is_some_and(|h| h.captures_output())
+    }
+}
+
+impl std::fmt::Debug for Events {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let target = if self.0.is_some() {
+            "handler"
+        } else {
+            "stderr"
+        };
+        f.debug_tuple("Events").field(&target).finish()
+    }
+}
+
+/// How the CLI shows events. Inside a `run_parallel` branch, lines get the
+/// branch's label.
+fn print_event(event: &Event) {
+    let text = match event {
+        Event::TaskSkipped { task, reason } => format!("\x1b[2mSkipping {task}: {reason}\x1b[0m"),
+        Event::CommandStarted {
+            command,
+            background,
+        } => {
+            let suffix = if *background { " &" } else { "" };
+            format!("→ {command}{suffix}")
+        }
+        Event::CommandFinished { .. } => return,
+        Event::Output { stream, line, .. } => {
+            let _ = write_stream(*stream, &format!("{line}\n"));
+            return;
+        }
+        Event::Summary { results } => {
+            let parts: Vec<String> = results
+                .iter()
+                .map(|(name, outcome)| match outcome {
+                    Outcome::Passed => format!("\x1b[32m✓ {name}\x1b[0m"),
+                    Outcome::Skipped => format!("\x1b[2m↷ {name} (skipped)\x1b[0m"),
+                    Outcome::Failed => format!("\x1b[31m✗ {name}\x1b[0m"),
+                    Outcome::PassedOnAttempt { attempt, attempts } => {
+                        format!("\x1b[33m✓ {name} (passed on attempt {attempt}/{attempts})\x1b[0m")
+                    }
+                    Outcome::TimedOut(after) => format!(
+                        "\x1b[31m✗ {name} (timed out after {})\x1b[0m",
+                        format_duration(*after)
+                    ),
+                    Outcome::Cancelled => format!("\x1b[2m⊘ {name} (cancelled)\x1b[0m"),
+                })
+                .collect();
+            format!("\nRan {} tasks: {}", results.len(), parts.join("  "))
+        }
+        Event::TaskFailed { task, error } => match task {
+            Some(task) => format!("\n\x1b[31mTask failed:\x1b[0m {task}: {error}"),
+            None => format!("\n\x1b[31mTask failed:\x1b[0m {error}"),
+        },
+        Event::Retrying {
+            task,
+            error,
+            attempt,
+            attempts,
+            delay,
+        } => {
+            let wait = if delay.is_zero() {
+                String::new()
+            } else {
+                format!(" in {}", format_duration(*delay))
+            };
+            format!(
+                "\x1b[31m✗\x1b[0m {error}\n\x1b[33m↻ Retrying {task}{wait} (attempt {attempt}/{attempts})\x1b[0m"
+            )
+        }
+        Event::FailHookStarted { command } => format!("Running fail hook: {command}"),
+        Event::FailHookMessage { message } => format!("⚠️  {message}"),
+        Event::FixSuggested { command } => {
+            format!("\n\x1b[1;33m💡 Try this:\x1b[0m \x1b[1;36m{command}\x1b[0m\n")
+        }
+        Event::FixFailed { .. } => "\x1b[31mFix command failed.\x1b[0m".to_string(),
+    };
+    match current_branch() {
+        Some(branch) => {
+            for line in text.lines() {
+                branch.write(Stream::Stderr, line);
+            }
+        }
+        None => eprintln!("{text}"),
+    }
+}
+
+fn write_stream(stream: Stream, text: &str) -> std::io::Result<()> {
+    use std::io::Write;
+    match stream {
+        Stream::Stdout => {
+            let mut out = std::io::stdout().lock();
+            out.write_all(text.as_bytes()).and_then(|()| out.flush())
+        }
+        Stream::Stderr => std::io::stderr().write_all(text.as_bytes()),
+    }
+}
+
+/// One `run_parallel` entry's output: each line starts with its label, and
+/// with `output = "grouped"` lines are held until the entry finishes.
+#[derive(Clone)]
+struct Branch {
+    prefix: String,
+    group: Option<Arc<Mutex<Group>>>,
+}
+
+/// Lines of a grouped branch, and how many pipes and threads are still
+/// adding to them.
+#[derive(Default)]
+struct Group {
+    lines: Vec<(Stream, String)>,
+    open: usize,
+}
+
+const LABEL_COLORS: &[u8] = &[36, 35, 33, 32, 34, 96, 95, 93];
+
+thread_local! {
+    /// The branch a `plz:` reference in `run_parallel` is running in
+    static BRANCH: RefCell<Option<Branch>> = const { RefCell::new(None) };
+    /// What stops commands run under a `fail_fast` `run_parallel`
+    static CANCEL: RefCell<Option<Cancel>> = const { RefCell::new(None) };
+}
+
+fn current_branch() -> Option<Branch> {
+    BRANCH.with(|b| b.borrow().clone())
+}
+
+impl Branch {
+    /// A branch labelled `label`, padded to `width`. Inside another branch,
+    /// its label comes after the outer one and grouping follows the outer
+    /// branch.
+    fn new(
+        label: &str,
+        index: usize,
+        width: usize,
+        mode: OutputMode,
+        parent: Option<&Branch>,
+    ) -> Self {
+        let color = LABEL_COLORS[index % LABEL_COLORS.len()];
+        let pad = " ".repeat(width.saturating_sub(label.chars().count()));
+        let prefix = format!("\x1b[{color}m[{label}]{pad}\x1b[0m ");
+        let group = match parent {
+            Some(Branch {
+                group: Some(group), ..
+            }) => Some(group.clone()),
+            _ if mode == OutputMode::Grouped => Some(Arc::default()),
+            _ => None,
+        };
+        Branch {
+            prefix: format!("{}{prefix}", parent.map_or("", |p| &p.prefix)),
+            group,
+        }
+    }
+
+    fn write(&self, stream: Stream, line: &str) {
+        let text = format!("{}{}\n", self.prefix, line.trim_end_matches(['\n', '\r']));
+        match self.group {
+            Some(ref group) => lock(group).lines.push((stream, text)),
+            None => {
+                let _ = write_stream(stream, &text);
+            }
+        }
+    }
+
+    fn hold(&self) {
+        if let Some(ref group) = self.group {
+            lock(group).open += 1;
+        }
+    }
+
+    /// Print a grouped branch's lines once nothing holds it open.
+    fn release(&self) {
+        let Some(ref group) = self.group else {
+            return;
+        };
+        let mut group = lock(group);
+        group.open -= 1;
+        if group.open == 0 {
+            // Hold both streams so other branches can't print in between
+            let _stdout = std::io::stdout().lock();
+            let _stderr = std::io::stderr().lock();
+            for (stream, text) in group.lines.drain(..) {
+                let _ = write_stream(stream, &text);
+            }
+        }
+    }
+}
+
+/// Set when an entry of a `fail_fast` `run_parallel` fails. Commands in
+/// nested lists stop when any list around them is cancelled.
+#[derive(Clone, Default)]
+struct Cancel(Vec<Arc<AtomicBool>>);
+
+impl Cancel {
+    /// A flag for a new `fail_fast` list inside `parent`.
+    fn inside(parent: Option<&Cancel>) -> Self {
+        let mut flags = parent.map(|p| p.0.clone()).unwrap_or_default();
+        flags.push(Arc::default());
+        Cancel(flags)
+    }
+
+    fn set(&self) {
+        if let Some(flag) = self.0.last() {
+            flag.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn is_set(&self) -> bool {
+        self.0.iter().any(|flag| flag.load(Ordering::Relaxed))
+    }
+}
+
+fn current_cancel() -> Option<Cancel> {
+    CANCEL.with(|c| c.borrow().clone())
+}
+
+/// A limit on how many commands run at once. Waiters get a slot in the order
+/// they queued for one.
+#[derive(Debug)]
+pub struct Jobs {
+    limit: usize,
+    queue: Mutex<JobQueue>,
+    freed: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct JobQueue {
+    running: usize,
+    queued: u64,
+    next: u64,
+}
+
+/// A place in a [`Jobs`] queue.
+struct Ticket<'a> {
+    jobs: &'a Jobs,
+    number: u64,
+}
+
+/// A running command's share of a [`Jobs`] limit, given back on drop.
+struct Slot<'a>(&'a Jobs);
+
+impl Jobs {
+    pub fn new(limit: usize) -> Self {
+        Jobs {
+            limit: limit.max(1),
+            queue: Mutex::default(),
+            freed: Condvar::new(),
+        }
+    }
+
+    fn queue(&self) -> Ticket<'_> {
+        let mut queue = lock(&self.queue);
+        queue.queued += 1;
+        Ticket {
+            jobs: self,
+            number: queue.queued - 1,
+        }
+    }
+
+    fn acquire(&self) -> Slot<'_> {
+        self.queue().wait()
+    }
+}
+
+impl<'a> Ticket<'a> {
+    fn wait(self) -> Slot<'a> {
+        let jobs = self.jobs;
+        let mut queue = lock(&jobs.queue);
+        while queue.next != self.number || queue.running >= jobs.limit {
+            queue = jobs.freed.wait(queue).unwrap_or_else(|e| e.into_inner());
+        }
+        queue.next += 1;
+        queue.running += 1;
+        // The next ticket may fit too
+        jobs.freed.notify_all();
+        Slot(jobs)
+    }
+}
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        lock(&self.0.queue).running -= 1;
+        self.0.freed.notify_all();
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Labels for `run_parallel` entries: the task for `plz:` references,
+/// otherwise the program, with more of the command where that's ambiguous.
+fn branch_labels(cmds: &[String]) -> Vec<String> {
+    let label = |cmd: &str, words: usize| match parse_task_ref(cmd) {
+        Some(TaskRef::TopLevel(name)) => name,
+        Some(TaskRef::Group(group, task)) => format!("{group}:{task}"),
+        None => cmd
+            .split_whitespace()
+            .take(words)
+            .collect::<Vec<_>>()
+            .join(" "),
+    };
+    let mut labels: Vec<String> = cmds.iter().map(|cmd| label(cmd, 1)).collect();
+    for words in [2, usize::MAX] {
+        let ambiguous: Vec<bool> = labels
+            .iter()
+            .map(|l| labels.iter().filter(|other| *other == l).count() > 1)
+            .collect();
+        for (i, cmd) in cmds.iter().enumerate() {
+            if ambiguous[i] {
+                labels[i] = label(cmd, words);
+            }
+        }
+    }
+    labels
+}
+
 fn new_completed_deps() -> CompletedDeps {
     Rc::new(RefCell::new(HashSet::new()))
 }
@@ -49,7 +521,7 @@ pub fn run_task(
     task_name: &str,
     base_dir: &Path,
     interactive: bool,
-) -> Result<()> {
+) -> Result<Outcome> {
     let task = config.tasks.get(task_name).ok_or_else(|| {
         anyhow::anyhow!("\"{task_name}\" isn't a task. Run `plz` to see all commands.")
     })?;
@@ -71,7 +543,7 @@ pub fn run_task_with_args(
     base_dir: &Path,
     interactive: bool,
     extra_args: &[String],
-) -> Result<()> {
+) -> Result<Outcome> {
     let task = config.tasks.get(task_name).ok_or_else(|| {
         anyhow::anyhow!("\"{task_name}\" isn't a task. Run `plz` to see all commands.")
     })?;
@@ -93,7 +565,7 @@ pub fn run_group_task(
     task_name: &str,
     base_dir: &Path,
     interactive: bool,
-) -> Result<()> {
+) -> Result<Outcome> {
     let task = config.get_group_task(group_name, task_name).ok_or_else(|| {
         anyhow::anyhow!(
             "\"{group_name}:{task_name}\" isn't a task. Run `plz {group_name}` to see group tasks."
@@ -119,7 +591,7 @@ pub fn run_group_task_with_args(
     base_dir: &Path,
     interactive: bool,
     extra_args: &[String],
-) -> Result<()> {
+) -> Result<Outcome> {
     let task = config.get_group_task(group_name, task_name).ok_or_else(|| {
         anyhow::anyhow!(
             "\"{group_name}:{task_name}\" isn't a task. Run `plz {group_name}` to see group tasks."
@@ -138,6 +610,58 @@ pub fn run_group_task_with_args(
     )
 }
 
+/// Run `task_name` in every workspace member that defines it, in parallel
+/// unless `[workspace] serial = true`.
+pub fn run_task_in_members(
+    config: &PlzConfig,
+    task_name: &str,
+    base_dir: &Path,
+    interactive: bool,
+) -> Result<()> {
+    let cmds: Vec<String> = config
+        .workspace_members()
+        .into_iter()
+        .filter(|member| config.get_group_task(member, task_name).is_some())
+        .map(|member| format!("plz:{member}:{task_name}"))
+        .collect();
+    if cmds.is_empty() {
+        bail!("No workspace member defines a \"{task_name}\" task");
+    }
+
+    let serial = config.workspace.as_ref().is_some_and(|w| w.serial);
+    let no_wrap = |cmd: &str| cmd.to_string();
+    if serial {
+        run_serial_commands(
+            config,
+            &cmds,
+            &no_wrap,
+            &Shell::default(),
+            None,
+            base_dir,
+            &[],
+            base_dir,
+            interactive,
+            &new_completed_deps(),
+        )
+    } else {
+        run_parallel_commands(
+            config,
+            &cmds,
+            &no_wrap,
+            &Shell::default(),
+            None,
+            OutputMode::default(),
+            None,
+            config.fail_fast,
+            base_dir,
+            &[],
+            base_dir,
+            interactive,
+            &new_completed_deps(),
+        )
+    }
+}
+
 fn run_dep_task(
     config: &PlzConfig,
     task_name: &str,
@@ -158,6 +682,7 @@ fn run_dep_task(
         &[],
         completed,
     )
+    .map(|_| ())
 }
 
 fn run_dep_group_task(
@@ -184,6 +709,7 @@ fn run_dep_group_task(
         &[],
         completed,
     )
+    .map(|_| ())
 }
 
 fn run_dependencies(
@@ -209,23 +735,68 @@ fn run_dependencies(
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 fn run_task_core(
     config: &PlzConfig,
     task: &Task,
-    _display_name: &str,
+    display_name: &str,
     base_dir: &Path,
     interactive: bool,
     run_hooks: bool,
     extra_args: &[String],
     completed: &CompletedDeps,
-) -> Result<()> {
-    run_dependencies(config, task, base_dir, interactive, completed)?;
+) -> Result<Outcome> {
+    if !task.runs_on_current_platform() {
+        let platforms = task.platforms.as_deref().unwrap_or_default().join(", ");
+        config.events.emit(Event::TaskSkipped {
+            task: display_name.to_string(),
+            reason: format!("only runs on {platforms}"),
+        });
+        return Ok(Outcome::Skipped);
+    }
 
-    let work_dir = match &task.dir {
-        Some(d) => base_dir.join(d),
-        None => base_dir.to_path_buf(),
+    let args = parse_task_args(task, display_name, extra_args, interactive)?;
+    let single_command = (task.script.is_some()
+        || task.run.as_ref().is_some_and(|r| r.0.len() == 1))
+        && task.run_serial.is_none()
+        && task.run_parallel.is_none();
+    if args.is_none() && !extra_args.is_empty() && !single_command {
+        bail!(
+            "Task \"{display_name}\" runs more than one command, so extra arguments can't be appended. Declare `args` and use {{{{args.name}}}} instead."
+        );
+    }
+    let args = args.unwrap_or_default();
+
+    let work_dir = task_work_dir(config, task, &args, base_dir)?;
+    let shell = task_shell(task);
+    let timeout = task_timeout(config, task);
+    let task_env = || -> Result<Vec<(String, String)>> {
+        task_environment(config, task, base_dir)?
+            .into_iter()
+            .map(|(k, v)| Ok((k, substitute_args(&v, &args, false)?)))
+            .collect()
     };
+    if let Some(reason) = skip_reason(task, &work_dir, &shell, timeout, &task_env)? {
+        config.events.emit(Event::TaskSkipped {
+            task: display_name.to_string(),
+            reason,
+        });
+        return Ok(Outcome::Skipped);
+    }
+    if let Some(ref unavailable) = task.unavailable {
+        return Err(unavailable.clone().into());
+    }
 
+    run_dependencies(config, task, base_dir, interactive, completed)?;
+    let env = task_env()?;
+    let expand = |cmds: &[String]| -> Result<Vec<String>> {
+        cmds.iter()
+            .map(|cmd| {
+                let cmd = expand_dynamic_vars(config, task, cmd, base_dir)?;
+                substitute_args(&cmd, &args, true)
+            })
+            .collect()
+    };
     let wrap = |cmd: &str| -> String {
         match task.tool_env.as_deref() {
             Some("uv") if !cmd.starts_with("uv ") && !cmd.starts_with("uvx ") => {
@@ -242,14 +813,17 @@ fn run_task_core(
         }
     };
 
-    let result: Result<()> = (|| {
+    let run_commands = || -> Result<()> {
         if let Some(ref run) = task.run {
-            if run.0.len() == 1 {
-                let cmd = &run.0[0];
+            let run = expand(&run.0)?;
+            if run.len() == 1 {
+                let cmd = &run[0];
                 // Resolve plz: refs before env wrapping so the referenced
                 // task runs with its own env, not the referencer's
                 if let Some(task_ref) = parse_task_ref(cmd) {
                     let (ref_task, display) = resolve_task_ref(config, &task_ref)?;
+                    // Declared args belong to this task, not the one it refers to
+                    let ref_args = if task.args.is_some() { &[] } else { extra_args };
                     run_task_core(
                         config,
                         ref_task,
@@ -257,25 +831,28 @@ fn run_task_core(
                         base_dir,
                         interactive,
                         true,
-                        extra_args,
+                        ref_args,
                         completed,
                     )?;
                 } else {
-                    let wrapped = if extra_args.is_empty() {
+                    let wrapped = if extra_args.is_empty() || task.args.is_some() {
                         wrap(cmd)
                     } else {
                         let args_str = shlex::try_join(extra_args.iter().map(|s| s.as_str()))
                             .map_err(|e| anyhow::anyhow!("Failed to escape arguments: {e}"))?;
                         format!("{} {args_str}", wrap(cmd))
                     };
-                    exec_shell(&wrapped, &work_dir)?;
+                    exec_shell(&wrapped, &shell, timeout, &work_dir, &env, config)?;
                 }
             } else {
                 run_serial_commands(
                     config,
-                    &run.0,
+                    &run,
                     &wrap,
+                    &shell,
+                    timeout,
                     &work_dir,
+                    &env,
                     base_dir,
                     interactive,
                     completed,
@@ -286,9 +863,12 @@ fn run_task_core(
         if let Some(ref cmds) = task.run_serial {
             run_serial_commands(
                 config,
-                cmds,
+                &expand(cmds)?,
                 &wrap,
+                &shell,
+                timeout,
                 &work_dir,
+                &env,
                 base_dir,
                 interactive,
                 completed,
@@ -298,29 +878,284 @@ fn run_task_core(
         if let Some(ref cmds) = task.run_parallel {
             run_parallel_commands(
                 config,
-                cmds,
+                &expand(cmds)?,
                 &wrap,
+                &shell,
+                timeout,
+                task.output.unwrap_or_default(),
+                task.max_parallel,
+                config.fail_fast || task.fail_fast == Some(true),
                 &work_dir,
+                &env,
                 base_dir,
                 interactive,
                 completed,
             )?;
         }
 
+        if let Some(ref script) = task.script {
+            let script = expand_dynamic_vars(config, task, script, base_dir)?;
+            let file = ScriptFile::create(&substitute_args(&script, &args, false)?)?;
+            let path = file.0.to_string_lossy();
+            let mut argv: Vec<&str> = vec![&path];
+            if task.args.is_none() {
+                argv.extend(extra_args.iter().map(|s| s.as_str()));
+            }
+            let argv = shlex::try_join(argv)
+                .map_err(|e| anyhow::anyhow!("Failed to escape arguments: {e}"))?;
+            let cmd = match task.interpreter {
+                Some(ref interpreter) => format!("{interpreter} {argv}"),
+                None => argv,
+            };
+            exec_shell(&wrap(&cmd), &shell, timeout, &work_dir, &env, config)?;
+        }
+
         Ok(())
-    })();
+    };
 
-    if run_hooks {
-        if let Err(ref e) = result
-            && let Some(ref hook) = task.fail_hook
-        {
-            if handle_fail_hook(hook, e, &work_dir, task.tool_env.as_deref(), interactive)? {
-                return Ok(());
+    let attempts = task.retry.as_ref().map_or(1, |r| r.attempts);
+    let mut attempt = 1;
+    let result = loop {
+        let result = run_commands();
+        match (&result, &task.retry) {
+            (Err(e), Some(retry)) if attempt < attempts && !is_cancelled(e) => {
+                let delay = retry.delay_after(attempt);
+                attempt += 1;
+                config.events.emit(Event::Retrying {
+                    task: display_name.to_string(),
+                    error: config.secrets.mask(&e.to_string()),
+                    attempt,
+                    attempts,
+                    delay,
+                });
+                std::thread::sleep(delay);
+            }
+            _ => break result,
+        }
+    };
+
+    if run_hooks
+        && let Err(ref e) = result
+        && let Some(ref hook) = task.fail_hook
+        && handle_fail_hook(
+            &expand_fail_hook(config, task, hook, &args, base_dir)?,
+            e,
+            &work_dir,
+            task.tool_env.as_deref(),
+            &shell,
+            &env,
+            config,
+            interactive,
+        )?
+    {
+        return Ok(Outcome::Passed);
+    }
+
+    result.map(|()| match attempt {
+        1 => Outcome::Passed,
+        _ => Outcome::PassedOnAttempt { attempt, attempts },
+    })
+}
+
+/// Why the task's `run_if` / `skip_if` conditions say it shouldn't run, if
+/// they do. `sh` conditions run like the task's commands, with its shell and
+/// environment.
+fn skip_reason(
+    task: &Task,
+    work_dir: &Path,
+    shell: &Shell,
+    timeout: Option<Duration>,
+    env: &dyn Fn() -> Result<Vec<(String, String)>>,
+) -> Result<Option<String>> {
+    if let Some(ref condition) = task.run_if
+        && let Some(unmet) = unmet_condition(condition, work_dir, shell, timeout, env)?
+    {
+        return Ok(Some(unmet));
+    }
+    if let Some(ref condition) = task.skip_if
+        && unmet_condition(condition, work_dir, shell, timeout, env)?.is_none()
+    {
+        return Ok(Some("skip_if condition is met".to_string()));
+    }
+    Ok(None)
+}
+
+/// Check each part of a condition, returning a description of the first one
+/// that doesn't hold.
+fn unmet_condition(
+    condition: &Condition,
+    work_dir: &Path,
+    shell: &Shell,
+    timeout: Option<Duration>,
+    env: &dyn Fn() -> Result<Vec<(String, String)>>,
+) -> Result<Option<String>> {
+    if let Some(ref file) = condition.file
+        && !work_dir.join(file).exists()
+    {
+        return Ok(Some(format!("{file} doesn't exist")));
+    }
+    if let Some(ref var) = condition.env
+        && std::env::var_os(var).is_none_or(|v| v.is_empty())
+    {
+        return Ok(Some(format!("${var} isn't set")));
+    }
+    if let Some(ci) = condition.ci
+        && is_ci::cached() != ci
+    {
+        let reason = if ci {
+            "not running in CI"
+        } else {
+            "running in CI"
+        };
+        return Ok(Some(reason.to_string()));
+    }
+    if let Some(ref cmd) = condition.sh {
+        let mut command = shell_command(cmd, shell, work_dir, &env()?)?;
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        let mut child = spawn_limited(&mut command, timeout)
+            .with_context(|| format!("Failed to run condition `{cmd}`"))?;
+        let status = wait_limited(&mut child, timeout, cmd)
+            .with_context(|| format!("Failed to run condition `{cmd}`"))?;
+        if !status.success() {
+            return Ok(Some(format!("`{cmd}` failed")));
+        }
+    }
+    Ok(None)
+}
+
+/// Values for a task's declared `args`, keyed by name. Variadic args can hold
+/// any number of values; the others hold exactly one.
+type ArgValues = HashMap<String, Vec<String>>;
+
+/// Match `extra_args` against the task's declared `args`, filling in defaults
+/// and prompting for missing choice args when interactive. Returns `None` for
+/// tasks that don't declare `args`.
+fn parse_task_args(
+    task: &Task,
+    display_name: &str,
+    extra_args: &[String],
+    interactive: bool,
+) -> Result<Option<ArgValues>> {
+    let Some(ref declared) = task.args else {
+        return Ok(None);
+    };
+    let usage = || {
+        let parts: Vec<String> = declared.iter().map(|a| a.usage()).collect();
+        format!("plz {display_name} {}", parts.join(" "))
+    };
+
+    let mut values = ArgValues::new();
+    let mut given = extra_args.iter();
+    for arg in declared {
+        let mut value: Vec<String> = if arg.variadic {
+            given.by_ref().cloned().collect()
+        } else {
+            given.next().cloned().into_iter().collect()
+        };
+        if value.is_empty() {
+            if let Some(ref default) = arg.default {
+                value.push(default.clone());
+            } else if let Some(ref choices) = arg.choices
+                && interactive
+                && !arg.variadic
+            {
+                let items: Vec<(&String, &str, &str)> =
+                    choices.iter().map(|c| (c, c.as_str(), "")).collect();
+                let selected: &String = cliclack::select(format!("Choose {}", arg.name))
+                    .items(&items)
+                    .interact()?;
+                value.push(selected.clone());
+            } else if !arg.variadic {
+                bail!(
+                    "Task \"{display_name}\" is missing argument \"{}\". Usage: {}",
+                    arg.name,
+                    usage()
+                );
             }
         }
+        if let Some(ref choices) = arg.choices
+            && let Some(bad) = value.iter().find(|v| !choices.contains(v))
+        {
+            bail!(
+                "Invalid value \"{bad}\" for argument \"{}\" of task \"{display_name}\". Choices: {}",
+                arg.name,
+                choices.join(", ")
+            );
+        }
+        values.insert(arg.name.clone(), value);
     }
 
-    if result.is_err() { result } else { Ok(()) }
+    let unexpected: Vec<&str> = given.map(|s| s.as_str()).collect();
+    if !unexpected.is_empty() {
+        bail!(
+            "Task \"{display_name}\" got unexpected arguments: {}. Usage: {}",
+            unexpected.join(" "),
+            usage()
+        );
+    }
+    Ok(Some(values))
+}
+
+/// Replace `{{args.name}}` with the argument's values, shell-quoted when the
+/// result is a command.
+fn substitute_args(input: &str, args: &ArgValues, quote: bool) -> Result<String> {
+    let mut result = input.to_string();
+    let mut names: Vec<&String> = args.keys().collect();
+    names.sort();
+    for name in names {
+        let pattern = format!("{{{{args.{name}}}}}");
+        if !result.contains(&pattern) {
+            continue;
+        }
+        let value = if quote {
+            shlex::try_join(args[name].iter().map(|s| s.as_str()))
+                .map_err(|e| anyhow::anyhow!("Failed to escape arguments: {e}"))?
+        } else {
+            args[name].join(" ")
+        };
+        result = result.replace(&pattern, &value);
+    }
+    Ok(result)
+}
+
+/// The directory `task` runs in, with dynamic vars and `{{args.name}}`
+/// filled in.
+fn task_work_dir(
+    config: &PlzConfig,
+    task: &Task,
+    args: &ArgValues,
+    base_dir: &Path,
+) -> Result<PathBuf> {
+    let Some(ref dir) = task.dir else {
+        return Ok(base_dir.to_path_buf());
+    };
+    let dir = expand_dynamic_vars(config, task, dir, base_dir)?;
+    Ok(base_dir.join(substitute_args(&dir, args, false)?))
+}
+
+/// `task`'s fail_hook with dynamic vars and `{{args.name}}` filled in. Only
+/// called once the task failed, so its vars don't run otherwise.
+fn expand_fail_hook(
+    config: &PlzConfig,
+    task: &Task,
+    hook: &FailHook,
+    args: &ArgValues,
+    base_dir: &Path,
+) -> Result<FailHook> {
+    let expand = |text: &str, quote: bool| -> Result<String> {
+        let text = expand_dynamic_vars(config, task, text, base_dir)?;
+        substitute_args(&text, args, quote)
+    };
+    Ok(match hook {
+        FailHook::Command(cmd) => FailHook::Command(expand(cmd, true)?),
+        FailHook::Suggest { suggest_command } => FailHook::Suggest {
+            suggest_command: expand(suggest_command, true)?,
+        },
+        FailHook::Message(msg) => FailHook::Message(expand(msg, false)?),
+    })
 }
 
 fn run_ref_task(
@@ -330,7 +1165,7 @@ fn run_ref_task(
     interactive: bool,
     run_hooks: bool,
     completed: &CompletedDeps,
-) -> Result<()> {
+) -> Result<Outcome> {
     let (task, display) = resolve_task_ref(config, task_ref)?;
     run_task_core(
         config,
@@ -344,42 +1179,671 @@ fn run_ref_task(
     )
 }
 
-fn exec_shell(cmd: &str, work_dir: &Path) -> Result<()> {
-    eprintln!("→ {cmd}");
-    let status = Command::new("/bin/sh")
+/// Environment variables for a task: entries from `env_file` first, then
+/// `environment` on top so explicit values win.
+fn task_environment(
+    config: &PlzConfig,
+    task: &Task,
+    base_dir: &Path,
+) -> Result<Vec<(String, String)>> {
+    let mut env = Vec::new();
+    // Nested `plz` calls load the same profile
+    if let Some(ref profile) = config.active_profile {
+        env.push(("PLZ_PROFILE".to_string(), profile.clone()));
+    }
+    if let Some(ref env_file) = task.env_file {
+        let path = base_dir.join(env_file);
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read env_file {}", path.display()))?;
+        env.extend(crate::utils::parse_dotenv(&content));
+    }
+    if let Some(ref environment) = task.environment {
+        let mut keys: Vec<&String> = environment.keys().collect();
+        keys.sort();
+        for key in keys {
+            let var = &environment[key];
+            let value = if var.is_dynamic() {
+                eval_dynamic_var(config, key, var, base_dir, task_timeout(config, task))?
+            } else {
+                expand_dynamic_vars(config, task, var.text(), base_dir)?
+            };
+            env.push((key.clone(), value));
+        }
+    }
+    Ok(env)
+}
+
+/// Replace `{{name}}` for each dynamic var referenced in `input`, running its
+/// command or reading its file the first time any task needs it.
+fn expand_dynamic_vars(
+    config: &PlzConfig,
+    task: &Task,
+    input: &str,
+    base_dir: &Path,
+) -> Result<String> {
+    let mut result = input.to_string();
+    let mut names: Vec<&String> = task.dynamic_vars.keys().collect();
+    names.sort();
+    for name in names {
+        let pattern = format!("{{{{{name}}}}}");
+        if result.contains(&pattern) {
+            let var = &task.dynamic_vars[name];
+            let value = eval_dynamic_var(config, name, var, base_dir, task_timeout(config, task))?;
+            result = result.replace(&pattern, &value);
+        }
+    }
+    Ok(result)
+}
+
+/// The value of a `{ sh = "..." }` or `{ file = "..." }` var. A command gets
+/// the `timeout` of the task that first needs it.
+fn eval_dynamic_var(
+    config: &PlzConfig,
+    name: &str,
+    var: &VarValue,
+    base_dir: &Path,
+    timeout: Option<Duration>,
+) -> Result<String> {
+    // Vars from a workspace member run in, and read files from, its directory
+    let dir = var.dir().unwrap_or(base_dir);
+    let key = (dir.to_path_buf(), var.source().clone());
+    // Only this var's entry stays locked while it evaluates, so parallel
+    // tasks wait for a var they share but not for unrelated ones
+    let entry = config
+        .var_cache
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry(key)
+        .or_default()
+        .clone();
+    let mut cached = entry.lock().unwrap_or_else(|e| e.into_inner());
+    let value = match *cached {
+        Some(ref value) => value.clone(),
+        None => {
+            let value = match var.source() {
+                VarValue::File { path, .. } => {
+                    let path = dir.join(path);
+                    std::fs::read_to_string(&path)
+                        .with_context(|| {
+                            format!("Failed to read {} for var \"{name}\"", path.display())
+                        })?
+                        .trim_end()
+                        .to_string()
+                }
+                _ => run_var_command(name, var.text(), dir, timeout)?,
+            };
+            *cached = Some(value.clone());
+            value
+        }
+    };
+    if var.is_secret() {
+        config.secrets.add(&value);
+    }
+    Ok(value)
+}
+
+fn run_var_command(name: &str, cmd: &str, dir: &Path, timeout: Option<Duration>) -> Result<String> {
+    use std::io::Read;
+
+    let mut command = Command::new("/bin/sh");
+    command
         .arg("-c")
         .arg(cmd)
+        .current_dir(dir)
+        .env("PLZ_COMMAND", "1")
+        .env("PLZ_INVOCATION_DIR", invocation_dir())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let context = || format!("Failed to evaluate var \"{name}\"");
+    let mut child = spawn_limited(&mut command, timeout).with_context(context)?;
+    let read = |pipe: Option<Box<dyn Read + Send>>| {
+        std::thread::spawn(move || {
+            let mut bytes = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut bytes);
+            }
+            bytes
+        })
+    };
+    let stdout = read(child.stdout.take().map(|p| Box::new(p) as _));
+    let stderr = read(child.stderr.take().map(|p| Box::new(p) as _));
+    let status = wait_limited(&mut child, timeout, cmd);
+    let output = std::process::Output {
+        status: status.with_context(context)?,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    };
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let stderr = stderr.trim();
+        let detail = if stderr.is_empty() {
+            String::new()
+        } else {
+            format!("\n{stderr}")
+        };
+        bail!(
+            "Failed to evaluate var \"{name}\": `{cmd}` exited with code {}{detail}",
+            output.status.code().unwrap_or(-1)
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .trim_end()
+        .to_string())
+}
+
+/// The directory the user ran `plz` from. Nested invocations (tasks that run
+/// `plz ...`) keep the outermost value.
+fn invocation_dir() -> OsString {
+    std::env::var_os("PLZ_INVOCATION_DIR")
+        .or_else(|| std::env::current_dir().ok().map(Into::into))
+        .unwrap_or_default()
+}
+
+/// The time limit for each command `task` runs: `--timeout`, then its own
+/// `timeout`.
+fn task_timeout(config: &PlzConfig, task: &Task) -> Option<Duration> {
+    config.timeout.or(task.timeout.map(|t| t.0))
+}
+
+/// The shell a task's commands run under: its own `shell` (or one from
+/// `[extends]`), then `shell` in ~/.plz/settings.toml, then `/bin/sh -c`.
+fn task_shell(task: &Task) -> Shell {
+    static USER_SHELL: OnceLock<Option<Shell>> = OnceLock::new();
+    task.shell
+        .as_ref()
+        .or_else(|| {
+            USER_SHELL
+                .get_or_init(|| crate::settings::load().shell)
+                .as_ref()
+        })
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// A command that runs `cmd` under `shell` in `work_dir`.
+fn shell_command(
+    cmd: &str,
+    shell: &Shell,
+    work_dir: &Path,
+    env: &[(String, String)],
+) -> Result<Command> {
+    let mut command = match shell {
+        Shell::Program(argv) if argv.is_empty() => {
+            let mut command = Command::new("/bin/sh");
+            command.arg("-c").arg(cmd);
+            command
+        }
+        Shell::Program(argv) => {
+            let mut command = Command::new(&argv[0]);
+            command.args(&argv[1..]).arg(cmd);
+            command
+        }
+        Shell::Direct => {
+            let argv = shlex::split(cmd)
+                .filter(|argv| !argv.is_empty())
+                .with_context(|| format!("Can't split command into arguments: {cmd}"))?;
+            let mut command = Command::new(&argv[0]);
+            command.args(&argv[1..]);
+            command
+        }
+    };
+    command
         .current_dir(work_dir)
+        .envs(env.iter().map(|(k, v)| (k, v)))
         .env("PLZ_COMMAND", "1")
-        .status()?;
+        .env("PLZ_INVOCATION_DIR", invocation_dir());
+    Ok(command)
+}
+
+fn exec_shell(
+    cmd: &str,
+    shell: &Shell,
+    timeout: Option<Duration>,
+    work_dir: &Path,
+    env: &[(String, String)],
+    config: &PlzConfig,
+) -> Result<()> {
+    run_command(cmd, false, shell, timeout, work_dir, env, config)
+}
+
+/// Run `cmd` once a `-j` slot is free. `background` marks a `run_parallel`
+/// entry.
+fn run_command(
+    cmd: &str,
+    background: bool,
+    shell: &Shell,
+    timeout: Option<Duration>,
+    work_dir: &Path,
+    env: &[(String, String)],
+    config: &PlzConfig,
+) -> Result<()> {
+    let cancel = current_cancel();
+    let _slot = config.jobs.as_deref().map(Jobs::acquire);
+    if cancel.as_ref().is_some_and(Cancel::is_set) {
+        bail!(Cancelled);
+    }
+    let shown = config.secrets.mask(cmd);
+    config.events.emit(Event::CommandStarted {
+        command: shown.clone(),
+        background,
+    });
+    let mut command = shell_command(cmd, shell, work_dir, env)?;
+    let branch = current_branch();
+    let own_group = timeout.is_some() || cancel.is_some();
+    let (mut child, pipes) = spawn(&mut command, &shown, own_group, branch.as_ref(), config)
+        .with_context(|| format!("Failed to run: {shown}"))?;
+    // A command outside any `run_parallel` list gets the terminal when plz
+    // has it, so it can still read from it in a group of its own.
+    let foreground = own_group && !background && branch.is_none() && std::io::stdin().is_terminal();
+    let terminal = foreground.then(|| Terminal::hand_to(&child));
+    let deadline = timeout.and_then(deadline_after);
+    let exit = wait_until(&mut child, own_group, deadline, cancel.as_ref());
+    if let Some(terminal) = terminal {
+        terminal.take_back(&exit);
+    }
+    for pipe in pipes {
+        let _ = pipe.join();
+    }
+    let exit = exit.with_context(|| format!("Failed to run: {shown}"))?;
+    let status = match exit {
+        Exit::Exited(status) => Some(status),
+        _ => None,
+    };
+    config.events.emit(Event::CommandFinished {
+        command: shown.clone(),
+        exit_code: status.and_then(|s| s.code()),
+    });
+    let status = match exit {
+        Exit::Exited(status) => status,
+        Exit::TimedOut => bail!(TimedOut {
+            after: timeout.unwrap_or_default(),
+            command: shown,
+        }),
+        Exit::Cancelled => bail!(Cancelled),
+    };
 
     if !status.success() {
         bail!(
-            "Command failed with exit code {}: {cmd}",
+            "Command failed with exit code {}: {shown}",
             status.code().unwrap_or(-1)
         );
     }
     Ok(())
 }
 
+/// How long a timed-out or cancelled command gets to exit after SIGTERM
+/// before its process group is sent SIGKILL.
+const KILL_GRACE: Duration = Duration::from_secs(5);
+
+/// When a command given `timeout` must have exited by. A timeout too long for
+/// the clock to represent never runs out.
+fn deadline_after(timeout: Duration) -> Option<Instant> {
+    Instant::now().checked_add(timeout)
+}
+
+/// How a command plz waited for ended.
+enum Exit {
+    Exited(ExitStatus),
+    TimedOut,
+    Cancelled,
+}
+
+/// Wait for `child` to exit. Past `deadline`, or once `cancel` is set, it is
+/// sent SIGTERM, then SIGKILL after [`KILL_GRACE`]. With `own_group`, the
+/// signals go to the process group it leads, which also gets any SIGINT or
+/// SIGTERM plz receives while waiting.
+fn wait_until(
+    child: &mut Child,
+    own_group: bool,
+    deadline: Option<Instant>,
+    cancel: Option<&Cancel>,
+) -> std::io::Result<Exit> {
+    let _live = own_group.then(|| LiveGroup::register(child));
+    if deadline.is_none() && cancel.is_none() {
+        return child.wait().map(Exit::Exited);
+    }
+    let cancelled = || cancel.is_some_and(Cancel::is_set);
+    if let Some(status) = poll_until(child, deadline, &cancelled)? {
+        return Ok(Exit::Exited(status));
+    }
+    let exit = match deadline {
+        Some(deadline) if Instant::now() >= deadline => Exit::TimedOut,
+        _ => Exit::Cancelled,
+    };
+    signal_child(child, own_group, libc::SIGTERM);
+    poll_until(child, Some(Instant::now() + KILL_GRACE), &|| false)?;
+    // Also reaches processes that outlived the command itself
+    signal_child(child, own_group, libc::SIGKILL);
+    child.wait()?;
+    Ok(exit)
+}
+
+/// Spawn a command that isn't shown or given the terminal, in a process
+/// group of its own when it has a `timeout`.
+fn spawn_limited(command: &mut Command, timeout: Option<Duration>) -> std::io::Result<Child> {
+    use std::os::unix::process::CommandExt;
+
+    if timeout.is_some() {
+        forward_signals();
+        command.process_group(0);
+    }
+    command.spawn()
+}
+
+/// Wait for a child from [`spawn_limited`], failing with [`TimedOut`] if it
+/// runs past `timeout`.
+fn wait_limited(child: &mut Child, timeout: Option<Duration>, cmd: &str) -> Result<ExitStatus> {
+    let deadline = timeout.and_then(deadline_after);
+    match wait_until(child, timeout.is_some(), deadline, None)? {
+        Exit::Exited(status) => Ok(status),
+        _ => bail!(TimedOut {
+            after: timeout.unwrap_or_default(),
+            command: cmd.to_string(),
+        }),
+    }
+}
+
+/// The terminal on plz's stdin, handed to a command's process group while it
+/// runs. Holds plz's own group, if plz had the terminal to give.
+struct Terminal(Option<libc::pid_t>);
+
+impl Terminal {
+    fn hand_to(child: &Child) -> Self {
+        let Ok(pid) = libc::pid_t::try_from(child.id()) else {
+            return Terminal(None);
+        };
+        // SAFETY: getpgrp and tcgetpgrp have no memory-safety preconditions
+        let ours = unsafe { libc::getpgrp() };
+        // A plz running in the background mustn't take the terminal from
+        // whoever has it
+        if unsafe { libc::tcgetpgrp(libc::STDIN_FILENO) } != ours {
+            return Terminal(None);
+        }
+        if !set_foreground(pid) {
+            return Terminal(None);
+        }
+        // Reading the terminal before it was handed over stopped the command
+        // SAFETY: kill has no memory-safety preconditions
+        unsafe {
+            libc::kill(-pid, libc::SIGCONT);
+        }
+        Terminal(Some(ours))
+    }
+
+    /// Give the terminal back to plz. A Ctrl-C there only reached the
+    /// command, so it is passed on to plz, which it would have stopped had
+    /// the command stayed in plz's group.
+    fn take_back(self, exit: &std::io::Result<Exit>) {
+        use std::os::unix::process::ExitStatusExt;
+
+        let Some(ours) = self.0 else {
+            return;
+        };
+        set_foreground(ours);
+        if let Ok(Exit::Exited(status)) = exit
+            && status.signal() == Some(libc::SIGINT)
+        {
+            // SAFETY: raise has no memory-safety preconditions
+            unsafe {
+                libc::raise(libc::SIGINT);
+            }
+        }
+    }
+}
+
+/// Make `group` the terminal's foreground process group.
+fn set_foreground(group: libc::pid_t) -> bool {
+    // SAFETY: the signal sets are initialized by sigemptyset or
+    // pthread_sigmask before they are read
+    unsafe {
+        // Taking the terminal back from outside its foreground group sends
+        // plz SIGTTOU, which would stop it
+        let mut ttou: libc::sigset_t = std::mem::zeroed();
+        let mut previous: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut ttou);
+        libc::sigaddset(&mut ttou, libc::SIGTTOU);
+        libc::pthread_sigmask(libc::SIG_BLOCK, &ttou, &mut previous);
+        let set = libc::tcsetpgrp(libc::STDIN_FILENO, group) == 0;
+        libc::pthread_sigmask(libc::SIG_SETMASK, &previous, std::ptr::null_mut());
+        set
+    }
+}
+
+/// Wait for `child` to exit, giving up at `deadline` or once `stop` returns
+/// true.
+fn poll_until(
+    child: &mut Child,
+    deadline: Option<Instant>,
+    stop: &dyn Fn() -> bool,
+) -> std::io::Result<Option<ExitStatus>> {
+    const POLL: Duration = Duration::from_millis(20);
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        let now = Instant::now();
+        if stop() || deadline.is_some_and(|d| now >= d) {
+            return Ok(None);
+        }
+        std::thread::sleep(deadline.map_or(POLL, |d| (d - now).min(POLL)));
+    }
+}
+
+/// Send `signal` to `child`, or to the process group it leads.
+fn signal_child(child: &Child, own_group: bool, signal: libc::c_int) {
+    if let Ok(pid) = libc::pid_t::try_from(child.id()) {
+        let target = if own_group { -pid } else { pid };
+        // SAFETY: kill has no memory-safety preconditions
+        unsafe {
+            libc::kill(target, signal);
+        }
+    }
+}
+
+/// Process groups of the commands plz is waiting on, so a SIGINT or SIGTERM
+/// sent to plz reaches them too. Zero marks a free slot. The signal handler
+/// can't take a lock, so this is a fixed table of atomics; groups past its
+/// size aren't forwarded to.
+static LIVE_GROUPS: [AtomicI32; 256] = [const { AtomicI32::new(0) }; 256];
+
+/// A slot in [`LIVE_GROUPS`], freed when dropped.
+struct LiveGroup(Option<usize>);
+
+impl LiveGroup {
+    fn register(child: &Child) -> Self {
+        let Ok(pid) = libc::pid_t::try_from(child.id()) else {
+            return LiveGroup(None);
+        };
+        LiveGroup(LIVE_GROUPS.iter().position(|slot| {
+            slot.compare_exchange(0, pid, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+        }))
+    }
+}
+
+impl Drop for LiveGroup {
+    fn drop(&mut self) {
+        if let Some(slot) = self.0 {
+            LIVE_GROUPS[slot].store(0, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Have SIGINT and SIGTERM go to every group in [`LIVE_GROUPS`] before they
+/// stop plz. Leaves alone signals that are ignored or already handled, e.g.
+/// by a program using plz as a library.
+fn forward_signals() {
+    static INSTALLED: std::sync::Once = std::sync::Once::new();
+    INSTALLED.call_once(|| {
+        for signal in [libc::SIGINT, libc::SIGTERM] {
+            // SAFETY: the handler only makes async-signal-safe calls
+            unsafe {
+                let mut current: libc::sigaction = std::mem::zeroed();
+                libc::sigaction(signal, std::ptr::null(), &mut current);
+                if current.sa_sigaction != libc::SIG_DFL {
+                    continue;
+                }
+                let mut action: libc::sigaction = std::mem::zeroed();
+                action.sa_sigaction = forward_signal as extern "C" fn(libc::c_int) as usize;
+                action.sa_flags = libc::SA_RESETHAND;
+                libc::sigemptyset(&mut action.sa_mask);
+                libc::sigaction(signal, &action, std::ptr::null_mut());
+            }
+        }
+    });
+}
+
+extern "C" fn forward_signal(signal: libc::c_int) {
+    for slot in &LIVE_GROUPS {
+        let pid = slot.load(Ordering::SeqCst);
+        if pid != 0 {
+            // SAFETY: kill is async-signal-safe
+            unsafe {
+                libc::kill(-pid, signal);
+            }
+        }
+    }
+    // SA_RESETHAND put back the default action, which now stops plz
+    // SAFETY: raise is async-signal-safe
+    unsafe {
+        libc::raise(signal);
+    }
+}
+
+/// Spawn `command`. When secrets are masked in task output or the event
+/// handler captures it, stdout and stderr are piped and copied line by line
+/// from threads; join them after waiting on the child so the last lines
+/// aren't lost.
+fn spawn(
+    command: &mut Command,
+    shown: &str,
+    own_group: bool,
+    branch: Option<&Branch>,
+    config: &PlzConfig,
+) -> std::io::Result<(Child, Vec<std::thread::JoinHandle<()>>)> {
+    use std::io::{BufRead, BufReader, Read};
+    use std::os::unix::process::CommandExt;
+
+    // A process group of its own lets a timeout or `fail_fast` kill
+    // everything the command started
+    if own_group {
+        forward_signals();
+        command.process_group(0);
+    }
+
+    let capture = config.events.captures_output();
+    if !capture && !config.secrets.masks_output() && branch.is_none() {
+        return command.spawn().map(|child| (child, Vec::new()));
+    }
+
+    let secrets = Arc::new(config.secrets.snapshot());
+    let copy = |from: Box<dyn Read + Send>, stream: Stream| {
+        let secrets = secrets.clone();
+        let events = config.events.clone();
+        let command = shown.to_string();
+        let branch = branch.cloned();
+        if let Some(ref branch) = branch {
+            branch.hold();
+        }
+        std::thread::spawn(move || {
+            let mut reader = BufReader::new(from);
+            let mut line = Vec::new();
+            while reader.read_until(b'\n', &mut line).is_ok_and(|n| n > 0) {
+                let masked = secrets.mask(&String::from_utf8_lossy(&line));
+                line.clear();
+                if capture {
+                    events.emit(Event::Output {
+                        command: command.clone(),
+                        stream,
+                        line: masked.trim_end_matches(['\n', '\r']).to_string(),
+                    });
+                    continue;
+                }
+                match branch {
+                    Some(ref branch) => branch.write(stream, &masked),
+                    None => {
+                        if write_stream(stream, &masked).is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+            if let Some(ref branch) = branch {
+                branch.release();
+            }
+        })
+    };
+
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut pipes = Vec::new();
+    if let Some(stdout) = child.stdout.take() {
+        pipes.push(copy(Box::new(stdout), Stream::Stdout));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        pipes.push(copy(Box::new(stderr), Stream::Stderr));
+    }
+    Ok((child, pipes))
+}
+
+/// A task's `script`, written to a temp file that's removed when dropped.
+struct ScriptFile(PathBuf);
+
+impl ScriptFile {
+    fn create(script: &str) -> Result<Self> {
+        use std::io::Write;
+        use std::os::unix::fs::OpenOptionsExt;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let name = format!(
+            "plz-script-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        );
+        let path = std::env::temp_dir().join(name);
+        // Executable so a #! line works without an interpreter
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o700)
+            .open(&path)
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+        let script_file = ScriptFile(path);
+        file.write_all(script.trim_start().as_bytes())
+            .with_context(|| format!("Failed to write {}", script_file.0.display()))?;
+        Ok(script_file)
+    }
+}
+
+impl Drop for ScriptFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
 struct DeferredFailure {
     name: String,
     error: anyhow::Error,
 }
 
-fn print_summary(results: &[(String, bool)]) {
-    let total = results.len();
-    let parts: Vec<String> = results
+/// Whether any entry only passed after a retry, which is worth a summary
+/// even when nothing failed.
+fn any_retried(results: &[(String, Outcome)]) -> bool {
+    results
         .iter()
-        .map(|(name, ok)| {
-            if *ok {
-                format!("\x1b[32m✓ {name}\x1b[0m")
-            } else {
-                format!("\x1b[31m✗ {name}\x1b[0m")
-            }
-        })
+        .any(|(_, outcome)| matches!(outcome, Outcome::PassedOnAttempt { .. }))
+}
+
+fn print_summary(results: &[(String, Outcome)], config: &PlzConfig) {
+    let results = results
+        .iter()
+        .map(|(name, outcome)| (config.secrets.mask(name), *outcome))
         .collect();
-    eprintln!("\nRan {total} tasks: {}", parts.join("  "));
+    config.events.emit(Event::Summary { results });
 }
 
 fn lookup_task_for_failure<'a>(config: &'a PlzConfig, name: &str) -> Option<&'a Task> {
@@ -400,23 +1864,46 @@ fn handle_deferred_failures(
 ) -> Result<()> {
     for (i, failure) in failures.iter().enumerate() {
         let task = lookup_task_for_failure(config, &failure.name);
-        let hook = task.and_then(|t| t.fail_hook.as_ref());
+        // Referenced tasks run without extra arguments, so only defaults apply
+        let args = task
+            .and_then(|t| parse_task_args(t, &failure.name, &[], false).ok())
+            .flatten()
+            .unwrap_or_default();
+        let hook = task
+            .and_then(|t| t.fail_hook.as_ref().map(|h| (t, h)))
+            .map(|(t, h)| expand_fail_hook(config, t, h, &args, base_dir))
+            .transpose()?;
+        let hook = hook.as_ref();
 
         if let Some(hook) = hook {
-            let task_work_dir = task
-                .and_then(|t| t.dir.as_ref())
-                .map(|d| base_dir.join(d))
-                .unwrap_or_else(|| base_dir.to_path_buf());
+            let task_work_dir = match task {
+                Some(task) => task_work_dir(config, task, &args, base_dir)?,
+                None => base_dir.to_path_buf(),
+            };
             let tool_env = task.and_then(|t| t.tool_env.as_deref());
+            let shell = task.map(task_shell).unwrap_or_default();
+            let env = task
+                .map(|t| task_environment(config, t, base_dir))
+                .transpose()?
+                .unwrap_or_default();
 
-            if handle_fail_hook(hook, &failure.error, &task_work_dir, tool_env, interactive)? {
+            if handle_fail_hook(
+                hook,
+                &failure.error,
+                &task_work_dir,
+                tool_env,
+                &shell,
+                &env,
+                config,
+                interactive,
+            )? {
                 continue;
             }
         } else {
-            eprintln!(
-                "\n\x1b[31mTask failed:\x1b[0m {}: {}",
-                failure.name, failure.error
-            );
+            config.events.emit(Event::TaskFailed {
+                task: Some(config.secrets.mask(&failure.name)),
+                error: config.secrets.mask(&failure.error.to_string()),
+            });
         }
 
         let has_more = i + 1 < failures.len();
@@ -434,16 +1921,20 @@ fn handle_deferred_failures(
     bail!("One or more tasks failed");
 }
 
+#[allow(clippy::too_many_arguments)]
 fn run_serial_commands(
     config: &PlzConfig,
     cmds: &[String],
     wrap: &dyn Fn(&str) -> String,
+    shell: &Shell,
+    timeout: Option<Duration>,
     work_dir: &Path,
+    env: &[(String, String)],
     base_dir: &Path,
     interactive: bool,
     completed: &CompletedDeps,
 ) -> Result<()> {
-    let mut task_results: Vec<(String, bool)> = Vec::new();
+    let mut task_results: Vec<(String, Outcome)> = Vec::new();
     let mut failures: Vec<DeferredFailure> = Vec::new();
 
     for cmd in cmds {
@@ -453,9 +1944,10 @@ fn run_serial_commands(
                 TaskRef::Group(g, t) => format!("{g}:{t}"),
             };
             match run_ref_task(config, &task_ref, base_dir, interactive, false, completed) {
-                Ok(()) => task_results.push((display, true)),
+                Ok(outcome) => task_results.push((display, outcome)),
+                Err(e) if is_cancelled(&e) => return Err(e),
                 Err(e) => {
-                    task_results.push((display.clone(), false));
+                    task_results.push((display.clone(), Outcome::of_error(&e)));
                     failures.push(DeferredFailure {
                         name: display,
                         error: e,
@@ -463,70 +1955,102 @@ fn run_serial_commands(
                 }
             }
         } else {
-            exec_shell(&wrap(cmd), work_dir)?;
+            exec_shell(&wrap(cmd), shell, timeout, work_dir, env, config)?;
         }
     }
 
+    if (!failures.is_empty() && task_results.len() > 1) || any_retried(&task_results) {
+        print_summary(&task_results, config);
+    }
     if !failures.is_empty() {
-        if task_results.len() > 1 {
-            print_summary(&task_results);
-        }
         return handle_deferred_failures(config, failures, base_dir, interactive);
     }
 
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 fn run_parallel_commands(
     config: &PlzConfig,
     cmds: &[String],
     wrap: &dyn Fn(&str) -> String,
+    shell: &Shell,
+    timeout: Option<Duration>,
+    output: OutputMode,
+    max_parallel: Option<usize>,
+    fail_fast: bool,
     work_dir: &Path,
+    env: &[(String, String)],
     base_dir: &Path,
     interactive: bool,
     _completed: &CompletedDeps,
 ) -> Result<()> {
-    let mut children = Vec::new();
-    let mut plz_refs: Vec<TaskRef> = Vec::new();
-
-    for cmd in cmds {
-        if let Some(task_ref) = parse_task_ref(cmd) {
-            plz_refs.push(task_ref);
-        } else {
-            let wrapped = wrap(cmd);
-            eprintln!("→ {wrapped} &");
-            let child = Command::new("/bin/sh")
-                .arg("-c")
-                .arg(&wrapped)
-                .current_dir(work_dir)
-                .env("PLZ_COMMAND", "1")
-                .spawn()?;
-            children.push((wrapped, child));
-        }
-    }
-
-    let mut task_results: Vec<(String, bool)> = Vec::new();
-    let mut failures: Vec<DeferredFailure> = Vec::new();
+    let parent = current_branch();
+    let labels = branch_labels(cmds);
+    let width = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let branches = labels.iter().enumerate().map(|(i, label)| match output {
+        OutputMode::Raw => parent.clone(),
+        _ => Some(Branch::new(label, i, width, output, parent.as_ref())),
+    });
+    let limit = max_parallel.map(Jobs::new);
+    let cancel = match fail_fast {
+        true => Some(Cancel::inside(current_cancel().as_ref())),
+        false => current_cancel(),
+    };
 
-    // Run plz: refs concurrently in scoped threads. Each thread gets a fresh
-    // CompletedDeps because Rc isn't Send and parallel branches are independent.
-    let ref_outcomes: Vec<(String, Result<()>)> = std::thread::scope(|s| {
-        let handles: Vec<_> = plz_refs
+    // Each entry waits for a `max_parallel` slot in its own thread, in list
+    // order. Commands also take a `-j` slot when they start, so the limit
+    // holds across nested `plz:` references. Each reference gets a fresh
+    // CompletedDeps because Rc isn't Send and parallel branches are
+    // independent. With `fail_fast`, the first failure cancels the rest.
+    let outcomes: Vec<(String, Result<Outcome>)> = std::thread::scope(|s| {
+        let handles: Vec<_> = cmds
             .iter()
-            .map(|task_ref| {
-                let display = match task_ref {
-                    TaskRef::TopLevel(n) => n.clone(),
-                    TaskRef::Group(g, t) => format!("{g}:{t}"),
+            .zip(branches)
+            .map(|(cmd, branch)| {
+                let ticket = limit.as_ref().map(Jobs::queue);
+                let task_ref = parse_task_ref(cmd);
+                let (display, wrapped) = match task_ref {
+                    Some(TaskRef::TopLevel(ref n)) => (n.clone(), String::new()),
+                    Some(TaskRef::Group(ref g, ref t)) => (format!("{g}:{t}"), String::new()),
+                    None => {
+                        let wrapped = wrap(cmd);
+                        (config.secrets.mask(&wrapped), wrapped)
+                    }
                 };
+                let cancel = cancel.clone();
                 let handle = s.spawn(move || {
-                    run_ref_task(
-                        config,
-                        task_ref,
-                        base_dir,
-                        interactive,
-                        false,
-                        &new_completed_deps(),
-                    )
+                    let _slot = ticket.map(Ticket::wait);
+                    if let Some(ref branch) = branch {
+                        branch.hold();
+                    }
+                    BRANCH.with(|b| b.borrow_mut().clone_from(&branch));
+                    CANCEL.with(|c| c.borrow_mut().clone_from(&cancel));
+                    let result = match task_ref {
+                        Some(_) if cancel.as_ref().is_some_and(Cancel::is_set) => {
+                            Err(Cancelled.into())
+                        }
+                        Some(ref task_ref) => run_ref_task(
+                            config,
+                            task_ref,
+                            base_dir,
+                            interactive,
+                            false,
+                            &new_completed_deps(),
+                        ),
+                        None => run_command(&wrapped, true, shell, timeout, work_dir, env, config)
+                            .map(|()| Outcome::Passed),
+                    };
+                    if let Some(ref branch) = branch {
+                        branch.release();
+                    }
+                    if fail_fast
+                        && let (Some(cancel), Err(e)) = (&cancel, &result)
+                        && !is_cancelled(e)
+                    {
+                        cancel.set();
+                    }
+                    result
                 });
                 (display, handle)
             })
@@ -544,11 +2068,18 @@ fn run_parallel_commands(
             .collect()
     });
 
-    for (display, res) in ref_outcomes {
+    let mut task_results: Vec<(String, Outcome)> = Vec::new();
+    let mut failures: Vec<DeferredFailure> = Vec::new();
+    let mut cancelled = false;
+    for (display, res) in outcomes {
         match res {
-            Ok(()) => task_results.push((display, true)),
+            Ok(outcome) => task_results.push((display, outcome)),
+            Err(e) if is_cancelled(&e) => {
+                task_results.push((display, Outcome::Cancelled));
+                cancelled = true;
+            }
             Err(e) => {
-                task_results.push((display.clone(), false));
+                task_results.push((display.clone(), Outcome::of_error(&e)));
                 failures.push(DeferredFailure {
                     name: display,
                     error: e,
@@ -557,40 +2088,39 @@ fn run_parallel_commands(
         }
     }
 
-    for (cmd, mut child) in children {
-        let status = child.wait()?;
-        if !status.success() {
-            task_results.push((cmd.clone(), false));
-            failures.push(DeferredFailure {
-                name: cmd.clone(),
-                error: anyhow::anyhow!(
-                    "Command failed with exit code {}: {cmd}",
-                    status.code().unwrap_or(-1)
-                ),
-            });
-        } else {
-            task_results.push((cmd, true));
-        }
+    if (!failures.is_empty() && task_results.len() > 1) || any_retried(&task_results) {
+        print_summary(&task_results, config);
     }
-
     if !failures.is_empty() {
-        if task_results.len() > 1 {
-            print_summary(&task_results);
-        }
         return handle_deferred_failures(config, failures, base_dir, interactive);
     }
+    // Only a list around this one can have cancelled it
+    if cancelled {
+        bail!(Cancelled);
+    }
 
     Ok(())
 }
 
 /// Returns true if the fail hook resolved the failure (e.g. suggestion was taken and succeeded).
+#[allow(clippy::too_many_arguments)]
 fn handle_fail_hook(
     hook: &FailHook,
     error: &anyhow::Error,
     work_dir: &Path,
     tool_env: Option<&str>,
+    shell: &Shell,
+    env: &[(String, String)],
+    config: &PlzConfig,
     interactive: bool,
 ) -> Result<bool> {
+    let error = config.secrets.mask(&error.to_string());
+    let failed = || {
+        config.events.emit(Event::TaskFailed {
+            task: None,
+            error: error.clone(),
+        })
+    };
     let wrap = |cmd: &str| -> String {
         match tool_env {
             Some("uv") if !cmd.starts_with("uv ") && !cmd.starts_with("uvx ") => {
@@ -610,28 +2140,33 @@ fn handle_fail_hook(
     match hook {
         FailHook::Command(cmd) => {
             let wrapped = wrap(cmd);
-            eprintln!("\n\x1b[31mTask failed:\x1b[0m {error}");
-            eprintln!("Running fail hook: {wrapped}");
-            let _ = exec_shell(&wrapped, work_dir);
+            failed();
+            config.events.emit(Event::FailHookStarted {
+                command: config.secrets.mask(&wrapped),
+            });
+            let _ = exec_shell(&wrapped, shell, None, work_dir, env, config);
         }
         FailHook::Message(msg) => {
-            eprintln!("\n\x1b[31mTask failed:\x1b[0m {error}");
-            eprintln!("⚠️  {msg}");
+            failed();
+            config.events.emit(Event::FailHookMessage {
+                message: config.secrets.mask(msg),
+            });
         }
         FailHook::Suggest { suggest_command } => {
-            eprintln!("\n\x1b[31mTask failed:\x1b[0m {error}");
+            let shown = config.secrets.mask(suggest_command);
+            failed();
             if !interactive {
-                eprintln!("\n\x1b[1;33m💡 Try this:\x1b[0m \x1b[1;36m{suggest_command}\x1b[0m\n");
+                config.events.emit(Event::FixSuggested { command: shown });
             } else {
-                let run_it: bool = cliclack::confirm(format!("💡 Try this: `{suggest_command}`?"))
+                let run_it: bool = cliclack::confirm(format!("💡 Try this: `{shown}`?"))
                     .initial_value(true)
                     .interact()
                     .unwrap_or(false);
                 if run_it {
-                    if exec_shell(suggest_command, work_dir).is_ok() {
+                    if exec_shell(suggest_command, shell, None, work_dir, env, config).is_ok() {
                         return Ok(true);
                     }
-                    eprintln!("\x1b[31mFix command failed.\x1b[0m");
+                    config.events.emit(Event::FixFailed { command: shown });
                 }
             }
         }
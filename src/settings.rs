@@ -1,3 +1,5 @@
+use crate::config::Shell;
+use serde::Deserialize;
 use std::env;
 use std::path::{Path, PathBuf};
 
@@ -24,6 +26,8 @@ pub const ALL_SETTINGS: &[SettingEntry] = &[
 pub struct Settings {
     pub show_hints: bool,
     pub check_for_updates: bool,
+    /// Shell for tasks that don't choose one, instead of `/bin/sh -c`
+    pub shell: Option<Shell>,
 }
 
 impl Default for Settings {
@@ -31,6 +35,7 @@ impl Default for Settings {
         Self {
             show_hints: true,
             check_for_updates: false,
+            shell: None,
         }
     }
 }
@@ -78,12 +83,17 @@ pub fn load_raw(path: &Path) -> Vec<(&'static str, bool, bool)> {
         .collect()
 }
 
+/// Write the toggles in `values`, keeping any other settings (like `shell`)
+/// already in the file.
 pub fn save(path: &Path, values: &[(&str, bool)]) -> anyhow::Result<()> {
-    let mut lines = Vec::new();
+    let mut doc = std::fs::read_to_string(path)
+        .ok()
+        .and_then(|c| c.parse::<toml_edit::DocumentMut>().ok())
+        .unwrap_or_default();
     for (key, value) in values {
-        lines.push(format!("{key} = {value}"));
+        doc[key] = toml_edit::value(*value);
     }
-    std::fs::write(path, lines.join("\n") + "\n")?;
+    std::fs::write(path, doc.to_string())?;
     Ok(())
 }
 
@@ -105,9 +115,18 @@ pub fn load_from(path: &Path) -> Settings {
         .and_then(|v| v.as_bool())
         .unwrap_or(true);
 
+    #[derive(Deserialize)]
+    struct ShellSetting {
+        shell: Option<Shell>,
+    }
+    let shell = toml_edit::de::from_str::<ShellSetting>(&content)
+        .ok()
+        .and_then(|s| s.shell);
+
     Settings {
         show_hints,
         check_for_updates,
+        shell,
     }
 }
 
@@ -139,4 +158,31 @@ mod tests {
         let s = load_from(&path);
         assert!(s.show_hints);
     }
+
+    #[test]
+    fn load_shell() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("settings.toml");
+        std::fs::write(&path, "shell = \"bash\"\n").unwrap();
+        let s = load_from(&path);
+        assert_eq!(
+            s.shell,
+            Some(Shell::Program(vec!["bash".into(), "-c".into()]))
+        );
+    }
+
+    #[test]
+    fn save_keeps_other_settings() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("settings.toml");
+        std::fs::write(&path, "shell = [\"zsh\", \"-c\"]\nshow_hints = true\n").unwrap();
+        save(&path, &[("show_hints", false), ("check_for_updates", true)]).unwrap();
+        let s = load_from(&path);
+        assert!(!s.show_hints);
+        assert!(s.check_for_updates);
+        assert_eq!(
+            s.shell,
+            Some(Shell::Program(vec!["zsh".into(), "-c".into()]))
+        );
+    }
 }
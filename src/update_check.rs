@@ -83,12 +83,11 @@ fn print_hint(latest: &str) {
     if let (Ok(curr), Ok(lat)) = (
         semver::Version::parse(current),
         semver::Version::parse(latest),
-    ) {
-        if lat > curr {
-            eprintln!(
-                "\x1b[2mA new version of plz is available (v{latest}). Run `plz update` to upgrade.\x1b[0m"
-            );
-        }
+    ) && lat > curr
+    {
+        eprintln!(
+            "\x1b[2mA new version of plz is available (v{latest}). Run `plz update` to upgrade.\x1b[0m"
+        );
     }
 }
 
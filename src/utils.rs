@@ -1,5 +1,6 @@
 use anyhow::Result;
 use std::io::Write as _;
+use std::time::Duration;
 
 pub fn fuzzy_match(query: &str, text: &str) -> bool {
     let query = query.to_lowercase();
@@ -13,6 +14,86 @@ pub fn fuzzy_match(query: &str, text: &str) -> bool {
     chars.peek().is_none()
 }
 
+/// Parse a duration like `30s`, `10m`, `1h30m` or `500ms`. Units are `ms`,
+/// `s`, `m` and `h`; a bare `0` and the empty string are zero.
+pub fn parse_duration(input: &str) -> Option<Duration> {
+    let input = input.trim();
+    if input.is_empty() || input == "0" {
+        return Some(Duration::ZERO);
+    }
+    let mut total = Duration::ZERO;
+    let mut rest = input;
+    while !rest.is_empty() {
+        let number_len = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .filter(|&n| n > 0)?;
+        let number: f64 = rest[..number_len].parse().ok()?;
+        rest = &rest[number_len..];
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let seconds = match &rest[..unit_len] {
+            "ms" => 0.001,
+            "s" => 1.0,
+            "m" => 60.0,
+            "h" => 3600.0,
+            _ => return None,
+        };
+        total = total.checked_add(Duration::try_from_secs_f64(number * seconds).ok()?)?;
+        rest = &rest[unit_len..];
+    }
+    Some(total)
+}
+
+/// Format a duration the way [`parse_duration`] reads it, e.g. `1h30m`.
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let millis = duration.subsec_millis();
+    let mut out = String::new();
+    for (amount, unit) in [
+        (secs / 3600, "h"),
+        (secs / 60 % 60, "m"),
+        (secs % 60, "s"),
+        (u64::from(millis), "ms"),
+    ] {
+        if amount > 0 {
+            out.push_str(&format!("{amount}{unit}"));
+        }
+    }
+    if out.is_empty() {
+        out.push_str("0s");
+    }
+    out
+}
+
+/// Parse a dotenv file into (key, value) pairs. Supports `#` comments, an
+/// optional `export ` prefix, and single- or double-quoted values.
+pub fn parse_dotenv(content: &str) -> Vec<(String, String)> {
+    let mut vars = Vec::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let value = value.trim();
+        let value = if value.len() >= 2
+            && ((value.starts_with('"') && value.ends_with('"'))
+                || (value.starts_with('\'') && value.ends_with('\'')))
+        {
+            &value[1..value.len() - 1]
+        } else {
+            // Strip trailing comments from unquoted values
+            value.split(" #").next().unwrap_or(value).trim_end()
+        };
+        vars.push((key.trim().to_string(), value.to_string()));
+    }
+    vars
+}
+
 #[derive(Clone)]
 pub struct PickItem {
     pub label: String,
@@ -121,10 +202,8 @@ pub fn multiselect(
                     KeyCode::Up => {
                         cursor = cursor.saturating_sub(1);
                     }
-                    KeyCode::Down => {
-                        if !items.is_empty() {
-                            cursor = (cursor + 1).min(items.len() - 1);
-                        }
+                    KeyCode::Down if !items.is_empty() => {
+                        cursor = (cursor + 1).min(items.len() - 1);
                     }
                     _ => {}
                 }
@@ -247,10 +326,8 @@ pub fn pick_from_list(items: &[PickItem], footer_hint: &str) -> Result<Option<us
                     KeyCode::Up => {
                         cursor_idx = cursor_idx.saturating_sub(1);
                     }
-                    KeyCode::Down => {
-                        if !matches.is_empty() {
-                            cursor_idx = (cursor_idx + 1).min(matches.len() - 1);
-                        }
+                    KeyCode::Down if !matches.is_empty() => {
+                        cursor_idx = (cursor_idx + 1).min(matches.len() - 1);
                     }
                     KeyCode::Backspace => {
                         query.pop();
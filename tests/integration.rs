@@ -3,7 +3,8 @@ use tempfile::TempDir;
 
 mod config_tests {
     use super::*;
-    use plzplz::config::{self, FailHook};
+    use plzplz::config::{self, FailHook, Shell};
+    use plzplz::diagnostic::{Diagnostic, Severity};
 
     fn write_config(dir: &TempDir, content: &str) -> std::path::PathBuf {
         let path = dir.path().join("plz.toml");
@@ -11,6 +12,14 @@ mod config_tests {
         path
     }
 
+    fn other_platform() -> &'static str {
+        if std::env::consts::OS == "linux" {
+            "macos"
+        } else {
+            "linux"
+        }
+    }
+
     #[test]
     fn parse_minimal_config() {
         let dir = TempDir::new().unwrap();
@@ -546,1239 +555,3901 @@ dir = ""
     }
 
     #[test]
-    fn taskgroup_validates_git_hook() {
+    fn parse_task_environment_and_env_file() {
         let dir = TempDir::new().unwrap();
         let path = write_config(
             &dir,
             r#"
-[tasks.hello]
-run = "echo hello"
-
-[taskgroup.rust.lint]
-run = "cargo clippy"
-git_hook = "not-a-hook"
+[tasks.serve]
+run = "node server.js"
+environment = { NODE_ENV = "production", PORT = "3000" }
+env_file = ".env"
 "#,
         );
-        let err = config::load(&path).unwrap_err();
-        assert!(err.to_string().contains("invalid git_hook"), "got: {err}");
+        let cfg = config::load(&path).unwrap();
+        let env = cfg.tasks["serve"].environment.as_ref().unwrap();
+        assert_eq!(env["NODE_ENV"], "production");
+        assert_eq!(env["PORT"], "3000");
+        assert_eq!(cfg.tasks["serve"].env_file.as_deref(), Some(".env"));
     }
 
     #[test]
-    fn taskgroup_comment_description() {
+    fn extends_environment_merges_with_task() {
         let dir = TempDir::new().unwrap();
         let path = write_config(
             &dir,
             r#"
-[tasks.hello]
-run = "echo hello"
+[extends]
+environment = { RUST_LOG = "info", CI = "true" }
+env_file = ".env"
 
-# Run the tests
-[taskgroup.rust.test]
-run = "cargo test"
+[tasks.build]
+run = "cargo build"
+environment = { RUST_LOG = "debug" }
+
+[tasks.plain]
+run = "echo hi"
+env_file = ""
 "#,
         );
         let cfg = config::load(&path).unwrap();
-        assert_eq!(
-            cfg.get_group_task("rust", "test")
-                .unwrap()
-                .description
-                .as_deref(),
-            Some("Run the tests")
-        );
+        let build = cfg.tasks["build"].environment.as_ref().unwrap();
+        assert_eq!(build["RUST_LOG"], "debug");
+        assert_eq!(build["CI"], "true");
+        assert_eq!(cfg.tasks["build"].env_file.as_deref(), Some(".env"));
+        assert_eq!(cfg.tasks["plain"].env_file, None);
     }
 
     #[test]
-    fn parse_taskgroups_only_no_tasks() {
+    fn extends_fail_hook_description_prefix_and_hide() {
         let dir = TempDir::new().unwrap();
         let path = write_config(
             &dir,
             r#"
-[taskgroup.rust.test]
-run = "cargo test"
+[extends]
+fail_hook = { message = "See the runbook" }
+description_prefix = "[app] "
+hide = true
 
-[taskgroup.rust.build]
+[tasks.build]
 run = "cargo build"
+description = "Build it"
+
+[tasks.deploy]
+run = "./deploy.sh"
+fail_hook = ""
+hide = false
+
+[taskgroup.docs.extends]
+description_prefix = ""
+fail_hook = "notify-send docs"
+
+[taskgroup.docs.serve]
+run = "pnpm docs:dev"
+description = "Serve docs"
 "#,
         );
         let cfg = config::load(&path).unwrap();
-        assert!(cfg.tasks.is_empty());
-        assert_eq!(cfg.get_group("rust").unwrap().tasks.len(), 2);
-        assert!(cfg.get_group_task("rust", "test").is_some());
-        assert!(cfg.get_group_task("rust", "build").is_some());
-    }
+        let build = &cfg.tasks["build"];
+        assert!(
+            matches!(build.fail_hook, Some(FailHook::Message(ref m)) if m == "See the runbook")
+        );
+        assert_eq!(build.description.as_deref(), Some("[app] Build it"));
+        assert!(build.hide);
 
-    #[test]
-    fn get_group_returns_none_for_missing() {
-        let dir = TempDir::new().unwrap();
-        let path = write_config(
-            &dir,
-            r#"
-[tasks.hello]
-run = "echo hello"
-"#,
+        let deploy = &cfg.tasks["deploy"];
+        assert!(deploy.fail_hook.is_none());
+        assert!(!deploy.hide);
+
+        let serve = cfg.get_group_task("docs", "serve").unwrap();
+        assert!(
+            matches!(serve.fail_hook, Some(FailHook::Command(ref c)) if c == "notify-send docs")
         );
-        let cfg = config::load(&path).unwrap();
-        assert!(cfg.get_group("nonexistent").is_none());
-        assert!(cfg.get_group_task("nonexistent", "test").is_none());
+        assert_eq!(serve.description.as_deref(), Some("Serve docs"));
+        assert!(serve.hide);
     }
 
     #[test]
-    fn parse_depends_string() {
+    fn shell_forms_and_extends_cascade() {
         let dir = TempDir::new().unwrap();
         let path = write_config(
             &dir,
             r#"
-[tasks.build]
-run = "echo build"
+[extends]
+shell = "bash -e"
 
-[tasks.test]
-depends = "build"
-run = "echo test"
+[tasks.a]
+run = "echo a"
+
+[tasks.b]
+run = "echo b"
+shell = ["zsh", "-c"]
+
+[tasks.c]
+run = "echo c"
+shell = false
+
+[tasks.d]
+run = "echo d"
+shell = ""
 "#,
         );
         let cfg = config::load(&path).unwrap();
-        let deps = cfg.tasks["test"].depends.as_ref().unwrap();
-        assert_eq!(deps.0, vec!["build"]);
+        let argv = |v: &[&str]| Some(Shell::Program(v.iter().map(|s| s.to_string()).collect()));
+        assert_eq!(cfg.tasks["a"].shell, argv(&["bash", "-e", "-c"]));
+        assert_eq!(cfg.tasks["b"].shell, argv(&["zsh", "-c"]));
+        assert_eq!(cfg.tasks["c"].shell, Some(Shell::Direct));
+        assert_eq!(cfg.tasks["d"].shell, None);
     }
 
     #[test]
-    fn parse_depends_list() {
+    fn timeout_parsing_and_extends_cascade() {
         let dir = TempDir::new().unwrap();
         let path = write_config(
             &dir,
             r#"
-[tasks.build]
-run = "echo build"
+[extends]
+timeout = "10m"
 
-[tasks.lint]
-run = "echo lint"
+[tasks.inherits]
+run = "true"
 
-[tasks.deploy]
-depends = ["build", "lint"]
-run = "echo deploy"
+[tasks.own]
+run = "true"
+timeout = "1h30m"
+
+[tasks.none]
+run = "true"
+timeout = ""
 "#,
         );
         let cfg = config::load(&path).unwrap();
-        let deps = cfg.tasks["deploy"].depends.as_ref().unwrap();
-        assert_eq!(deps.0, vec!["build", "lint"]);
-    }
+        let timeout = |name: &str| cfg.tasks[name].timeout.map(|t| t.to_string());
+        assert_eq!(timeout("inherits").as_deref(), Some("10m"));
+        assert_eq!(timeout("own").as_deref(), Some("1h30m"));
+        assert_eq!(timeout("none"), None);
+        assert_eq!(
+            cfg.tasks["own"].timeout.unwrap().0,
+            std::time::Duration::from_secs(5400)
+        );
 
-    #[test]
-    fn depends_nonexistent_errors() {
-        let dir = TempDir::new().unwrap();
+        let path = write_config(&dir, "[tasks.a]\nrun = \"true\"\ntimeout = \"10x\"\n");
+        let err = format!("{:#}", config::load(&path).unwrap_err());
+        assert!(err.contains("invalid duration \"10x\""), "got: {err}");
+
+        // Too long to add up is invalid too, rather than a crash
+        let too_long = "10000000000000000000s10000000000000000000s";
         let path = write_config(
             &dir,
-            r#"
-[tasks.test]
-depends = "missing"
-run = "echo test"
-"#,
+            &format!("[tasks.a]\nrun = \"true\"\ntimeout = \"{too_long}\"\n"),
         );
-        let err = config::load(&path).unwrap_err();
+        let err = format!("{:#}", config::load(&path).unwrap_err());
         assert!(
-            err.to_string().contains("no task \"missing\" exists"),
+            err.contains(&format!("invalid duration \"{too_long}\"")),
             "got: {err}"
         );
     }
 
     #[test]
-    fn depends_circular_errors() {
+    fn retry_parsing_and_validation() {
         let dir = TempDir::new().unwrap();
         let path = write_config(
             &dir,
             r#"
-[tasks.a]
-depends = "b"
-run = "echo a"
+[tasks.flaky]
+run = "true"
+retry = { attempts = 3, delay = "2s", backoff = 2.0 }
 
-[tasks.b]
-depends = "a"
-run = "echo b"
+[tasks.plain]
+run = "true"
+retry = { attempts = 2 }
 "#,
         );
-        let err = config::load(&path).unwrap_err();
-        assert!(
-            err.to_string().contains("Circular dependency"),
-            "got: {err}"
+        let cfg = config::load(&path).unwrap();
+        let retry = cfg.tasks["flaky"].retry.as_ref().unwrap();
+        assert_eq!(retry.attempts, 3);
+        let secs = |attempt| retry.delay_after(attempt).as_secs();
+        assert_eq!((secs(1), secs(2), secs(3)), (2, 4, 8));
+        let plain = cfg.tasks["plain"].retry.as_ref().unwrap();
+        assert!(plain.delay_after(1).is_zero());
+
+        let path = write_config(
+            &dir,
+            "[tasks.a]\nrun = \"true\"\nretry = { attempts = 0 }\n",
+        );
+        let err = format!("{:#}", config::load(&path).unwrap_err());
+        assert!(err.contains("retry attempts = 0"), "got: {err}");
+
+        let path = write_config(
+            &dir,
+            "[tasks.a]\nrun = \"true\"\nretry = { attempts = 2, backoff = 0.5 }\n",
         );
+        let err = format!("{:#}", config::load(&path).unwrap_err());
+        assert!(err.contains("retry backoff = 0.5"), "got: {err}");
     }
 
     #[test]
-    fn depends_self_errors() {
+    fn output_mode_parsing_and_extends() {
         let dir = TempDir::new().unwrap();
         let path = write_config(
             &dir,
             r#"
-[tasks.a]
-depends = "a"
-run = "echo a"
+[tasks.check]
+run_parallel = ["true", "true"]
+output = "grouped"
+
+[tasks.check-ci]
+extends = "check"
+
+[tasks.plain]
+run_parallel = ["true", "true"]
 "#,
         );
-        let err = config::load(&path).unwrap_err();
-        assert!(
-            err.to_string().contains("Circular dependency"),
-            "got: {err}"
+        let cfg = config::load(&path).unwrap();
+        assert_eq!(cfg.tasks["check"].output, Some(config::OutputMode::Grouped));
+        assert_eq!(
+            cfg.tasks["check-ci"].output,
+            Some(config::OutputMode::Grouped)
+        );
+        assert_eq!(cfg.tasks["plain"].output, None);
+
+        let path = write_config(
+            &dir,
+            "[tasks.a]\nrun_parallel = [\"true\"]\noutput = \"quiet\"\n",
         );
+        let err = format!("{:#}", config::load(&path).unwrap_err());
+        assert!(err.contains("unknown variant `quiet`"), "got: {err}");
     }
 
     #[test]
-    fn depends_group_task_ref() {
+    fn max_parallel_zero_is_rejected() {
         let dir = TempDir::new().unwrap();
         let path = write_config(
             &dir,
-            r#"
-[tasks.serve]
-depends = ["ui.build"]
-run = "echo serve"
-
-[taskgroup.ui.build]
-run = "echo build"
-"#,
+            "[tasks.a]\nrun_parallel = [\"true\"]\nmax_parallel = 0\n",
         );
-        let cfg = config::load(&path).unwrap();
-        let deps = cfg.tasks["serve"].depends.as_ref().unwrap();
-        assert_eq!(deps.0, vec!["ui.build"]);
+        let err = format!("{:#}", config::load(&path).unwrap_err());
+        assert!(err.contains("max_parallel = 0"), "got: {err}");
     }
 
     #[test]
-    fn depends_group_task_nonexistent_errors() {
+    fn shell_true_is_rejected() {
         let dir = TempDir::new().unwrap();
         let path = write_config(
             &dir,
             r#"
-[tasks.serve]
-depends = ["ui.missing"]
-run = "echo serve"
-
-[taskgroup.ui.build]
-run = "echo build"
+[tasks.a]
+run = "echo a"
+shell = true
 "#,
         );
-        let err = config::load(&path).unwrap_err();
+        let err = format!("{:#}", config::load(&path).unwrap_err());
         assert!(
-            err.to_string().contains("no task \"ui.missing\" exists"),
-            "got: {err}"
+            err.contains("a shell name, an array of arguments, or false"),
+            "{err}"
         );
     }
 
     #[test]
-    fn parse_plz_section_with_version() {
+    fn script_validation() {
         let dir = TempDir::new().unwrap();
+        let cases = [
+            ("script = \"print(1)\"", "has a script but no interpreter"),
+            (
+                "script = \"print(1)\"\ninterpreter = \"python3\"\nrun = \"echo\"",
+                "sets both script and run",
+            ),
+            (
+                "interpreter = \"python3\"",
+                "sets interpreter but has no script",
+            ),
+        ];
+        for (body, expected) in cases {
+            let path = write_config(&dir, &format!("[tasks.a]\n{body}\n"));
+            let err = format!("{:#}", config::load(&path).unwrap_err());
+            assert!(err.contains(expected), "{err}");
+        }
         let path = write_config(
             &dir,
             r#"
-[plz]
-version = ">=0.0.1"
-
-[tasks.hello]
-run = "echo hello"
+[tasks.base]
+script = """
+#!/bin/sh
+echo {{plz.task}}
+"""
+
+[tasks.child]
+extends = "base"
+
+[tasks.commands]
+extends = "base"
+run = "echo own"
 "#,
         );
         let cfg = config::load(&path).unwrap();
-        assert_eq!(
-            cfg.plz.as_ref().unwrap().version.as_deref(),
-            Some(">=0.0.1")
+        assert!(
+            cfg.tasks["child"]
+                .script
+                .as_ref()
+                .unwrap()
+                .contains("echo child")
         );
+        assert!(cfg.tasks["commands"].script.is_none());
     }
 
     #[test]
-    fn parse_plz_section_without_version() {
+    fn taskgroup_environment_cascade() {
         let dir = TempDir::new().unwrap();
         let path = write_config(
             &dir,
             r#"
-[plz]
+[extends]
+environment = { A = "top", B = "top" }
 
-[tasks.hello]
-run = "echo hello"
+[taskgroup.web.extends]
+environment = { B = "group", C = "group" }
+
+[taskgroup.web.build]
+run = "vite build"
+environment = { C = "task" }
 "#,
         );
         let cfg = config::load(&path).unwrap();
-        assert!(cfg.plz.as_ref().unwrap().version.is_none());
+        let env = cfg
+            .get_group_task("web", "build")
+            .unwrap()
+            .environment
+            .as_ref()
+            .unwrap();
+        assert_eq!(env["A"], "top");
+        assert_eq!(env["B"], "group");
+        assert_eq!(env["C"], "task");
     }
 
     #[test]
-    fn parse_no_plz_section() {
+    fn vars_substituted_in_environment_values() {
         let dir = TempDir::new().unwrap();
         let path = write_config(
             &dir,
             r#"
-[tasks.hello]
-run = "echo hello"
+[vars]
+port = "8080"
+
+[tasks.serve]
+run = "node server.js"
+environment = { PORT = "{{port}}" }
 "#,
         );
         let cfg = config::load(&path).unwrap();
-        assert!(cfg.plz.is_none());
+        assert_eq!(
+            cfg.tasks["serve"].environment.as_ref().unwrap()["PORT"],
+            "8080"
+        );
     }
 
     #[test]
-    fn parse_invalid_toml_errors() {
-        let dir = TempDir::new().unwrap();
-        let path = write_config(&dir, "this is not valid toml [[[");
-        assert!(config::load(&path).is_err());
+    fn parse_dotenv_file() {
+        use plzplz::utils::parse_dotenv;
+        let vars = parse_dotenv(
+            "# comment\nFOO=bar\nexport BAZ=\"quoted value\"\n\nSINGLE='x y'\nTRAIL=val # note\n",
+        );
+        assert_eq!(
+            vars,
+            vec![
+                ("FOO".to_string(), "bar".to_string()),
+                ("BAZ".to_string(), "quoted value".to_string()),
+                ("SINGLE".to_string(), "x y".to_string()),
+                ("TRAIL".to_string(), "val".to_string()),
+            ]
+        );
     }
 
     #[test]
-    fn parse_missing_file_errors() {
+    fn find_config_walks_up_to_parent() {
         let dir = TempDir::new().unwrap();
-        let path = dir.path().join("nonexistent.toml");
-        assert!(config::load(&path).is_err());
+        let path = write_config(&dir, "[tasks.hello]\nrun = \"echo hello\"\n");
+        let nested = dir.path().join("a/b");
+        fs::create_dir_all(&nested).unwrap();
+        assert_eq!(config::find_config(&nested), Some(path));
     }
 
     #[test]
-    fn vars_substitution_in_run() {
+    fn find_config_stops_at_git_root() {
         let dir = TempDir::new().unwrap();
-        let path = write_config(
-            &dir,
-            r#"
-[vars]
-name = "myapp"
+        write_config(&dir, "[tasks.hello]\nrun = \"echo hello\"\n");
+        let repo = dir.path().join("repo");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::create_dir_all(repo.join("src")).unwrap();
+        assert_eq!(config::find_config(&repo.join("src")), None);
+    }
 
-[tasks.build]
-run = "echo {{name}}"
-"#,
-        );
-        let cfg = config::load(&path).unwrap();
-        let run = cfg.tasks["build"].run.as_ref().unwrap();
-        assert_eq!(run.0, vec!["echo myapp"]);
+    #[test]
+    fn find_config_prefers_nearest() {
+        let dir = TempDir::new().unwrap();
+        write_config(&dir, "[tasks.outer]\nrun = \"echo outer\"\n");
+        let inner = dir.path().join("inner");
+        fs::create_dir_all(&inner).unwrap();
+        fs::write(
+            inner.join(".plz.toml"),
+            "[tasks.inner]\nrun = \"echo inner\"\n",
+        )
+        .unwrap();
+        assert_eq!(config::find_config(&inner), Some(inner.join(".plz.toml")));
     }
 
     #[test]
-    fn vars_substitution_in_serial_and_parallel() {
+    fn include_merges_tasks_vars_and_extends() {
         let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("tools")).unwrap();
+        fs::write(
+            dir.path().join("tools/common.toml"),
+            r#"
+[vars]
+target = "shared"
+profile = "release"
+
+[extends]
+env = "pnpm"
+
+# Lint everything
+[tasks.lint]
+run = "lint {{target}}"
+
+[taskgroup.docs.build]
+run = "build docs"
+"#,
+        )
+        .unwrap();
         let path = write_config(
             &dir,
             r#"
+[plz]
+include = ["tools/common.toml"]
+
 [vars]
-x = "hello"
+target = "local"
 
-[tasks.multi]
-run_serial = ["echo {{x}}", "echo {{x}} world"]
-run_parallel = ["echo {{x}} a", "echo {{x}} b"]
+[tasks.build]
+run = "build --{{profile}}"
 "#,
         );
         let cfg = config::load(&path).unwrap();
-        let task = &cfg.tasks["multi"];
         assert_eq!(
-            task.run_serial.as_ref().unwrap(),
-            &vec!["echo hello", "echo hello world"]
+            cfg.tasks["lint"].run.as_ref().unwrap().0,
+            vec!["lint local"]
         );
         assert_eq!(
-            task.run_parallel.as_ref().unwrap(),
-            &vec!["echo hello a", "echo hello b"]
+            cfg.tasks["build"].run.as_ref().unwrap().0,
+            vec!["build --release"]
+        );
+        assert_eq!(cfg.tasks["build"].tool_env.as_deref(), Some("pnpm"));
+        assert_eq!(
+            cfg.tasks["lint"].description.as_deref(),
+            Some("Lint everything")
+        );
+        assert!(cfg.get_group_task("docs", "build").is_some());
+        assert_eq!(
+            cfg.task_sources["lint"],
+            dir.path().join("tools/common.toml").canonicalize().unwrap()
         );
     }
 
     #[test]
-    fn vars_multiple_occurrences() {
+    fn include_task_overridden_by_including_file() {
         let dir = TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("common.toml"),
+            "[tasks.lint]\nrun = \"shared lint\"\n",
+        )
+        .unwrap();
         let path = write_config(
             &dir,
             r#"
-[vars]
-port = "8080"
+[plz]
+include = ["common.toml"]
 
-[tasks.serve]
-run = "docker run -p {{port}}:{{port}}"
+[tasks.lint]
+run = "local lint"
 "#,
         );
         let cfg = config::load(&path).unwrap();
-        let run = cfg.tasks["serve"].run.as_ref().unwrap();
-        assert_eq!(run.0, vec!["docker run -p 8080:8080"]);
+        assert_eq!(
+            cfg.tasks["lint"].run.as_ref().unwrap().0,
+            vec!["local lint"]
+        );
+        assert_eq!(cfg.task_sources["lint"], path);
     }
 
     #[test]
-    fn vars_taskgroup_overrides_toplevel() {
+    fn include_conflicting_tasks_name_both_files() {
         let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("a.toml"), "[tasks.lint]\nrun = \"a\"\n").unwrap();
+        fs::write(dir.path().join("b.toml"), "[tasks.lint]\nrun = \"b\"\n").unwrap();
         let path = write_config(
             &dir,
             r#"
-[vars]
-port = "8080"
-
-[taskgroup.web.vars]
-port = "3000"
-
-[taskgroup.web.serve]
-run = "echo {{port}}"
+[plz]
+include = ["a.toml", "b.toml"]
 "#,
         );
-        let cfg = config::load(&path).unwrap();
-        let task = cfg.get_group_task("web", "serve").unwrap();
-        assert_eq!(task.run.as_ref().unwrap().0, vec!["echo 3000"]);
+        let err = config::load(&path).unwrap_err().to_string();
+        assert!(err.contains("\"lint\" is defined in both"), "{err}");
+        assert!(err.contains("a.toml") && err.contains("b.toml"), "{err}");
     }
 
     #[test]
-    fn vars_taskgroup_inherits_toplevel() {
+    fn include_nested_and_shared_files_load_once() {
         let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("base.toml"), "[tasks.fmt]\nrun = \"fmt\"\n").unwrap();
+        fs::write(
+            dir.path().join("a.toml"),
+            "[plz]\ninclude = [\"base.toml\"]\n\n[tasks.a]\nrun = \"a\"\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("b.toml"),
+            "[plz]\ninclude = [\"base.toml\"]\n\n[tasks.b]\nrun = \"b\"\n",
+        )
+        .unwrap();
         let path = write_config(
             &dir,
             r#"
-[vars]
-name = "myapp"
-
-[taskgroup.web.serve]
-run = "echo {{name}}"
+[plz]
+include = ["a.toml", "b.toml"]
 "#,
         );
         let cfg = config::load(&path).unwrap();
-        let task = cfg.get_group_task("web", "serve").unwrap();
-        assert_eq!(task.run.as_ref().unwrap().0, vec!["echo myapp"]);
+        assert!(cfg.tasks.contains_key("a"));
+        assert!(cfg.tasks.contains_key("b"));
+        assert!(cfg.tasks.contains_key("fmt"));
     }
 
     #[test]
-    fn vars_unresolved_errors() {
+    fn include_cycle_detected() {
         let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("a.toml"), "[plz]\ninclude = [\"b.toml\"]\n").unwrap();
+        fs::write(dir.path().join("b.toml"), "[plz]\ninclude = [\"a.toml\"]\n").unwrap();
         let path = write_config(
             &dir,
             r#"
-[vars]
-name = "myapp"
+[plz]
+include = ["a.toml"]
+"#,
+        );
+        let err = config::load(&path).unwrap_err().to_string();
+        assert!(err.contains("Circular include"), "{err}");
+    }
 
-[tasks.build]
-run = "echo {{typo}}"
+    #[test]
+    fn include_missing_file_errors() {
+        let dir = TempDir::new().unwrap();
+        let path = write_config(
+            &dir,
+            r#"
+[plz]
+include = ["nope.toml"]
 "#,
         );
-        let err = config::load(&path).unwrap_err();
-        let msg = format!("{err:#}");
-        assert!(msg.contains("Unresolved variable"), "got: {msg}");
+        let err = config::load(&path).unwrap_err().to_string();
+        assert!(err.contains("nope.toml"), "{err}");
+        assert!(err.contains("included from"), "{err}");
     }
 
     #[test]
-    fn vars_no_vars_unchanged() {
+    fn workspace_members_load_as_groups() {
         let dir = TempDir::new().unwrap();
+        for (member, body) in [
+            (
+                "packages/web",
+                "[tasks.build]\nrun = \"vite build\"\ndepends = \"gen\"\n\n[tasks.gen]\nrun = \"codegen\"\n\n[tasks.all]\nrun_serial = [\"plz:gen\", \"plz:build\"]\n",
+            ),
+            (
+                "packages/api",
+                "[tasks.build]\nrun = \"cargo build\"\ndir = \"crates\"\n",
+            ),
+            ("packages/skipped", "[tasks.build]\nrun = \"nope\"\n"),
+        ] {
+            fs::create_dir_all(dir.path().join(member)).unwrap();
+            fs::write(dir.path().join(member).join("plz.toml"), body).unwrap();
+        }
+        // Directories without a plz.toml are ignored
+        fs::create_dir_all(dir.path().join("packages/empty")).unwrap();
         let path = write_config(
             &dir,
             r#"
-[tasks.hello]
-run = "echo hello"
+[workspace]
+members = ["packages/*"]
+exclude = ["packages/skipped"]
 "#,
         );
         let cfg = config::load(&path).unwrap();
-        let run = cfg.tasks["hello"].run.as_ref().unwrap();
-        assert_eq!(run.0, vec!["echo hello"]);
+        assert_eq!(cfg.workspace_members(), vec!["api", "web"]);
+
+        let build = cfg.get_group_task("web", "build").unwrap();
+        assert_eq!(build.dir.as_deref(), Some("packages/web"));
+        assert_eq!(build.depends.as_ref().unwrap().0, vec!["web.gen"]);
+        let all = cfg.get_group_task("web", "all").unwrap();
+        assert_eq!(
+            all.run_serial.as_ref().unwrap(),
+            &["plz:web:gen", "plz:web:build"]
+        );
+        let api = cfg.get_group_task("api", "build").unwrap();
+        assert_eq!(api.dir.as_deref(), Some("packages/api/crates"));
+        assert!(cfg.get_group("skipped").is_none());
     }
 
     #[test]
-    fn warn_unknown_top_level_key() {
-        let doc: toml_edit::DocumentMut = r#"
-[tasks.hello]
-run = "echo hello"
+    fn workspace_members_extends_layer_over_the_root() {
+        let dir = TempDir::new().unwrap();
+        for (member, body) in [
+            (
+                "packages/web",
+                "[extends]\ndescription_prefix = \"[web] \"\n\n[tasks.build]\nrun = \"vite build\"\ndescription = \"Build web\"\n",
+            ),
+            (
+                "packages/api",
+                "[tasks.build]\nrun = \"cargo build\"\ndescription = \"Build api\"\n\n[tasks.deploy]\nrun = \"./deploy.sh\"\nfail_hook = \"\"\n",
+            ),
+        ] {
+            fs::create_dir_all(dir.path().join(member)).unwrap();
+            fs::write(dir.path().join(member).join("plz.toml"), body).unwrap();
+        }
+        let path = write_config(
+            &dir,
+            r#"
+[workspace]
+members = ["packages/*"]
 
-[something]
-foo = "bar"
-"#
-        .parse()
-        .unwrap();
-        let warnings = config::warn_unknown_keys(&doc);
-        assert_eq!(warnings, vec!["unknown key \"something\" in plz.toml"]);
+[extends]
+description_prefix = "[root] "
+fail_hook = { message = "See the runbook" }
+env_file = ".env"
+"#,
+        );
+        let cfg = config::load(&path).unwrap();
+        let web = cfg.get_group_task("web", "build").unwrap();
+        assert_eq!(web.description.as_deref(), Some("[web] Build web"));
+        assert!(matches!(web.fail_hook, Some(FailHook::Message(_))));
+        let api = cfg.get_group_task("api", "build").unwrap();
+        assert_eq!(api.description.as_deref(), Some("[root] Build api"));
+        assert_eq!(api.env_file.as_deref(), Some("packages/api/../../.env"));
+        let deploy = cfg.get_group_task("api", "deploy").unwrap();
+        assert!(deploy.fail_hook.is_none());
     }
 
     #[test]
-    fn warn_unknown_task_key() {
-        let doc: toml_edit::DocumentMut = r#"
-[tasks.hello]
-run = "echo hello"
-oops = true
-"#
-        .parse()
+    fn workspace_member_conflicts_with_taskgroup() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("packages/web")).unwrap();
+        fs::write(
+            dir.path().join("packages/web/plz.toml"),
+            "[tasks.build]\nrun = \"vite build\"\n",
+        )
         .unwrap();
-        let warnings = config::warn_unknown_keys(&doc);
-        assert_eq!(warnings, vec!["unknown key \"oops\" in [tasks.hello]"]);
+        let path = write_config(
+            &dir,
+            r#"
+[workspace]
+members = ["packages/*"]
+
+[taskgroup.web.dev]
+run = "vite dev"
+"#,
+        );
+        let err = config::load(&path).unwrap_err().to_string();
+        assert!(
+            err.contains("conflicts with existing [taskgroup.web]"),
+            "{err}"
+        );
     }
 
     #[test]
-    fn warn_unknown_plz_key() {
-        let doc: toml_edit::DocumentMut = r#"
-[plz]
-version = ">=0.1.0"
-unknown = "bad"
-
+    fn taskgroup_validates_git_hook() {
+        let dir = TempDir::new().unwrap();
+        let path = write_config(
+            &dir,
+            r#"
 [tasks.hello]
 run = "echo hello"
-"#
-        .parse()
-        .unwrap();
-        let warnings = config::warn_unknown_keys(&doc);
-        assert_eq!(warnings, vec!["unknown key \"unknown\" in [plz]"]);
+
+[taskgroup.rust.lint]
+run = "cargo clippy"
+git_hook = "not-a-hook"
+"#,
+        );
+        let err = config::load(&path).unwrap_err();
+        assert!(err.to_string().contains("invalid git_hook"), "got: {err}");
     }
 
     #[test]
-    fn warn_unknown_extends_key() {
-        let doc: toml_edit::DocumentMut = r#"
-[extends]
-env = "pnpm"
-bad = "value"
-
+    fn taskgroup_comment_description() {
+        let dir = TempDir::new().unwrap();
+        let path = write_config(
+            &dir,
+            r#"
 [tasks.hello]
 run = "echo hello"
-"#
-        .parse()
-        .unwrap();
-        let warnings = config::warn_unknown_keys(&doc);
-        assert_eq!(warnings, vec!["unknown key \"bad\" in [extends]"]);
-    }
 
-    #[test]
-    fn warn_unknown_taskgroup_task_key() {
-        let doc: toml_edit::DocumentMut = r#"
-[taskgroup.mygroup.mytask]
-run = "echo hello"
-nope = "bad"
-"#
-        .parse()
-        .unwrap();
-        let warnings = config::warn_unknown_keys(&doc);
+# Run the tests
+[taskgroup.rust.test]
+run = "cargo test"
+"#,
+        );
+        let cfg = config::load(&path).unwrap();
         assert_eq!(
-            warnings,
-            vec!["unknown key \"nope\" in [taskgroup.mygroup.mytask]"]
+            cfg.get_group_task("rust", "test")
+                .unwrap()
+                .description
+                .as_deref(),
+            Some("Run the tests")
         );
     }
 
     #[test]
-    fn warn_unknown_taskgroup_extends_key() {
-        let doc: toml_edit::DocumentMut = r#"
-[taskgroup.mygroup.extends]
-env = "pnpm"
-bad = "value"
+    fn parse_taskgroups_only_no_tasks() {
+        let dir = TempDir::new().unwrap();
+        let path = write_config(
+            &dir,
+            r#"
+[taskgroup.rust.test]
+run = "cargo test"
 
-[taskgroup.mygroup.mytask]
-run = "echo hello"
-"#
-        .parse()
-        .unwrap();
-        let warnings = config::warn_unknown_keys(&doc);
-        assert_eq!(
-            warnings,
-            vec!["unknown key \"bad\" in [taskgroup.mygroup.extends]"]
+[taskgroup.rust.build]
+run = "cargo build"
+"#,
         );
+        let cfg = config::load(&path).unwrap();
+        assert!(cfg.tasks.is_empty());
+        assert_eq!(cfg.get_group("rust").unwrap().tasks.len(), 2);
+        assert!(cfg.get_group_task("rust", "test").is_some());
+        assert!(cfg.get_group_task("rust", "build").is_some());
     }
 
     #[test]
-    fn no_warnings_for_valid_config() {
-        let doc: toml_edit::DocumentMut = r#"
-[plz]
-version = ">=0.1.0"
-
-[extends]
-env = "pnpm"
-dir = "."
-
-[vars]
-name = "world"
-
+    fn get_group_returns_none_for_missing() {
+        let dir = TempDir::new().unwrap();
+        let path = write_config(
+            &dir,
+            r#"
 [tasks.hello]
 run = "echo hello"
-run_serial = ["echo a", "echo b"]
-run_parallel = ["echo c", "echo d"]
-depends = "hello"
-env = "pnpm"
-dir = "."
-fail_hook = "echo fail"
-description = "Says hello"
-git_hook = "pre-commit"
-hide = true
-
-[taskgroup.mygroup.extends]
-env = "pnpm"
-dir = "."
-
-[taskgroup.mygroup.vars]
-foo = "bar"
-
-[taskgroup.mygroup.mytask]
-run = "echo hello"
-"#
-        .parse()
-        .unwrap();
-        let warnings = config::warn_unknown_keys(&doc);
-        assert!(
-            warnings.is_empty(),
-            "Expected no warnings, got: {warnings:?}"
-        );
-    }
-}
-
-mod runner_tests {
-    use super::*;
-    use plzplz::config;
-    use plzplz::runner;
-
-    fn load_config(dir: &TempDir, content: &str) -> config::PlzConfig {
-        let path = dir.path().join("plz.toml");
-        fs::write(&path, content).unwrap();
-        config::load(&path).unwrap()
-    }
+"#,
+        );
+        let cfg = config::load(&path).unwrap();
+        assert!(cfg.get_group("nonexistent").is_none());
+        assert!(cfg.get_group_task("nonexistent", "test").is_none());
+    }
 
     #[test]
-    fn run_simple_command() {
+    fn parse_depends_string() {
         let dir = TempDir::new().unwrap();
-        let cfg = load_config(
+        let path = write_config(
             &dir,
             r#"
-[tasks.hello]
-run = "echo hello"
+[tasks.build]
+run = "echo build"
+
+[tasks.test]
+depends = "build"
+run = "echo test"
 "#,
         );
-        runner::run_task(&cfg, "hello", dir.path(), false).unwrap();
+        let cfg = config::load(&path).unwrap();
+        let deps = cfg.tasks["test"].depends.as_ref().unwrap();
+        assert_eq!(deps.0, vec!["build"]);
     }
 
     #[test]
-    fn run_failing_command() {
+    fn parse_depends_list() {
         let dir = TempDir::new().unwrap();
-        let cfg = load_config(
+        let path = write_config(
             &dir,
             r#"
-[tasks.fail]
-run = "false"
+[tasks.build]
+run = "echo build"
+
+[tasks.lint]
+run = "echo lint"
+
+[tasks.deploy]
+depends = ["build", "lint"]
+run = "echo deploy"
 "#,
         );
-        assert!(runner::run_task(&cfg, "fail", dir.path(), false).is_err());
+        let cfg = config::load(&path).unwrap();
+        let deps = cfg.tasks["deploy"].depends.as_ref().unwrap();
+        assert_eq!(deps.0, vec!["build", "lint"]);
     }
 
     #[test]
-    fn run_serial_commands() {
+    fn depends_nonexistent_errors() {
         let dir = TempDir::new().unwrap();
-        let marker = dir.path().join("marker.txt");
-        let cfg = load_config(
+        let path = write_config(
             &dir,
-            &format!(
-                r#"
-[tasks.serial]
-run_serial = ["echo first", "touch {}"]
+            r#"
+[tasks.test]
+depends = "missing"
+run = "echo test"
 "#,
-                marker.display()
-            ),
         );
-        runner::run_task(&cfg, "serial", dir.path(), false).unwrap();
-        assert!(marker.exists());
+        let err = config::load(&path).unwrap_err();
+        assert!(
+            err.to_string().contains("no task \"missing\" exists"),
+            "got: {err}"
+        );
     }
 
     #[test]
-    fn run_serial_stops_on_failure() {
+    fn depends_circular_errors() {
         let dir = TempDir::new().unwrap();
-        let marker = dir.path().join("should_not_exist.txt");
-        let cfg = load_config(
+        let path = write_config(
             &dir,
-            &format!(
-                r#"
-[tasks.serial]
-run_serial = ["false", "touch {}"]
+            r#"
+[tasks.a]
+depends = "b"
+run = "echo a"
+
+[tasks.b]
+depends = "a"
+run = "echo b"
 "#,
-                marker.display()
-            ),
         );
-        assert!(runner::run_task(&cfg, "serial", dir.path(), false).is_err());
-        assert!(!marker.exists());
+        let err = config::load(&path).unwrap_err();
+        assert!(
+            err.to_string().contains("Circular dependency"),
+            "got: {err}"
+        );
     }
 
     #[test]
-    fn run_parallel_commands() {
+    fn depends_self_errors() {
         let dir = TempDir::new().unwrap();
-        let m1 = dir.path().join("p1.txt");
-        let m2 = dir.path().join("p2.txt");
-        let cfg = load_config(
+        let path = write_config(
             &dir,
-            &format!(
-                r#"
-[tasks.par]
-run_parallel = ["touch {}", "touch {}"]
+            r#"
+[tasks.a]
+depends = "a"
+run = "echo a"
 "#,
-                m1.display(),
-                m2.display()
-            ),
         );
-        runner::run_task(&cfg, "par", dir.path(), false).unwrap();
-        assert!(m1.exists());
-        assert!(m2.exists());
+        let err = config::load(&path).unwrap_err();
+        assert!(
+            err.to_string().contains("Circular dependency"),
+            "got: {err}"
+        );
     }
 
     #[test]
-    fn run_parallel_task_refs_execute_concurrently() {
-        // Regression: previously plz: refs in run_parallel ran sequentially.
-        // Each task signals "started" then waits up to 5s for the other's
-        // signal — serial execution would time out and fail the task.
+    fn depends_group_task_ref() {
         let dir = TempDir::new().unwrap();
-        let a_started = dir.path().join("a_started");
-        let b_started = dir.path().join("b_started");
-        let cfg = load_config(
+        let path = write_config(
             &dir,
-            &format!(
-                r#"
-[tasks.both]
-run_parallel = ["plz:a", "plz:b"]
-
-[tasks.a]
-run = "bash -c 'touch {a}; for i in $(seq 1 50); do [ -f {b} ] && exit 0; sleep 0.1; done; exit 1'"
+            r#"
+[tasks.serve]
+depends = ["ui.build"]
+run = "echo serve"
 
-[tasks.b]
-run = "bash -c 'touch {b}; for i in $(seq 1 50); do [ -f {a} ] && exit 0; sleep 0.1; done; exit 1'"
+[taskgroup.ui.build]
+run = "echo build"
 "#,
-                a = a_started.display(),
-                b = b_started.display(),
-            ),
         );
-        runner::run_task(&cfg, "both", dir.path(), false).unwrap();
-        assert!(a_started.exists());
-        assert!(b_started.exists());
+        let cfg = config::load(&path).unwrap();
+        let deps = cfg.tasks["serve"].depends.as_ref().unwrap();
+        assert_eq!(deps.0, vec!["ui.build"]);
     }
 
     #[test]
-    fn run_parallel_fails_on_error() {
+    fn depends_group_task_nonexistent_errors() {
         let dir = TempDir::new().unwrap();
-        let cfg = load_config(
+        let path = write_config(
             &dir,
             r#"
-[tasks.par]
-run_parallel = ["true", "false"]
+[tasks.serve]
+depends = ["ui.missing"]
+run = "echo serve"
+
+[taskgroup.ui.build]
+run = "echo build"
 "#,
         );
-        assert!(runner::run_task(&cfg, "par", dir.path(), false).is_err());
+        let err = config::load(&path).unwrap_err();
+        assert!(
+            err.to_string().contains("no task \"ui.missing\" exists"),
+            "got: {err}"
+        );
     }
 
     #[test]
-    fn run_with_working_dir() {
+    fn parse_plz_section_with_version() {
         let dir = TempDir::new().unwrap();
-        let subdir = dir.path().join("mysubdir");
-        fs::create_dir(&subdir).unwrap();
-        let out = dir.path().join("pwd_out.txt");
-        let cfg = load_config(
+        let path = write_config(
             &dir,
-            &format!(
-                r#"
-[tasks.wd]
-run = "pwd > {}"
-dir = "mysubdir"
+            r#"
+[plz]
+version = ">=0.0.1"
+
+[tasks.hello]
+run = "echo hello"
 "#,
-                out.display()
-            ),
         );
-        runner::run_task(&cfg, "wd", dir.path(), false).unwrap();
-        let content = fs::read_to_string(&out).unwrap();
-        assert!(content.trim().ends_with("mysubdir"));
+        let cfg = config::load(&path).unwrap();
+        assert_eq!(
+            cfg.plz.as_ref().unwrap().version.as_deref(),
+            Some(">=0.0.1")
+        );
     }
 
     #[test]
-    fn run_with_tool_env_uv() {
+    fn parse_plz_section_without_version() {
         let dir = TempDir::new().unwrap();
-        let cfg = load_config(
+        let path = write_config(
             &dir,
             r#"
-[tasks.uv_test]
+[plz]
+
+[tasks.hello]
 run = "echo hello"
-env = "uv"
 "#,
         );
-        assert_eq!(cfg.tasks["uv_test"].tool_env.as_deref(), Some("uv"));
-        let _ = runner::run_task(&cfg, "uv_test", dir.path(), false);
+        let cfg = config::load(&path).unwrap();
+        assert!(cfg.plz.as_ref().unwrap().version.is_none());
     }
 
     #[test]
-    fn run_with_tool_env_pnpm() {
+    fn parse_no_plz_section() {
         let dir = TempDir::new().unwrap();
-        let cfg = load_config(
+        let path = write_config(
             &dir,
             r#"
-[tasks.pnpm_test]
+[tasks.hello]
 run = "echo hello"
-env = "pnpm"
 "#,
         );
-        assert_eq!(cfg.tasks["pnpm_test"].tool_env.as_deref(), Some("pnpm"));
-        let _ = runner::run_task(&cfg, "pnpm_test", dir.path(), false);
+        let cfg = config::load(&path).unwrap();
+        assert!(cfg.plz.is_none());
     }
 
     #[test]
-    fn run_with_tool_env_npm() {
+    fn parse_invalid_toml_errors() {
         let dir = TempDir::new().unwrap();
-        let cfg = load_config(
-            &dir,
-            r#"
-[tasks.npm_test]
-run = "echo hello"
-env = "npm"
-"#,
-        );
-        assert_eq!(cfg.tasks["npm_test"].tool_env.as_deref(), Some("npm"));
-        let _ = runner::run_task(&cfg, "npm_test", dir.path(), false);
+        let path = write_config(&dir, "this is not valid toml [[[");
+        assert!(config::load(&path).is_err());
     }
 
     #[test]
-    fn run_task_reference() {
+    fn parse_missing_file_errors() {
         let dir = TempDir::new().unwrap();
-        let marker = dir.path().join("ref_marker.txt");
-        let cfg = load_config(
+        let path = dir.path().join("nonexistent.toml");
+        assert!(config::load(&path).is_err());
+    }
+
+    #[test]
+    fn vars_substitution_in_run() {
+        let dir = TempDir::new().unwrap();
+        let path = write_config(
             &dir,
-            &format!(
-                r#"
-[tasks.caller]
-run = "plz:target"
+            r#"
+[vars]
+name = "myapp"
 
-[tasks.target]
-run = "touch {}"
+[tasks.build]
+run = "echo {{name}}"
 "#,
-                marker.display()
-            ),
         );
-        runner::run_task(&cfg, "caller", dir.path(), false).unwrap();
-        assert!(marker.exists());
+        let cfg = config::load(&path).unwrap();
+        let run = cfg.tasks["build"].run.as_ref().unwrap();
+        assert_eq!(run.0, vec!["echo myapp"]);
     }
 
     #[test]
-    fn run_unknown_task_errors() {
+    fn vars_substitution_in_serial_and_parallel() {
         let dir = TempDir::new().unwrap();
-        let cfg = load_config(
+        let path = write_config(
             &dir,
             r#"
-[tasks.hello]
-run = "echo hello"
+[vars]
+x = "hello"
+
+[tasks.multi]
+run_serial = ["echo {{x}}", "echo {{x}} world"]
+run_parallel = ["echo {{x}} a", "echo {{x}} b"]
 "#,
         );
-        let err = runner::run_task(&cfg, "nonexistent", dir.path(), false);
-        assert!(err.is_err());
-        assert!(err.unwrap_err().to_string().contains("isn't a task"));
+        let cfg = config::load(&path).unwrap();
+        let task = &cfg.tasks["multi"];
+        assert_eq!(
+            task.run_serial.as_ref().unwrap(),
+            &vec!["echo hello", "echo hello world"]
+        );
+        assert_eq!(
+            task.run_parallel.as_ref().unwrap(),
+            &vec!["echo hello a", "echo hello b"]
+        );
     }
 
     #[test]
-    fn run_serial_with_task_ref() {
+    fn vars_multiple_occurrences() {
         let dir = TempDir::new().unwrap();
-        let m1 = dir.path().join("s1.txt");
-        let m2 = dir.path().join("s2.txt");
-        let cfg = load_config(
+        let path = write_config(
             &dir,
-            &format!(
-                r#"
-[tasks.main]
-run_serial = ["touch {}", "plz:helper"]
+            r#"
+[vars]
+port = "8080"
 
-[tasks.helper]
-run = "touch {}"
+[tasks.serve]
+run = "docker run -p {{port}}:{{port}}"
 "#,
-                m1.display(),
-                m2.display()
-            ),
         );
-        runner::run_task(&cfg, "main", dir.path(), false).unwrap();
-        assert!(m1.exists());
-        assert!(m2.exists());
+        let cfg = config::load(&path).unwrap();
+        let run = cfg.tasks["serve"].run.as_ref().unwrap();
+        assert_eq!(run.0, vec!["docker run -p 8080:8080"]);
     }
 
     #[test]
-    fn task_refs_resolve_when_referencer_has_tool_env() {
-        // Regression: env wrapping ran before ref resolution, so "plz:inner"
-        // became "pnpm exec plz:inner" and executed as a shell command
+    fn vars_taskgroup_overrides_toplevel() {
         let dir = TempDir::new().unwrap();
-        let single = dir.path().join("single.txt");
-        let serial = dir.path().join("serial.txt");
-        let parallel = dir.path().join("parallel.txt");
-        let cfg = load_config(
+        let path = write_config(
             &dir,
-            &format!(
-                r#"
-[tasks.single]
-run = "plz:touch_single"
-env = "pnpm"
-
-[tasks.serial]
-run_serial = ["plz:touch_serial"]
-env = "pnpm"
-
-[tasks.parallel]
-run_parallel = ["plz:touch_parallel"]
-env = "pnpm"
-
-[tasks.touch_single]
-run = "touch {}"
+            r#"
+[vars]
+port = "8080"
 
-[tasks.touch_serial]
-run = "touch {}"
+[taskgroup.web.vars]
+port = "3000"
 
-[tasks.touch_parallel]
-run = "touch {}"
+[taskgroup.web.serve]
+run = "echo {{port}}"
 "#,
-                single.display(),
-                serial.display(),
-                parallel.display()
-            ),
         );
-        runner::run_task(&cfg, "single", dir.path(), false).unwrap();
-        runner::run_task(&cfg, "serial", dir.path(), false).unwrap();
-        runner::run_task(&cfg, "parallel", dir.path(), false).unwrap();
-        assert!(single.exists());
-        assert!(serial.exists());
-        assert!(parallel.exists());
+        let cfg = config::load(&path).unwrap();
+        let task = cfg.get_group_task("web", "serve").unwrap();
+        assert_eq!(task.run.as_ref().unwrap().0, vec!["echo 3000"]);
     }
 
     #[test]
-    fn run_group_task_simple() {
+    fn vars_taskgroup_inherits_toplevel() {
         let dir = TempDir::new().unwrap();
-        let marker = dir.path().join("group_marker.txt");
-        let cfg = load_config(
+        let path = write_config(
             &dir,
-            &format!(
-                r#"
-[tasks.hello]
-run = "echo hello"
+            r#"
+[vars]
+name = "myapp"
 
-[taskgroup.rust.test]
-run = "touch {}"
+[taskgroup.web.serve]
+run = "echo {{name}}"
 "#,
-                marker.display()
-            ),
         );
-        runner::run_group_task(&cfg, "rust", "test", dir.path(), false).unwrap();
-        assert!(marker.exists());
+        let cfg = config::load(&path).unwrap();
+        let task = cfg.get_group_task("web", "serve").unwrap();
+        assert_eq!(task.run.as_ref().unwrap().0, vec!["echo myapp"]);
     }
 
     #[test]
-    fn run_group_task_with_dir() {
+    fn vars_unresolved_errors() {
         let dir = TempDir::new().unwrap();
-        let subdir = dir.path().join("backend");
-        fs::create_dir(&subdir).unwrap();
-        let out = dir.path().join("grp_pwd.txt");
-        let cfg = load_config(
+        let path = write_config(
             &dir,
-            &format!(
-                r#"
-[tasks.hello]
-run = "echo hello"
-
-[taskgroup.rust.extends]
-dir = "backend"
+            r#"
+[vars]
+name = "myapp"
 
-[taskgroup.rust.wd]
-run = "pwd > {}"
+[tasks.build]
+run = "echo {{typo}}"
 "#,
-                out.display()
-            ),
         );
-        runner::run_group_task(&cfg, "rust", "wd", dir.path(), false).unwrap();
-        let content = fs::read_to_string(&out).unwrap();
-        assert!(content.trim().ends_with("backend"));
+        let err = config::load(&path).unwrap_err();
+        let msg = format!("{err:#}");
+        assert!(msg.contains("Unresolved variable"), "got: {msg}");
     }
 
     #[test]
-    fn run_group_task_unknown_errors() {
+    fn vars_no_vars_unchanged() {
         let dir = TempDir::new().unwrap();
-        let cfg = load_config(
+        let path = write_config(
             &dir,
             r#"
 [tasks.hello]
 run = "echo hello"
-
-[taskgroup.rust.test]
-run = "cargo test"
 "#,
         );
-        let err = runner::run_group_task(&cfg, "rust", "nonexistent", dir.path(), false);
-        assert!(err.is_err());
-        assert!(err.unwrap_err().to_string().contains("isn't a task"));
+        let cfg = config::load(&path).unwrap();
+        let run = cfg.tasks["hello"].run.as_ref().unwrap();
+        assert_eq!(run.0, vec!["echo hello"]);
     }
 
     #[test]
-    fn run_group_task_with_extra_args() {
+    fn vars_dynamic_left_for_runtime() {
         let dir = TempDir::new().unwrap();
-        let out = dir.path().join("args_out.txt");
-        let cfg = load_config(
+        let path = write_config(
             &dir,
-            &format!(
-                r#"
-[tasks.hello]
-run = "echo hello"
+            r#"
+[vars]
+name = "myapp"
+sha = { sh = "git rev-parse --short HEAD" }
 
-[taskgroup.rust.echo]
-run = "echo > {}"
+[tasks.build]
+run = "docker build -t {{name}}:{{sha}} ."
 "#,
-                out.display()
-            ),
         );
-        let args = vec!["--nocapture".to_string()];
-        runner::run_group_task_with_args(&cfg, "rust", "echo", dir.path(), false, &args).unwrap();
-        assert!(out.exists());
+        let cfg = config::load(&path).unwrap();
+        let task = &cfg.tasks["build"];
+        assert_eq!(
+            task.run.as_ref().unwrap().0,
+            vec!["docker build -t myapp:{{sha}} ."]
+        );
+        assert_eq!(task.dynamic_vars["sha"], "git rev-parse --short HEAD");
     }
 
     #[test]
-    fn run_group_task_ref_in_serial() {
+    fn vars_value_file_and_secret_forms() {
         let dir = TempDir::new().unwrap();
-        let m1 = dir.path().join("gs1.txt");
-        let m2 = dir.path().join("gs2.txt");
-        let cfg = load_config(
+        let path = write_config(
             &dir,
-            &format!(
-                r#"
-[tasks.main]
-run_serial = ["touch {}", "plz:grp:helper"]
+            r#"
+[vars]
+user = "deploy"
+password = { value = "hunter2-{{user}}", secret = true }
+token = { file = ".token", secret = true }
+plain = { value = "visible" }
+
+[tasks.login]
+run = "login {{user}} {{password}} {{token}} {{plain}}"
+environment = { API_KEY = { value = "abc123", secret = true }, TOKEN = "{{token}}" }
+"#,
+        );
+        let cfg = config::load(&path).unwrap();
+        let task = &cfg.tasks["login"];
+        assert_eq!(
+            task.run.as_ref().unwrap().0,
+            vec!["login deploy hunter2-deploy {{token}} visible"]
+        );
+        assert!(task.dynamic_vars["token"].is_secret());
+        let env = task.environment.as_ref().unwrap();
+        assert!(env["API_KEY"].is_secret());
+        assert_eq!(env["TOKEN"], "{{token}}");
+        assert_eq!(
+            cfg.secrets.mask("login hunter2-deploy with abc123"),
+            "login *** with ***"
+        );
 
-[taskgroup.grp.helper]
-run = "touch {}"
+        let path = write_config(
+            &dir,
+            r#"
+[vars]
+both = { value = "a", sh = "echo b" }
 "#,
-                m1.display(),
-                m2.display()
-            ),
         );
-        runner::run_task(&cfg, "main", dir.path(), false).unwrap();
-        assert!(m1.exists());
-        assert!(m2.exists());
+        let err = format!("{:#}", config::load(&path).unwrap_err());
+        assert!(err.contains("only one of"), "got: {err}");
+
+        let path = write_config(
+            &dir,
+            r#"
+[vars]
+typo = { vaule = "a" }
+"#,
+        );
+        let err = format!("{:#}", config::load(&path).unwrap_err());
+        assert!(
+            err.contains("unknown field `vaule`, expected one of `value`, `sh`, `file`, `secret`"),
+            "got: {err}"
+        );
     }
 
     #[test]
-    fn run_group_task_ref_in_parallel() {
+    fn vars_env_interpolation() {
         let dir = TempDir::new().unwrap();
-        let m1 = dir.path().join("gp1.txt");
-        let m2 = dir.path().join("gp2.txt");
-        let cfg = load_config(
+        let path = write_config(
             &dir,
-            &format!(
-                r#"
-[tasks.main]
-run_parallel = ["touch {}", "plz:grp:helper"]
-
-[taskgroup.grp.helper]
-run = "touch {}"
+            r#"
+[tasks.serve]
+run = "serve --home {{env.HOME}} --port {{env.PLZ_TEST_UNSET_PORT:-3000}}"
 "#,
-                m1.display(),
-                m2.display()
-            ),
         );
-        runner::run_task(&cfg, "main", dir.path(), false).unwrap();
-        assert!(m1.exists());
-        assert!(m2.exists());
+        let cfg = config::load(&path).unwrap();
+        let home = std::env::var("HOME").unwrap();
+        assert_eq!(
+            cfg.tasks["serve"].run.as_ref().unwrap().0,
+            vec![format!("serve --home {home} --port 3000")]
+        );
     }
 
     #[test]
-    fn depends_runs_before_task() {
+    fn vars_env_unset_without_default_only_fails_its_task() {
         let dir = TempDir::new().unwrap();
-        let marker = dir.path().join("dep_marker.txt");
-        let out = dir.path().join("main_out.txt");
-        let cfg = load_config(
+        let path = write_config(
             &dir,
-            &format!(
-                r#"
-[tasks.setup]
-run = "touch {}"
+            r#"
+[tasks.serve]
+run = "serve {{env.PLZ_TEST_UNSET_PORT}}"
 
-[tasks.main]
-depends = "setup"
-run = "test -f {} && touch {}"
+[tasks.test]
+run = "true"
 "#,
-                marker.display(),
-                marker.display(),
-                out.display()
-            ),
         );
-        runner::run_task(&cfg, "main", dir.path(), false).unwrap();
-        assert!(marker.exists());
-        assert!(out.exists());
+        let cfg = config::load(&path).unwrap();
+        assert!(cfg.tasks["test"].unavailable.is_none());
+        let unavailable = cfg.tasks["serve"].unavailable.as_ref().unwrap();
+        assert!(
+            unavailable
+                .message
+                .contains("Environment variable \"PLZ_TEST_UNSET_PORT\" is not set"),
+            "got: {}",
+            unavailable.message
+        );
+        assert_eq!(unavailable.location.as_ref().unwrap().line, 3);
     }
 
     #[test]
-    fn depends_failure_prevents_task() {
+    fn vars_builtins_in_dir_and_fail_hook() {
         let dir = TempDir::new().unwrap();
-        let marker = dir.path().join("should_not_exist.txt");
-        let cfg = load_config(
+        let path = write_config(
             &dir,
-            &format!(
-                r#"
-[tasks.bad]
-run = "false"
+            r#"
+[tasks.build]
+run = "build --os {{plz.os}} --arch {{plz.arch}}"
+dir = "out/{{plz.task}}"
+fail_hook = { message = "{{plz.task}} failed in {{plz.root}}" }
 
-[tasks.main]
-depends = "bad"
-run = "touch {}"
+[taskgroup.web.test]
+run = "echo {{plz.task}} {{plz.cpus}}"
 "#,
-                marker.display()
-            ),
         );
-        assert!(runner::run_task(&cfg, "main", dir.path(), false).is_err());
-        assert!(!marker.exists());
+        let cfg = config::load(&path).unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let task = &cfg.tasks["build"];
+        assert_eq!(
+            task.run.as_ref().unwrap().0,
+            vec![format!(
+                "build --os {} --arch {}",
+                std::env::consts::OS,
+                std::env::consts::ARCH
+            )]
+        );
+        assert_eq!(task.dir.as_deref(), Some("out/build"));
+        match task.fail_hook {
+            Some(config::FailHook::Message(ref msg)) => {
+                assert_eq!(msg, &format!("build failed in {}", root.display()));
+            }
+            ref other => panic!("unexpected fail_hook: {other:?}"),
+        }
+        let test = cfg.get_group_task("web", "test").unwrap();
+        assert!(test.run.as_ref().unwrap().0[0].starts_with("echo web:test "));
     }
 
     #[test]
-    fn depends_multiple_run_in_order() {
+    fn vars_git_root_builtin() {
         let dir = TempDir::new().unwrap();
-        let m1 = dir.path().join("dep1.txt");
-        let m2 = dir.path().join("dep2.txt");
-        let out = dir.path().join("main_out.txt");
-        let cfg = load_config(
-            &dir,
-            &format!(
-                r#"
-[tasks.dep1]
-run = "touch {}"
+        fs::create_dir(dir.path().join(".git")).unwrap();
+        let sub = dir.path().join("app");
+        fs::create_dir(&sub).unwrap();
+        let path = sub.join("plz.toml");
+        fs::write(&path, "[tasks.a]\nrun = \"ls {{plz.git_root}}\"\n").unwrap();
+        let cfg = config::load(&path).unwrap();
+        let git_root = dir.path().canonicalize().unwrap();
+        assert_eq!(
+            cfg.tasks["a"].run.as_ref().unwrap().0,
+            vec![format!("ls {}", git_root.display())]
+        );
+    }
 
-[tasks.dep2]
-run = "touch {}"
+    #[test]
+    fn vars_reference_other_vars() {
+        let dir = TempDir::new().unwrap();
+        let path = write_config(
+            &dir,
+            r#"
+[vars]
+registry = "ghcr.io/myorg"
+image = "{{registry}}/{{app}}"
+app = "myapp"
 
-[tasks.main]
-depends = ["dep1", "dep2"]
-run = "test -f {} && test -f {} && touch {}"
+[tasks.push]
+run = "docker push {{image}}"
 "#,
-                m1.display(),
-                m2.display(),
-                m1.display(),
-                m2.display(),
-                out.display()
-            ),
         );
-        runner::run_task(&cfg, "main", dir.path(), false).unwrap();
-        assert!(m1.exists());
-        assert!(m2.exists());
-        assert!(out.exists());
+        let cfg = config::load(&path).unwrap();
+        assert_eq!(
+            cfg.tasks["push"].run.as_ref().unwrap().0,
+            vec!["docker push ghcr.io/myorg/myapp"]
+        );
     }
 
     #[test]
-    fn depends_group_task() {
+    fn vars_cycle_detected() {
         let dir = TempDir::new().unwrap();
-        let marker = dir.path().join("grp_dep.txt");
-        let out = dir.path().join("main_out.txt");
-        let cfg = load_config(
+        let path = write_config(
             &dir,
-            &format!(
-                r#"
-[tasks.main]
-depends = ["ui.build"]
-run = "test -f {} && touch {}"
+            r#"
+[vars]
+a = "{{b}}"
+b = "{{a}}"
 
-[taskgroup.ui.build]
-run = "touch {}"
+[tasks.hello]
+run = "echo hello"
 "#,
-                marker.display(),
-                out.display(),
-                marker.display()
-            ),
         );
-        runner::run_task(&cfg, "main", dir.path(), false).unwrap();
-        assert!(marker.exists());
-        assert!(out.exists());
+        let msg = format!("{:#}", config::load(&path).unwrap_err());
+        assert!(
+            msg.contains("Circular variable reference: a → b → a"),
+            "got: {msg}"
+        );
     }
 
     #[test]
-    fn depends_shared_dep_runs_once() {
+    fn task_args_parsed() {
         let dir = TempDir::new().unwrap();
-        let counter = dir.path().join("counter.txt");
-        // setup appends "x" to counter file; both a and b depend on setup
-        // If dedup works, counter should contain exactly one "x"
-        let cfg = load_config(
+        let path = write_config(
             &dir,
-            &format!(
-                r#"
-[tasks.setup]
-run = "printf x >> {}"
-
-[tasks.a]
-depends = "setup"
-run = "echo a"
-
-[tasks.b]
-depends = "setup"
-run = "echo b"
-
-[tasks.all]
-run_serial = ["plz:a", "plz:b"]
+            r#"
+[tasks.deploy]
+args = [
+  { name = "env", choices = ["staging", "prod"], default = "staging" },
+  { name = "rest", variadic = true },
+]
+run = "deploy --env {{args.env}} {{args.rest}}"
 "#,
-                counter.display()
-            ),
         );
-        runner::run_task(&cfg, "all", dir.path(), false).unwrap();
-        let content = fs::read_to_string(&counter).unwrap();
-        assert_eq!(content, "x", "setup should run only once, got: {content}");
+        let cfg = config::load(&path).unwrap();
+        let task = &cfg.tasks["deploy"];
+        let args = task.args.as_ref().unwrap();
+        assert_eq!(args.len(), 2);
+        assert_eq!(args[0].usage(), "[env]");
+        assert_eq!(args[1].usage(), "[rest...]");
+        assert_eq!(
+            task.run.as_ref().unwrap().0,
+            vec!["deploy --env {{args.env}} {{args.rest}}"]
+        );
     }
 
     #[test]
-    fn run_parallel_with_task_ref() {
+    fn task_args_validated() {
+        let cases = [
+            (
+                r#"args = [{ name = "env", choices = ["a"], default = "b" }]"#,
+                "isn't one of its choices",
+            ),
+            (
+                r#"args = [{ name = "rest", variadic = true }, { name = "env" }]"#,
+                "only the last argument can be variadic",
+            ),
+            (
+                r#"args = [{ name = "env" }, { name = "env" }]"#,
+                "more than once",
+            ),
+            (r#"args = [{ name = "env" }]"#, "no argument \"typo\""),
+        ];
+        for (args, expected) in cases {
+            let dir = TempDir::new().unwrap();
+            let path = write_config(
+                &dir,
+                &format!("[tasks.deploy]\n{args}\nrun = \"deploy {{{{args.typo}}}}\"\n"),
+            );
+            let msg = format!("{:#}", config::load(&path).unwrap_err());
+            assert!(msg.contains(expected), "expected {expected:?}, got: {msg}");
+        }
+    }
+
+    #[test]
+    fn platform_tables_select_current_os() {
         let dir = TempDir::new().unwrap();
-        let m1 = dir.path().join("p1.txt");
-        let m2 = dir.path().join("p2.txt");
-        let cfg = load_config(
+        let path = write_config(
             &dir,
             &format!(
                 r#"
-[tasks.main]
-run_parallel = ["touch {}", "plz:helper"]
+[tasks.open]
+run = {{ {os} = "open-here", default = "open-elsewhere" }}
+dir = {{ {os} = "here" }}
+env = "pnpm"
 
-[tasks.helper]
-run = "touch {}"
+[tasks.fallback]
+run_serial = {{ {other} = ["a"], default = ["b", "c"] }}
+dir = {{ {other} = "there" }}
 "#,
-                m1.display(),
-                m2.display()
+                os = std::env::consts::OS,
+                other = other_platform(),
             ),
         );
-        runner::run_task(&cfg, "main", dir.path(), false).unwrap();
-        assert!(m1.exists());
-        assert!(m2.exists());
+        let cfg = config::load(&path).unwrap();
+        let open = &cfg.tasks["open"];
+        assert_eq!(open.run.as_ref().unwrap().0, vec!["open-here"]);
+        assert_eq!(open.dir.as_deref(), Some("here"));
+        assert_eq!(open.tool_env.as_deref(), Some("pnpm"));
+        let fallback = &cfg.tasks["fallback"];
+        assert_eq!(fallback.run_serial.as_ref().unwrap(), &vec!["b", "c"]);
+        assert_eq!(fallback.dir, None);
     }
 
     #[test]
-    fn run_task_with_vars_substitution() {
+    fn platform_table_unknown_key_errors() {
         let dir = TempDir::new().unwrap();
-        let marker = dir.path().join("vars_marker.txt");
-        let cfg = load_config(
+        let path = write_config(&dir, "[tasks.a]\nrun = { plan9 = \"x\" }\n");
+        let msg = format!("{:#}", config::load(&path).unwrap_err());
+        assert!(msg.contains("unknown field `plan9`"), "got: {msg}");
+    }
+
+    #[test]
+    fn platforms_hide_tasks_elsewhere() {
+        let dir = TempDir::new().unwrap();
+        let path = write_config(
             &dir,
             &format!(
                 r#"
-[vars]
-marker_path = "{}"
+[tasks.here]
+platforms = ["{os}"]
+run = "echo here"
 
-[tasks.test]
-run = "touch {{{{marker_path}}}}"
+[tasks.elsewhere]
+platforms = ["{other}"]
+run = "echo elsewhere"
 "#,
-                marker.display()
+                os = std::env::consts::OS,
+                other = other_platform(),
             ),
         );
-        runner::run_task(&cfg, "test", dir.path(), false).unwrap();
-        assert!(marker.exists());
-    }
-}
-
-mod init_tests {
-    use super::*;
-    use plzplz::init;
-    use plzplz::templates;
+        let cfg = config::load(&path).unwrap();
+        assert!(!cfg.tasks["here"].hide);
+        assert!(cfg.tasks["elsewhere"].hide);
 
-    #[test]
-    fn detect_environment_rust() {
-        let dir = TempDir::new().unwrap();
-        fs::write(dir.path().join("Cargo.toml"), "[package]").unwrap();
-        let envs = templates::load_environments();
-        let detected = templates::detect_environments(dir.path(), &envs);
-        assert!(detected.contains(&"rust".to_string()));
+        let path = write_config(&dir, "[tasks.a]\nplatforms = [\"beos\"]\nrun = \"x\"\n");
+        let msg = format!("{:#}", config::load(&path).unwrap_err());
+        assert!(msg.contains("unknown platform \"beos\""), "got: {msg}");
     }
 
     #[test]
-    fn detect_environment_pnpm() {
+    fn empty_run_if_errors() {
         let dir = TempDir::new().unwrap();
-        fs::write(dir.path().join("pnpm-lock.yaml"), "").unwrap();
-        let envs = templates::load_environments();
-        let detected = templates::detect_environments(dir.path(), &envs);
-        assert!(detected.contains(&"pnpm".to_string()));
+        let path = write_config(&dir, "[tasks.a]\nrun_if = {}\nrun = \"x\"\n");
+        let msg = format!("{:#}", config::load(&path).unwrap_err());
+        assert!(
+            msg.contains("run_if needs at least one of file, env, sh or ci"),
+            "got: {msg}"
+        );
     }
 
     #[test]
-    fn detect_environment_npm() {
+    fn aliases_resolve() {
         let dir = TempDir::new().unwrap();
-        fs::write(dir.path().join("package-lock.json"), "{}").unwrap();
-        let envs = templates::load_environments();
-        let detected = templates::detect_environments(dir.path(), &envs);
-        assert!(detected.contains(&"npm".to_string()));
+        let path = write_config(
+            &dir,
+            r#"
+[tasks.test]
+aliases = ["t"]
+run = "cargo test"
+
+[taskgroup.docs.build]
+aliases = ["b"]
+run = "pnpm build"
+"#,
+        );
+        let cfg = config::load(&path).unwrap();
+        assert_eq!(cfg.task_for_alias("t"), Some("test"));
+        assert_eq!(cfg.task_for_alias("b"), None);
+        assert_eq!(
+            cfg.get_group("docs").unwrap().task_for_alias("b"),
+            Some("build")
+        );
     }
 
     #[test]
-    fn detect_environment_npm_from_package_json() {
-        let dir = TempDir::new().unwrap();
-        fs::write(dir.path().join("package.json"), "{}").unwrap();
-        let envs = templates::load_environments();
-        let detected = templates::detect_environments(dir.path(), &envs);
-        assert!(detected.contains(&"npm".to_string()));
+    fn alias_collisions_error() {
+        let cases = [
+            (
+                "[tasks.test]\naliases = [\"build\"]\nrun = \"x\"\n[tasks.build]\nrun = \"x\"\n",
+                "conflicts with the task \"build\"",
+            ),
+            (
+                "[tasks.test]\naliases = [\"docs\"]\nrun = \"x\"\n[taskgroup.docs.a]\nrun = \"x\"\n",
+                "conflicts with the task group \"docs\"",
+            ),
+            (
+                "[tasks.test]\naliases = [\"init\"]\nrun = \"x\"\n",
+                "conflicts with the built-in command \"init\"",
+            ),
+            (
+                "[tasks.a]\naliases = [\"x\"]\nrun = \"x\"\n[tasks.b]\naliases = [\"x\"]\nrun = \"x\"\n",
+                "Alias \"x\" is used by both \"a\" and \"b\"",
+            ),
+            (
+                "[taskgroup.g.a]\naliases = [\"b\"]\nrun = \"x\"\n[taskgroup.g.b]\nrun = \"x\"\n",
+                "conflicts with the task \"g:b\"",
+            ),
+        ];
+        for (content, expected) in cases {
+            let dir = TempDir::new().unwrap();
+            let path = write_config(&dir, content);
+            let msg = format!("{:#}", config::load(&path).unwrap_err());
+            assert!(msg.contains(expected), "expected {expected:?}, got: {msg}");
+        }
     }
 
     #[test]
-    fn detect_environment_uv() {
+    fn task_extends_inherits_fields() {
         let dir = TempDir::new().unwrap();
-        fs::write(dir.path().join("uv.lock"), "").unwrap();
-        let envs = templates::load_environments();
-        let detected = templates::detect_environments(dir.path(), &envs);
-        assert!(detected.contains(&"uv".to_string()));
-    }
+        let path = write_config(
+            &dir,
+            r#"
+[tasks.test]
+run = "cargo test"
+depends = "build"
+dir = "crates/core"
+environment = { RUST_LOG = "info", CI = "1" }
+fail_hook = { message = "tests failed" }
+git_hook = "pre-push"
+
+[tasks.build]
+run = "cargo build"
+
+[tasks.test-release]
+extends = "test"
+run = "cargo test --release"
+environment = { RUST_LOG = "debug" }
+
+[tasks.test-release-quiet]
+extends = "test-release"
+git_hook = "pre-commit"
+
+[taskgroup.web.test]
+extends = "test"
+dir = "web"
+"#,
+        );
+        let cfg = config::load(&path).unwrap();
+        let release = &cfg.tasks["test-release"];
+        assert_eq!(
+            release.run.as_ref().unwrap().0,
+            vec!["cargo test --release"]
+        );
+        assert_eq!(release.depends.as_ref().unwrap().0, vec!["build"]);
+        assert_eq!(release.dir.as_deref(), Some("crates/core"));
+        assert_eq!(release.git_hook.as_deref(), Some("pre-push"));
+        assert!(matches!(release.fail_hook, Some(FailHook::Message(_))));
+        let env = release.environment.as_ref().unwrap();
+        assert_eq!(env["RUST_LOG"], "debug");
+        assert_eq!(env["CI"], "1");
+
+        let quiet = &cfg.tasks["test-release-quiet"];
+        assert_eq!(quiet.run.as_ref().unwrap().0, vec!["cargo test --release"]);
+        assert_eq!(quiet.git_hook.as_deref(), Some("pre-commit"));
+        assert_eq!(quiet.inherited["run"], "test-release");
+        assert_eq!(quiet.inherited["depends"], "test");
+
+        let web = cfg.get_group_task("web", "test").unwrap();
+        assert_eq!(web.run.as_ref().unwrap().0, vec!["cargo test"]);
+        assert_eq!(web.dir.as_deref(), Some("web"));
+    }
+
+    #[test]
+    fn task_extends_errors() {
+        let cases = [
+            (
+                "[tasks.a]\nextends = \"missing\"\n",
+                "Task \"a\" extends \"missing\", but no task \"missing\" exists",
+            ),
+            (
+                "[tasks.a]\nextends = \"b\"\n[tasks.b]\nextends = \"a\"\n",
+                "Circular extends: a → b → a",
+            ),
+            (
+                "[tasks.base]\nrun = \"x\"\ndepends = \"gone\"\n[tasks.app]\nextends = \"base\"\n",
+                "Task \"app\" has depends \"gone\" (inherited from \"base\")",
+            ),
+        ];
+        for (content, expected) in cases {
+            let dir = TempDir::new().unwrap();
+            let path = write_config(&dir, content);
+            let msg = format!("{:#}", config::load(&path).unwrap_err());
+            assert!(msg.contains(expected), "expected {expected:?}, got: {msg}");
+        }
+    }
+
+    #[test]
+    fn load_errors_point_at_source() {
+        let cases = [
+            (
+                "[tasks.a]\nrun = \"x\"\ngit_hook = \"pre-comit\"\n",
+                "invalid git_hook",
+                (3, 12),
+            ),
+            (
+                "[tasks.a]\nrun = \"x\"\n[tasks.b]\ndepends = [\"a\", \"nope\"]\nrun = \"x\"\n",
+                "no task \"nope\" exists",
+                (4, 17),
+            ),
+            (
+                "[tasks.a]\nrun = [\"ok\", \"echo {{foo}}\"]\n",
+                "Unresolved variable",
+                (2, 14),
+            ),
+            (
+                "[taskgroup.g.base]\nrun = \"x\"\naliases = [\"b\", \"base\"]\n",
+                "conflicts with the task",
+                (3, 17),
+            ),
+            (
+                "[tasks.base]\nrun = \"x\"\ndepends = \"gone\"\n[tasks.app]\nextends = \"base\"\n",
+                "Task \"app\" has depends \"gone\" (inherited from \"base\")",
+                (3, 11),
+            ),
+            (
+                "[tasks.a]\nrun = 5\n",
+                "Failed to deserialize config",
+                (2, 7),
+            ),
+            ("[tasks.a]\nrun = [\n", "Failed to parse plz.toml", (2, 8)),
+        ];
+        for (content, expected, (line, column)) in cases {
+            let dir = TempDir::new().unwrap();
+            let path = write_config(&dir, content);
+            let err = config::load(&path).unwrap_err();
+            let diagnostic = err
+                .downcast_ref::<Diagnostic>()
+                .unwrap_or_else(|| panic!("no location for {expected:?}: {err:#}"));
+            assert!(diagnostic.message.contains(expected), "{diagnostic}");
+            let location = diagnostic.location.as_ref().unwrap();
+            assert_eq!(
+                (location.line, location.column),
+                (line, column),
+                "{diagnostic}"
+            );
+            assert!(location.file.ends_with("plz.toml"), "{diagnostic}");
+        }
+    }
+
+    #[test]
+    fn load_errors_point_at_the_defining_file() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("web")).unwrap();
+        fs::write(
+            dir.path().join("web/plz.toml"),
+            "[tasks.build]\nrun = \"x\"\n\ngit_hook = \"nope\"\n",
+        )
+        .unwrap();
+        let path = write_config(&dir, "[workspace]\nmembers = [\"web\"]\n");
+        let err = config::load(&path).unwrap_err();
+        let diagnostic = err
+            .chain()
+            .find_map(|e| e.downcast_ref::<Diagnostic>())
+            .unwrap();
+        let location = diagnostic.location.as_ref().unwrap();
+        assert!(location.file.ends_with("web/plz.toml"), "{diagnostic}");
+        assert_eq!((location.line, location.column), (4, 12));
+        assert!(
+            diagnostic
+                .to_string()
+                .ends_with("\n4 | git_hook = \"nope\"\n  |            ^^^^^^"),
+            "{diagnostic}"
+        );
+    }
+
+    #[test]
+    fn check_reports_every_problem() {
+        let dir = TempDir::new().unwrap();
+        let path = write_config(
+            &dir,
+            r#"
+[tasks.build]
+run = "echo {{foo}}"
+dir = "missing"
+
+[tasks.test]
+depends = ["build", "nope"]
+run = "cargo test"
+oops = true
+
+[tasks.lint]
+git_hook = "pre-comit"
+"#,
+        );
+        let found: Vec<(Severity, String, usize)> = config::check(&path, None)
+            .into_iter()
+            .map(|d| (d.severity, d.message, d.location.unwrap().line))
+            .collect();
+        let expected = [
+            (Severity::Error, "Unresolved variable \"{{foo}}\"", 3),
+            (Severity::Error, "dir \"missing\", which doesn't exist", 4),
+            (Severity::Error, "no task \"nope\" exists", 7),
+            (Severity::Warning, "unknown key \"oops\"", 9),
+            (Severity::Warning, "\"lint\" has nothing to run", 11),
+            (Severity::Error, "invalid git_hook \"pre-comit\"", 12),
+        ];
+        assert_eq!(found.len(), expected.len(), "{found:#?}");
+        for ((severity, message, line), (want_severity, want_message, want_line)) in
+            found.iter().zip(expected)
+        {
+            assert_eq!(*severity, want_severity, "{message}");
+            assert!(message.contains(want_message), "{message}");
+            assert_eq!(*line, want_line, "{message}");
+        }
+    }
+
+    #[test]
+    fn check_valid_config_has_no_problems() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("web")).unwrap();
+        let path = write_config(
+            &dir,
+            "[tasks.build]\nrun = \"x\"\ndir = \"web\"\n\n[tasks.all]\ndepends = [\"build\"]\n",
+        );
+        let found = config::check(&path, None);
+        assert!(found.is_empty(), "{found:?}");
+    }
+
+    #[test]
+    fn local_config_overrides_shared() {
+        let dir = TempDir::new().unwrap();
+        let path = write_config(
+            &dir,
+            r#"
+[vars]
+port = "3000"
+host = "localhost"
+
+[tasks.serve]
+run = "serve --port {{port}} --host {{host}}"
+environment = { A = "shared", B = "shared" }
+description = "Serve it"
+
+[tasks.lint]
+run = "cargo clippy"
+git_hook = "pre-commit"
+"#,
+        );
+        fs::write(
+            dir.path().join("plz.local.toml"),
+            r#"
+[plz]
+version = ">=0.0.1"
+
+[vars]
+port = "8080"
+
+[tasks.serve]
+environment = { B = "local" }
+
+[tasks.lint]
+git_hook = ""
+
+[tasks.mine]
+script = "print('hi')"
+interpreter = "python3"
+"#,
+        )
+        .unwrap();
+
+        let cfg = config::load(&path).unwrap();
+        let serve = &cfg.tasks["serve"];
+        assert_eq!(
+            serve.run.as_ref().unwrap().0,
+            ["serve --port 8080 --host localhost"]
+        );
+        let env = serve.environment.as_ref().unwrap();
+        assert_eq!((env["A"].text(), env["B"].text()), ("shared", "local"));
+        assert_eq!(serve.description.as_deref(), Some("Serve it"));
+        assert!(cfg.tasks["lint"].git_hook.is_none());
+        assert!(cfg.tasks["mine"].script.is_some());
+
+        let found: Vec<(Severity, String)> = config::check(&path, None)
+            .into_iter()
+            .filter(|d| {
+                d.location
+                    .as_ref()
+                    .unwrap()
+                    .file
+                    .ends_with("plz.local.toml")
+            })
+            .map(|d| (d.severity, d.message))
+            .collect();
+        let expected = [
+            (Severity::Warning, "[plz] is ignored in plz.local.toml"),
+            (Severity::Note, "plz.local.toml sets var \"port\""),
+            (
+                Severity::Note,
+                "plz.local.toml overrides task \"serve\": environment",
+            ),
+            (
+                Severity::Note,
+                "plz.local.toml overrides task \"lint\": git_hook",
+            ),
+            (Severity::Note, "plz.local.toml adds task \"mine\""),
+        ];
+        assert_eq!(found.len(), expected.len(), "{found:#?}");
+        for ((severity, message), (want_severity, want_message)) in found.iter().zip(expected) {
+            assert_eq!(*severity, want_severity, "{message}");
+            assert!(message.starts_with(want_message), "{message}");
+        }
+    }
+
+    #[test]
+    fn profiles_layer_vars_and_environment() {
+        let dir = TempDir::new().unwrap();
+        let path = write_config(
+            &dir,
+            r#"
+[plz]
+default_profile = "dev"
+
+[vars]
+url = "http://localhost"
+region = "local"
+
+[profile.dev.vars]
+url = "http://dev.internal"
+
+[profile.prod.vars]
+url = "https://example.com"
+region = "eu"
+
+[profile.prod.environment]
+MODE = "prod-{{region}}"
+
+[tasks.deploy]
+run = "deploy {{url}} {{region}}"
+environment = { MODE = "task", OTHER = "kept" }
+
+[taskgroup.web.vars]
+region = "us"
+
+[taskgroup.web.deploy]
+run = "deploy {{region}}"
+"#,
+        );
+        let run = |cfg: &config::PlzConfig| cfg.tasks["deploy"].run.as_ref().unwrap().0[0].clone();
+
+        let dev = config::load_profile(&path, None).unwrap();
+        assert_eq!(dev.active_profile.as_deref(), Some("dev"));
+        assert_eq!(run(&dev), "deploy http://dev.internal local");
+
+        let prod = config::load_profile(&path, Some("prod")).unwrap();
+        assert_eq!(prod.active_profile.as_deref(), Some("prod"));
+        assert_eq!(run(&prod), "deploy https://example.com eu");
+        let env = prod.tasks["deploy"].environment.as_ref().unwrap();
+        assert_eq!(
+            (env["MODE"].text(), env["OTHER"].text()),
+            ("prod-eu", "kept")
+        );
+        let web = prod.get_group_task("web", "deploy").unwrap();
+        assert_eq!(web.run.as_ref().unwrap().0[0], "deploy eu");
+
+        let err = format!("{:#}", config::load_profile(&path, Some("qa")).unwrap_err());
+        assert!(
+            err.contains("Unknown profile \"qa\". Profiles: dev, prod"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn warn_unknown_top_level_key() {
+        let doc: toml_edit::DocumentMut = r#"
+[tasks.hello]
+run = "echo hello"
+
+[something]
+foo = "bar"
+"#
+        .parse()
+        .unwrap();
+        let warnings = config::warn_unknown_keys(&doc);
+        assert_eq!(warnings, vec!["unknown key \"something\" in plz.toml"]);
+    }
+
+    #[test]
+    fn warn_unknown_task_key() {
+        let doc: toml_edit::DocumentMut = r#"
+[tasks.hello]
+run = "echo hello"
+oops = true
+"#
+        .parse()
+        .unwrap();
+        let warnings = config::warn_unknown_keys(&doc);
+        assert_eq!(warnings, vec!["unknown key \"oops\" in [tasks.hello]"]);
+    }
+
+    #[test]
+    fn warn_unknown_plz_key() {
+        let doc: toml_edit::DocumentMut = r#"
+[plz]
+version = ">=0.1.0"
+unknown = "bad"
+
+[tasks.hello]
+run = "echo hello"
+"#
+        .parse()
+        .unwrap();
+        let warnings = config::warn_unknown_keys(&doc);
+        assert_eq!(warnings, vec!["unknown key \"unknown\" in [plz]"]);
+    }
+
+    #[test]
+    fn warn_unknown_extends_key() {
+        let doc: toml_edit::DocumentMut = r#"
+[extends]
+env = "pnpm"
+bad = "value"
+
+[tasks.hello]
+run = "echo hello"
+"#
+        .parse()
+        .unwrap();
+        let warnings = config::warn_unknown_keys(&doc);
+        assert_eq!(warnings, vec!["unknown key \"bad\" in [extends]"]);
+    }
+
+    #[test]
+    fn warn_unknown_taskgroup_task_key() {
+        let doc: toml_edit::DocumentMut = r#"
+[taskgroup.mygroup.mytask]
+run = "echo hello"
+nope = "bad"
+"#
+        .parse()
+        .unwrap();
+        let warnings = config::warn_unknown_keys(&doc);
+        assert_eq!(
+            warnings,
+            vec!["unknown key \"nope\" in [taskgroup.mygroup.mytask]"]
+        );
+    }
+
+    #[test]
+    fn warn_unknown_taskgroup_extends_key() {
+        let doc: toml_edit::DocumentMut = r#"
+[taskgroup.mygroup.extends]
+env = "pnpm"
+bad = "value"
+
+[taskgroup.mygroup.mytask]
+run = "echo hello"
+"#
+        .parse()
+        .unwrap();
+        let warnings = config::warn_unknown_keys(&doc);
+        assert_eq!(
+            warnings,
+            vec!["unknown key \"bad\" in [taskgroup.mygroup.extends]"]
+        );
+    }
+
+    #[test]
+    fn no_warnings_for_valid_config() {
+        let doc: toml_edit::DocumentMut = r#"
+[plz]
+version = ">=0.1.0"
+
+[extends]
+env = "pnpm"
+dir = "."
+
+[vars]
+name = "world"
+
+[tasks.hello]
+run = "echo hello"
+run_serial = ["echo a", "echo b"]
+run_parallel = ["echo c", "echo d"]
+depends = "hello"
+env = "pnpm"
+dir = "."
+fail_hook = "echo fail"
+description = "Says hello"
+git_hook = "pre-commit"
+hide = true
+
+[taskgroup.mygroup.extends]
+env = "pnpm"
+dir = "."
+
+[taskgroup.mygroup.vars]
+foo = "bar"
+
+[taskgroup.mygroup.mytask]
+run = "echo hello"
+"#
+        .parse()
+        .unwrap();
+        let warnings = config::warn_unknown_keys(&doc);
+        assert!(
+            warnings.is_empty(),
+            "Expected no warnings, got: {warnings:?}"
+        );
+    }
+
+    #[test]
+    fn check_warns_unknown_keys() {
+        let cases = [
+            (
+                "[tasks.hello]\nrun = \"echo hello\"\n\n[something]\nfoo = \"bar\"\n",
+                "unknown key \"something\" in plz.toml",
+                (4, 2),
+            ),
+            (
+                "[tasks.hello]\nrun = \"echo hello\"\noops = true\n",
+                "unknown key \"oops\" in [tasks.hello]",
+                (3, 1),
+            ),
+            (
+                "[plz]\nunknown = \"bad\"\n\n[tasks.hello]\nrun = \"echo hello\"\n",
+                "unknown key \"unknown\" in [plz]",
+                (2, 1),
+            ),
+            (
+                "[extends]\nbad = \"value\"\n\n[tasks.hello]\nrun = \"echo hello\"\n",
+                "unknown key \"bad\" in [extends]",
+                (2, 1),
+            ),
+            (
+                "[taskgroup.mygroup.mytask]\nrun = \"echo hello\"\nnope = \"bad\"\n",
+                "unknown key \"nope\" in [taskgroup.mygroup.mytask]",
+                (3, 1),
+            ),
+            (
+                "[taskgroup.mygroup.extends]\nbad = \"value\"\n\n[taskgroup.mygroup.mytask]\nrun = \"echo hello\"\n",
+                "unknown key \"bad\" in [taskgroup.mygroup.extends]",
+                (2, 1),
+            ),
+        ];
+        for (content, expected, (line, column)) in cases {
+            let dir = TempDir::new().unwrap();
+            let path = write_config(&dir, content);
+            let warnings: Vec<_> = config::check(&path, None)
+                .into_iter()
+                .filter(|d| d.severity == Severity::Warning)
+                .collect();
+            assert_eq!(warnings.len(), 1, "{warnings:?}");
+            assert_eq!(warnings[0].message, expected);
+            let location = warnings[0].location.as_ref().unwrap();
+            assert_eq!(
+                (location.line, location.column),
+                (line, column),
+                "{expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn no_unknown_key_warnings_for_valid_config() {
+        let dir = TempDir::new().unwrap();
+        let path = write_config(
+            &dir,
+            r#"
+[plz]
+version = ">=0.1.0"
+
+[extends]
+env = "pnpm"
+dir = "."
+
+[vars]
+name = "world"
+
+[tasks.hello]
+run = "echo hello"
+run_serial = ["echo a", "echo b"]
+run_parallel = ["echo c", "echo d"]
+depends = "hello"
+env = "pnpm"
+dir = "."
+fail_hook = "echo fail"
+description = "Says hello"
+git_hook = "pre-commit"
+hide = true
+
+[taskgroup.mygroup.extends]
+env = "pnpm"
+dir = "."
+
+[taskgroup.mygroup.vars]
+foo = "bar"
+
+[taskgroup.mygroup.mytask]
+run = "echo hello"
+"#,
+        );
+        let warnings: Vec<String> = config::check(&path, None)
+            .into_iter()
+            .map(|d| d.message)
+            .filter(|m| m.starts_with("unknown key"))
+            .collect();
+        assert!(
+            warnings.is_empty(),
+            "Expected no warnings, got: {warnings:?}"
+        );
+    }
+}
+
+mod runner_tests {
+    use super::*;
+    use plzplz::config;
+    use plzplz::runner;
+
+    fn load_config(dir: &TempDir, content: &str) -> config::PlzConfig {
+        let path = dir.path().join("plz.toml");
+        fs::write(&path, content).unwrap();
+        config::load(&path).unwrap()
+    }
+
+    #[test]
+    fn run_simple_command() {
+        let dir = TempDir::new().unwrap();
+        let cfg = load_config(
+            &dir,
+            r#"
+[tasks.hello]
+run = "echo hello"
+"#,
+        );
+        runner::run_task(&cfg, "hello", dir.path(), false).unwrap();
+    }
+
+    #[test]
+    fn run_failing_command() {
+        let dir = TempDir::new().unwrap();
+        let cfg = load_config(
+            &dir,
+            r#"
+[tasks.fail]
+run = "false"
+"#,
+        );
+        assert!(runner::run_task(&cfg, "fail", dir.path(), false).is_err());
+    }
+
+    #[test]
+    fn run_serial_commands() {
+        let dir = TempDir::new().unwrap();
+        let marker = dir.path().join("marker.txt");
+        let cfg = load_config(
+            &dir,
+            &format!(
+                r#"
+[tasks.serial]
+run_serial = ["echo first", "touch {}"]
+"#,
+                marker.display()
+            ),
+        );
+        runner::run_task(&cfg, "serial", dir.path(), false).unwrap();
+        assert!(marker.exists());
+    }
+
+    #[test]
+    fn run_serial_stops_on_failure() {
+        let dir = TempDir::new().unwrap();
+        let marker = dir.path().join("should_not_exist.txt");
+        let cfg = load_config(
+            &dir,
+            &format!(
+                r#"
+[tasks.serial]
+run_serial = ["false", "touch {}"]
+"#,
+                marker.display()
+            ),
+        );
+        assert!(runner::run_task(&cfg, "serial", dir.path(), false).is_err());
+        assert!(!marker.exists());
+    }
+
+    #[test]
+    fn run_parallel_commands() {
+        let dir = TempDir::new().unwrap();
+        let m1 = dir.path().join("p1.txt");
+        let m2 = dir.path().join("p2.txt");
+        let cfg = load_config(
+            &dir,
+            &format!(
+                r#"
+[tasks.par]
+run_parallel = ["touch {}", "touch {}"]
+"#,
+                m1.display(),
+                m2.display()
+            ),
+        );
+        runner::run_task(&cfg, "par", dir.path(), false).unwrap();
+        assert!(m1.exists());
+        assert!(m2.exists());
+    }
+
+    #[test]
+    fn run_parallel_task_refs_execute_concurrently() {
+        // Regression: previously plz: refs in run_parallel ran sequentially.
+        // Each task signals "started" then waits up to 5s for the other's
+        // signal — serial execution would time out and fail the task.
+        let dir = TempDir::new().unwrap();
+        let a_started = dir.path().join("a_started");
+        let b_started = dir.path().join("b_started");
+        let cfg = load_config(
+            &dir,
+            &format!(
+                r#"
+[tasks.both]
+run_parallel = ["plz:a", "plz:b"]
+
+[tasks.a]
+run = "bash -c 'touch {a}; for i in $(seq 1 50); do [ -f {b} ] && exit 0; sleep 0.1; done; exit 1'"
+
+[tasks.b]
+run = "bash -c 'touch {b}; for i in $(seq 1 50); do [ -f {a} ] && exit 0; sleep 0.1; done; exit 1'"
+"#,
+                a = a_started.display(),
+                b = b_started.display(),
+            ),
+        );
+        runner::run_task(&cfg, "both", dir.path(), false).unwrap();
+        assert!(a_started.exists());
+        assert!(b_started.exists());
+    }
+
+    #[test]
+    fn run_parallel_fails_on_error() {
+        let dir = TempDir::new().unwrap();
+        let cfg = load_config(
+            &dir,
+            r#"
+[tasks.par]
+run_parallel = ["true", "false"]
+"#,
+        );
+        assert!(runner::run_task(&cfg, "par", dir.path(), false).is_err());
+    }
+
+    #[test]
+    fn run_with_bash_shell() {
+        let dir = TempDir::new().unwrap();
+        let marker = dir.path().join("marker.txt");
+        let cfg = load_config(
+            &dir,
+            &format!(
+                r#"
+[tasks.serial]
+run_serial = ["[[ -d . ]]", "touch {}"]
+shell = "bash"
+
+[tasks.parallel]
+run_parallel = ["[[ -d . ]]", "echo ok"]
+shell = ["bash", "-c"]
+"#,
+                marker.display()
+            ),
+        );
+        runner::run_task(&cfg, "serial", dir.path(), false).unwrap();
+        assert!(marker.exists());
+        runner::run_task(&cfg, "parallel", dir.path(), false).unwrap();
+    }
+
+    #[test]
+    fn run_without_shell() {
+        let dir = TempDir::new().unwrap();
+        let cfg = load_config(
+            &dir,
+            r#"
+[tasks.touch]
+run = "touch 'a b' $HOME"
+shell = false
+
+[tasks.missing]
+run = "no-such-program-plz"
+shell = false
+"#,
+        );
+        runner::run_task(&cfg, "touch", dir.path(), false).unwrap();
+        assert!(dir.path().join("a b").exists());
+        assert!(dir.path().join("$HOME").exists());
+        let err = runner::run_task(&cfg, "missing", dir.path(), false).unwrap_err();
+        assert!(format!("{err:#}").contains("Failed to run: no-such-program-plz"));
+    }
+
+    #[test]
+    fn timeout_kills_the_process_group() {
+        let dir = TempDir::new().unwrap();
+        let cfg = load_config(
+            &dir,
+            r#"
+[tasks.hang]
+run = "sleep 30 & echo $! > pid.txt; wait"
+timeout = "500ms"
+"#,
+        );
+        let start = std::time::Instant::now();
+        let err = runner::run_task(&cfg, "hang", dir.path(), false).unwrap_err();
+        assert!(start.elapsed() < std::time::Duration::from_secs(10));
+        assert_eq!(
+            err.to_string(),
+            "Command timed out after 500ms: sleep 30 & echo $! > pid.txt; wait"
+        );
+        assert!(err.downcast_ref::<runner::TimedOut>().is_some());
+
+        // The background sleep was killed along with the shell
+        let pid = fs::read_to_string(dir.path().join("pid.txt")).unwrap();
+        let alive = || {
+            std::process::Command::new("kill")
+                .args(["-0", pid.trim()])
+                .stderr(std::process::Stdio::null())
+                .status()
+                .unwrap()
+                .success()
+        };
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while alive() && std::time::Instant::now() < deadline {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+        assert!(!alive());
+    }
+
+    #[test]
+    fn timeout_past_the_clock_never_runs_out() {
+        let dir = TempDir::new().unwrap();
+        let cfg = load_config(
+            &dir,
+            r#"
+[tasks.long]
+run = "true"
+timeout = "5000000000000000h"
+"#,
+        );
+        runner::run_task(&cfg, "long", dir.path(), false).unwrap();
+    }
+
+    #[test]
+    fn timeout_covers_conditions_and_vars() {
+        let dir = TempDir::new().unwrap();
+        let cfg = load_config(
+            &dir,
+            r#"
+[tasks.condition]
+run_if = { sh = "sleep 30" }
+run = "touch condition.txt"
+timeout = "300ms"
+
+[tasks.var]
+environment = { SLOW = { sh = "sleep 30" } }
+run = "touch var.txt"
+timeout = "300ms"
+"#,
+        );
+        for (name, expected) in [
+            ("condition", "Failed to run condition `sleep 30`"),
+            ("var", "Failed to evaluate var \"SLOW\""),
+        ] {
+            let start = std::time::Instant::now();
+            let err = runner::run_task(&cfg, name, dir.path(), false).unwrap_err();
+            assert!(start.elapsed() < std::time::Duration::from_secs(10));
+            assert_eq!(
+                format!("{err:#}"),
+                format!("{expected}: Command timed out after 300ms: sleep 30")
+            );
+            assert!(err.downcast_ref::<runner::TimedOut>().is_some());
+            assert!(!dir.path().join(format!("{name}.txt")).exists());
+        }
+    }
+
+    #[test]
+    fn timed_out_parallel_command_in_summary() {
+        let dir = TempDir::new().unwrap();
+        let mut cfg = load_config(
+            &dir,
+            r#"
+[tasks.check]
+run_parallel = ["sleep 30", "true"]
+timeout = "300ms"
+"#,
+        );
+        let summaries = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = summaries.clone();
+        cfg.events = runner::Events(Some(std::sync::Arc::new(move |event: &runner::Event| {
+            if let runner::Event::Summary { results } = event {
+                seen.lock().unwrap().push(results.clone());
+            }
+        })));
+        assert!(runner::run_task(&cfg, "check", dir.path(), false).is_err());
+        let summaries = summaries.lock().unwrap();
+        assert_eq!(
+            summaries[0],
+            vec![
+                (
+                    "sleep 30".to_string(),
+                    runner::Outcome::TimedOut(std::time::Duration::from_millis(300))
+                ),
+                ("true".to_string(), runner::Outcome::Passed),
+            ]
+        );
+    }
+
+    #[test]
+    fn retry_until_passing_before_fail_hook() {
+        let dir = TempDir::new().unwrap();
+        let mut cfg = load_config(
+            &dir,
+            r#"
+[tasks.flaky]
+run = "echo x >> tries.txt; test $(wc -l < tries.txt) -ge 3"
+retry = { attempts = 3, delay = "10ms", backoff = 2.0 }
+
+[tasks.broken]
+run = "echo x >> broken.txt; false"
+retry = { attempts = 2 }
+fail_hook = "echo hook >> hook.txt"
+
+[tasks.check]
+run_serial = ["plz:flaky", "plz:broken"]
+"#,
+        );
+        let events = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = events.clone();
+        cfg.events = runner::Events(Some(std::sync::Arc::new(move |event: &runner::Event| {
+            seen.lock().unwrap().push(event.clone());
+        })));
+
+        assert_eq!(
+            runner::run_task(&cfg, "flaky", dir.path(), false).unwrap(),
+            runner::Outcome::PassedOnAttempt {
+                attempt: 3,
+                attempts: 3
+            }
+        );
+        let retries: Vec<(u32, u128)> = events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|e| match e {
+                runner::Event::Retrying {
+                    attempt,
+                    attempts: 3,
+                    delay,
+                    ..
+                } => Some((*attempt, delay.as_millis())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(retries, vec![(2, 10), (3, 20)]);
+
+        runner::run_task(&cfg, "broken", dir.path(), false).unwrap_err();
+        let count = |file: &str| {
+            fs::read_to_string(dir.path().join(file))
+                .unwrap()
+                .lines()
+                .count()
+        };
+        assert_eq!((count("broken.txt"), count("hook.txt")), (2, 1));
+
+        fs::remove_file(dir.path().join("tries.txt")).unwrap();
+        events.lock().unwrap().clear();
+        runner::run_task(&cfg, "check", dir.path(), false).unwrap_err();
+        let events = events.lock().unwrap();
+        let summary = events.iter().find_map(|e| match e {
+            runner::Event::Summary { results } => Some(results.clone()),
+            _ => None,
+        });
+        assert_eq!(
+            summary.unwrap(),
+            vec![
+                (
+                    "flaky".to_string(),
+                    runner::Outcome::PassedOnAttempt {
+                        attempt: 3,
+                        attempts: 3
+                    }
+                ),
+                ("broken".to_string(), runner::Outcome::Failed),
+            ]
+        );
+    }
+
+    #[test]
+    fn summary_shown_when_an_entry_passes_on_retry() {
+        let dir = TempDir::new().unwrap();
+        let mut cfg = load_config(
+            &dir,
+            r#"
+[tasks.flaky]
+run = "echo x >> tries.txt; test $(wc -l < tries.txt) -ge 2"
+retry = { attempts = 2 }
+
+[tasks.ok]
+run = "true"
+
+[tasks.serial]
+run_serial = ["plz:ok"]
+
+[tasks.check]
+run_parallel = ["plz:flaky", "plz:ok"]
+"#,
+        );
+        let summaries = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = summaries.clone();
+        cfg.events = runner::Events(Some(std::sync::Arc::new(move |event: &runner::Event| {
+            if let runner::Event::Summary { results } = event {
+                seen.lock().unwrap().push(results.clone());
+            }
+        })));
+
+        runner::run_task(&cfg, "serial", dir.path(), false).unwrap();
+        assert!(summaries.lock().unwrap().is_empty());
+
+        runner::run_task(&cfg, "check", dir.path(), false).unwrap();
+        assert_eq!(
+            *summaries.lock().unwrap(),
+            vec![vec![
+                (
+                    "flaky".to_string(),
+                    runner::Outcome::PassedOnAttempt {
+                        attempt: 2,
+                        attempts: 2
+                    }
+                ),
+                ("ok".to_string(), runner::Outcome::Passed),
+            ]]
+        );
+    }
+
+    /// Most commands that ran at once, from a log of `+` and `-` lines.
+    fn most_at_once(log: &str) -> i32 {
+        log.lines()
+            .scan(0, |running, line| {
+                *running += if line == "+" { 1 } else { -1 };
+                Some(*running)
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn max_parallel_and_jobs_limit() {
+        let dir = TempDir::new().unwrap();
+        let mut cfg = load_config(
+            &dir,
+            r#"
+[vars]
+step = "echo + >> {{plz.root}}/log; sleep 0.2; echo - >> {{plz.root}}/log"
+
+[tasks.wide]
+run_parallel = ["{{step}}", "{{step}}", "{{step}}", "{{step}}"]
+max_parallel = 2
+
+[tasks.nested]
+run_parallel = ["plz:pair", "plz:pair", "{{step}}"]
+
+[tasks.pair]
+run_parallel = ["{{step}}", "{{step}}"]
+"#,
+        );
+        let log = dir.path().join("log");
+
+        runner::run_task(&cfg, "wide", dir.path(), false).unwrap();
+        let ran = fs::read_to_string(&log).unwrap();
+        assert_eq!(ran.lines().count(), 8);
+        assert_eq!(most_at_once(&ran), 2, "log: {ran}");
+
+        fs::remove_file(&log).unwrap();
+        runner::run_task(&cfg, "nested", dir.path(), false).unwrap();
+        assert_eq!(most_at_once(&fs::read_to_string(&log).unwrap()), 5);
+
+        // -j counts commands inside referenced tasks too
+        fs::remove_file(&log).unwrap();
+        cfg.jobs = Some(std::sync::Arc::new(runner::Jobs::new(3)));
+        runner::run_task(&cfg, "nested", dir.path(), false).unwrap();
+        let ran = fs::read_to_string(&log).unwrap();
+        assert_eq!(ran.lines().count(), 10);
+        assert_eq!(most_at_once(&ran), 3, "log: {ran}");
+    }
+
+    #[test]
+    fn fail_fast_cancels_the_rest_before_fail_hooks() {
+        let dir = TempDir::new().unwrap();
+        let mut cfg = load_config(
+            &dir,
+            r#"
+[tasks.check]
+run_parallel = ["sleep 30", "plz:slow", "plz:broken", "plz:lint"]
+fail_fast = true
+max_parallel = 3
+
+[tasks.slow]
+run_serial = ["sleep 30", "touch slow.txt"]
+
+[tasks.broken]
+run = "sleep 0.3; exit 3"
+fail_hook = "touch hook.txt"
+
+[tasks.lint]
+run = "touch lint.txt"
+"#,
+        );
+        let events = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = events.clone();
+        cfg.events = runner::Events(Some(std::sync::Arc::new(move |event: &runner::Event| {
+            seen.lock().unwrap().push(event.clone());
+        })));
+
+        let started = std::time::Instant::now();
+        runner::run_task(&cfg, "check", dir.path(), false).unwrap_err();
+        assert!(started.elapsed() < std::time::Duration::from_secs(10));
+
+        let summary = events.lock().unwrap().iter().find_map(|e| match e {
+            runner::Event::Summary { results } => Some(results.clone()),
+            _ => None,
+        });
+        let cancelled = runner::Outcome::Cancelled;
+        assert_eq!(
+            summary.unwrap(),
+            vec![
+                ("sleep 30".to_string(), cancelled),
+                ("slow".to_string(), cancelled),
+                ("broken".to_string(), runner::Outcome::Failed),
+                ("lint".to_string(), cancelled),
+            ]
+        );
+        assert!(dir.path().join("hook.txt").exists());
+        assert!(!dir.path().join("slow.txt").exists());
+        assert!(!dir.path().join("lint.txt").exists());
+    }
+
+    #[test]
+    fn run_script_with_interpreter() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        let cfg = load_config(
+            &dir,
+            r#"
+[tasks.py]
+dir = "sub"
+environment = { GREETING = "hi" }
+interpreter = "sh -e"
+script = '''
+printf '%s %s %s\n' "$GREETING" "$#" "$1" > out.txt
+echo "$0" >> out.txt
+'''
+"#,
+        );
+        let args = vec!["a b".to_string(), "c".to_string()];
+        runner::run_task_with_args(&cfg, "py", dir.path(), false, &args).unwrap();
+        let output = fs::read_to_string(dir.path().join("sub/out.txt")).unwrap();
+        let (first, script_path) = output.split_once('\n').unwrap();
+        assert_eq!(first, "hi 2 a b");
+        // The temp file is gone once the task finishes
+        assert!(!std::path::Path::new(script_path.trim()).exists());
+    }
+
+    #[test]
+    fn run_script_with_shebang() {
+        let dir = TempDir::new().unwrap();
+        let cfg = load_config(
+            &dir,
+            r#"
+[tasks.shebang]
+args = [{ name = "name" }]
+script = """
+#!/bin/sh
+echo "{{args.name}}" > out.txt
+"""
+"#,
+        );
+        let args = vec!["it's me".to_string()];
+        runner::run_task_with_args(&cfg, "shebang", dir.path(), false, &args).unwrap();
+        let output = fs::read_to_string(dir.path().join("out.txt")).unwrap();
+        assert_eq!(output, "it's me\n");
+    }
+
+    #[test]
+    fn run_with_working_dir() {
+        let dir = TempDir::new().unwrap();
+        let subdir = dir.path().join("mysubdir");
+        fs::create_dir(&subdir).unwrap();
+        let out = dir.path().join("pwd_out.txt");
+        let cfg = load_config(
+            &dir,
+            &format!(
+                r#"
+[tasks.wd]
+run = "pwd > {}"
+dir = "mysubdir"
+"#,
+                out.display()
+            ),
+        );
+        runner::run_task(&cfg, "wd", dir.path(), false).unwrap();
+        let content = fs::read_to_string(&out).unwrap();
+        assert!(content.trim().ends_with("mysubdir"));
+    }
+
+    #[test]
+    fn run_with_environment() {
+        let dir = TempDir::new().unwrap();
+        let out = dir.path().join("env_out.txt");
+        let cfg = load_config(
+            &dir,
+            &format!(
+                r#"
+[tasks.show]
+run = "echo $GREETING-$NAME > {}"
+environment = {{ GREETING = "hello", NAME = "plz" }}
+"#,
+                out.display()
+            ),
+        );
+        runner::run_task(&cfg, "show", dir.path(), false).unwrap();
+        assert_eq!(fs::read_to_string(&out).unwrap().trim(), "hello-plz");
+    }
+
+    #[test]
+    fn run_with_env_file() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join(".env"), "FROM_FILE=file\nOVERRIDE=file\n").unwrap();
+        let out = dir.path().join("env_file_out.txt");
+        let cfg = load_config(
+            &dir,
+            &format!(
+                r#"
+[tasks.show]
+run = "echo $FROM_FILE-$OVERRIDE > {}"
+env_file = ".env"
+environment = {{ OVERRIDE = "task" }}
+"#,
+                out.display()
+            ),
+        );
+        runner::run_task(&cfg, "show", dir.path(), false).unwrap();
+        assert_eq!(fs::read_to_string(&out).unwrap().trim(), "file-task");
+    }
+
+    #[test]
+    fn run_with_missing_env_file_errors() {
+        let dir = TempDir::new().unwrap();
+        let cfg = load_config(
+            &dir,
+            r#"
+[tasks.show]
+run = "echo hi"
+env_file = "missing.env"
+"#,
+        );
+        let err = runner::run_task(&cfg, "show", dir.path(), false).unwrap_err();
+        assert!(err.to_string().contains("missing.env"), "{err}");
+    }
+
+    #[test]
+    fn unavailable_placeholders_only_fail_tasks_using_them() {
+        let dir = TempDir::new().unwrap();
+        let cfg = load_config(
+            &dir,
+            r#"
+[tasks.test]
+run = "touch tested"
+
+[tasks.deploy]
+run = "deploy --token {{env.PLZ_TEST_UNSET_TOKEN}}"
+
+[tasks.release]
+depends = ["deploy"]
+run = "touch released"
+
+[tasks.where]
+run = "echo {{plz.git_root}}"
+"#,
+        );
+        runner::run_task(&cfg, "test", dir.path(), false).unwrap();
+        assert!(dir.path().join("tested").exists());
+
+        for task in ["deploy", "release"] {
+            let err = runner::run_task(&cfg, task, dir.path(), false).unwrap_err();
+            assert!(
+                err.to_string()
+                    .contains("Environment variable \"PLZ_TEST_UNSET_TOKEN\" is not set"),
+                "{err}"
+            );
+        }
+        assert!(!dir.path().join("released").exists());
+
+        let err = runner::run_task(&cfg, "where", dir.path(), false).unwrap_err();
+        assert!(
+            err.to_string().contains("outside a git repository"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn run_parallel_with_environment() {
+        let dir = TempDir::new().unwrap();
+        let out = dir.path().join("par_env.txt");
+        let cfg = load_config(
+            &dir,
+            &format!(
+                r#"
+[tasks.par]
+run_parallel = ["echo $MODE > {}", "true"]
+environment = {{ MODE = "parallel" }}
+"#,
+                out.display()
+            ),
+        );
+        runner::run_task(&cfg, "par", dir.path(), false).unwrap();
+        assert_eq!(fs::read_to_string(&out).unwrap().trim(), "parallel");
+    }
+
+    #[test]
+    fn run_task_in_all_workspace_members() {
+        let dir = TempDir::new().unwrap();
+        for member in ["web", "api", "docs"] {
+            let member_dir = dir.path().join("packages").join(member);
+            fs::create_dir_all(&member_dir).unwrap();
+            let body = if member == "docs" {
+                "[tasks.serve]\nrun = \"true\"\n"
+            } else {
+                "[tasks.build]\nrun = \"pwd > built.txt\"\n"
+            };
+            fs::write(member_dir.join("plz.toml"), body).unwrap();
+        }
+        let cfg = load_config(
+            &dir,
+            r#"
+[workspace]
+members = ["packages/*"]
+"#,
+        );
+        runner::run_task_in_members(&cfg, "build", dir.path(), false).unwrap();
+        assert!(dir.path().join("packages/web/built.txt").exists());
+        assert!(dir.path().join("packages/api/built.txt").exists());
+        assert!(!dir.path().join("packages/docs/built.txt").exists());
+
+        let err = runner::run_task_in_members(&cfg, "nope", dir.path(), false).unwrap_err();
+        assert!(err.to_string().contains("No workspace member"), "{err}");
+    }
+
+    #[test]
+    fn workspace_member_vars_run_in_member_dir() {
+        let dir = TempDir::new().unwrap();
+        for member in ["web", "api"] {
+            let member_dir = dir.path().join("packages").join(member);
+            fs::create_dir_all(&member_dir).unwrap();
+            fs::write(member_dir.join("VERSION"), format!("{member}-1.0\n")).unwrap();
+            fs::write(
+                member_dir.join("plz.toml"),
+                r#"
+[vars]
+here = { sh = "pwd" }
+version = { file = "VERSION" }
+
+[tasks.build]
+run = "echo {{here}} {{version}} > out.txt"
+"#,
+            )
+            .unwrap();
+        }
+        let cfg = load_config(
+            &dir,
+            r#"
+[workspace]
+members = ["packages/*"]
+"#,
+        );
+        runner::run_task_in_members(&cfg, "build", dir.path(), false).unwrap();
+        for member in ["web", "api"] {
+            let out = dir.path().join("packages").join(member).join("out.txt");
+            let out = fs::read_to_string(out).unwrap();
+            let (here, version) = out.trim().split_once(' ').unwrap();
+            assert!(here.ends_with(&format!("packages/{member}")), "{out}");
+            assert_eq!(version, format!("{member}-1.0"));
+        }
+    }
+
+    #[test]
+    fn run_with_tool_env_uv() {
+        let dir = TempDir::new().unwrap();
+        let cfg = load_config(
+            &dir,
+            r#"
+[tasks.uv_test]
+run = "echo hello"
+env = "uv"
+"#,
+        );
+        assert_eq!(cfg.tasks["uv_test"].tool_env.as_deref(), Some("uv"));
+        let _ = runner::run_task(&cfg, "uv_test", dir.path(), false);
+    }
+
+    #[test]
+    fn run_with_tool_env_pnpm() {
+        let dir = TempDir::new().unwrap();
+        let cfg = load_config(
+            &dir,
+            r#"
+[tasks.pnpm_test]
+run = "echo hello"
+env = "pnpm"
+"#,
+        );
+        assert_eq!(cfg.tasks["pnpm_test"].tool_env.as_deref(), Some("pnpm"));
+        let _ = runner::run_task(&cfg, "pnpm_test", dir.path(), false);
+    }
+
+    #[test]
+    fn run_with_tool_env_npm() {
+        let dir = TempDir::new().unwrap();
+        let cfg = load_config(
+            &dir,
+            r#"
+[tasks.npm_test]
+run = "echo hello"
+env = "npm"
+"#,
+        );
+        assert_eq!(cfg.tasks["npm_test"].tool_env.as_deref(), Some("npm"));
+        let _ = runner::run_task(&cfg, "npm_test", dir.path(), false);
+    }
+
+    #[test]
+    fn run_task_reference() {
+        let dir = TempDir::new().unwrap();
+        let marker = dir.path().join("ref_marker.txt");
+        let cfg = load_config(
+            &dir,
+            &format!(
+                r#"
+[tasks.caller]
+run = "plz:target"
+
+[tasks.target]
+run = "touch {}"
+"#,
+                marker.display()
+            ),
+        );
+        runner::run_task(&cfg, "caller", dir.path(), false).unwrap();
+        assert!(marker.exists());
+    }
+
+    #[test]
+    fn run_unknown_task_errors() {
+        let dir = TempDir::new().unwrap();
+        let cfg = load_config(
+            &dir,
+            r#"
+[tasks.hello]
+run = "echo hello"
+"#,
+        );
+        let err = runner::run_task(&cfg, "nonexistent", dir.path(), false);
+        assert!(err.is_err());
+        assert!(err.unwrap_err().to_string().contains("isn't a task"));
+    }
+
+    #[test]
+    fn run_serial_with_task_ref() {
+        let dir = TempDir::new().unwrap();
+        let m1 = dir.path().join("s1.txt");
+        let m2 = dir.path().join("s2.txt");
+        let cfg = load_config(
+            &dir,
+            &format!(
+                r#"
+[tasks.main]
+run_serial = ["touch {}", "plz:helper"]
+
+[tasks.helper]
+run = "touch {}"
+"#,
+                m1.display(),
+                m2.display()
+            ),
+        );
+        runner::run_task(&cfg, "main", dir.path(), false).unwrap();
+        assert!(m1.exists());
+        assert!(m2.exists());
+    }
+
+    #[test]
+    fn task_refs_resolve_when_referencer_has_tool_env() {
+        // Regression: env wrapping ran before ref resolution, so "plz:inner"
+        // became "pnpm exec plz:inner" and executed as a shell command
+        let dir = TempDir::new().unwrap();
+        let single = dir.path().join("single.txt");
+        let serial = dir.path().join("serial.txt");
+        let parallel = dir.path().join("parallel.txt");
+        let cfg = load_config(
+            &dir,
+            &format!(
+                r#"
+[tasks.single]
+run = "plz:touch_single"
+env = "pnpm"
+
+[tasks.serial]
+run_serial = ["plz:touch_serial"]
+env = "pnpm"
+
+[tasks.parallel]
+run_parallel = ["plz:touch_parallel"]
+env = "pnpm"
+
+[tasks.touch_single]
+run = "touch {}"
+
+[tasks.touch_serial]
+run = "touch {}"
+
+[tasks.touch_parallel]
+run = "touch {}"
+"#,
+                single.display(),
+                serial.display(),
+                parallel.display()
+            ),
+        );
+        runner::run_task(&cfg, "single", dir.path(), false).unwrap();
+        runner::run_task(&cfg, "serial", dir.path(), false).unwrap();
+        runner::run_task(&cfg, "parallel", dir.path(), false).unwrap();
+        assert!(single.exists());
+        assert!(serial.exists());
+        assert!(parallel.exists());
+    }
+
+    #[test]
+    fn run_group_task_simple() {
+        let dir = TempDir::new().unwrap();
+        let marker = dir.path().join("group_marker.txt");
+        let cfg = load_config(
+            &dir,
+            &format!(
+                r#"
+[tasks.hello]
+run = "echo hello"
+
+[taskgroup.rust.test]
+run = "touch {}"
+"#,
+                marker.display()
+            ),
+        );
+        runner::run_group_task(&cfg, "rust", "test", dir.path(), false).unwrap();
+        assert!(marker.exists());
+    }
+
+    #[test]
+    fn run_group_task_with_dir() {
+        let dir = TempDir::new().unwrap();
+        let subdir = dir.path().join("backend");
+        fs::create_dir(&subdir).unwrap();
+        let out = dir.path().join("grp_pwd.txt");
+        let cfg = load_config(
+            &dir,
+            &format!(
+                r#"
+[tasks.hello]
+run = "echo hello"
+
+[taskgroup.rust.extends]
+dir = "backend"
+
+[taskgroup.rust.wd]
+run = "pwd > {}"
+"#,
+                out.display()
+            ),
+        );
+        runner::run_group_task(&cfg, "rust", "wd", dir.path(), false).unwrap();
+        let content = fs::read_to_string(&out).unwrap();
+        assert!(content.trim().ends_with("backend"));
+    }
+
+    #[test]
+    fn run_group_task_unknown_errors() {
+        let dir = TempDir::new().unwrap();
+        let cfg = load_config(
+            &dir,
+            r#"
+[tasks.hello]
+run = "echo hello"
+
+[taskgroup.rust.test]
+run = "cargo test"
+"#,
+        );
+        let err = runner::run_group_task(&cfg, "rust", "nonexistent", dir.path(), false);
+        assert!(err.is_err());
+        assert!(err.unwrap_err().to_string().contains("isn't a task"));
+    }
+
+    #[test]
+    fn run_group_task_with_extra_args() {
+        let dir = TempDir::new().unwrap();
+        let out = dir.path().join("args_out.txt");
+        let cfg = load_config(
+            &dir,
+            &format!(
+                r#"
+[tasks.hello]
+run = "echo hello"
+
+[taskgroup.rust.echo]
+run = "echo > {}"
+"#,
+                out.display()
+            ),
+        );
+        let args = vec!["--nocapture".to_string()];
+        runner::run_group_task_with_args(&cfg, "rust", "echo", dir.path(), false, &args).unwrap();
+        assert!(out.exists());
+    }
+
+    #[test]
+    fn run_group_task_ref_in_serial() {
+        let dir = TempDir::new().unwrap();
+        let m1 = dir.path().join("gs1.txt");
+        let m2 = dir.path().join("gs2.txt");
+        let cfg = load_config(
+            &dir,
+            &format!(
+                r#"
+[tasks.main]
+run_serial = ["touch {}", "plz:grp:helper"]
+
+[taskgroup.grp.helper]
+run = "touch {}"
+"#,
+                m1.display(),
+                m2.display()
+            ),
+        );
+        runner::run_task(&cfg, "main", dir.path(), false).unwrap();
+        assert!(m1.exists());
+        assert!(m2.exists());
+    }
+
+    #[test]
+    fn run_group_task_ref_in_parallel() {
+        let dir = TempDir::new().unwrap();
+        let m1 = dir.path().join("gp1.txt");
+        let m2 = dir.path().join("gp2.txt");
+        let cfg = load_config(
+            &dir,
+            &format!(
+                r#"
+[tasks.main]
+run_parallel = ["touch {}", "plz:grp:helper"]
+
+[taskgroup.grp.helper]
+run = "touch {}"
+"#,
+                m1.display(),
+                m2.display()
+            ),
+        );
+        runner::run_task(&cfg, "main", dir.path(), false).unwrap();
+        assert!(m1.exists());
+        assert!(m2.exists());
+    }
+
+    #[test]
+    fn depends_runs_before_task() {
+        let dir = TempDir::new().unwrap();
+        let marker = dir.path().join("dep_marker.txt");
+        let out = dir.path().join("main_out.txt");
+        let cfg = load_config(
+            &dir,
+            &format!(
+                r#"
+[tasks.setup]
+run = "touch {}"
+
+[tasks.main]
+depends = "setup"
+run = "test -f {} && touch {}"
+"#,
+                marker.display(),
+                marker.display(),
+                out.display()
+            ),
+        );
+        runner::run_task(&cfg, "main", dir.path(), false).unwrap();
+        assert!(marker.exists());
+        assert!(out.exists());
+    }
+
+    #[test]
+    fn depends_failure_prevents_task() {
+        let dir = TempDir::new().unwrap();
+        let marker = dir.path().join("should_not_exist.txt");
+        let cfg = load_config(
+            &dir,
+            &format!(
+                r#"
+[tasks.bad]
+run = "false"
+
+[tasks.main]
+depends = "bad"
+run = "touch {}"
+"#,
+                marker.display()
+            ),
+        );
+        assert!(runner::run_task(&cfg, "main", dir.path(), false).is_err());
+        assert!(!marker.exists());
+    }
+
+    #[test]
+    fn depends_multiple_run_in_order() {
+        let dir = TempDir::new().unwrap();
+        let m1 = dir.path().join("dep1.txt");
+        let m2 = dir.path().join("dep2.txt");
+        let out = dir.path().join("main_out.txt");
+        let cfg = load_config(
+            &dir,
+            &format!(
+                r#"
+[tasks.dep1]
+run = "touch {}"
+
+[tasks.dep2]
+run = "touch {}"
+
+[tasks.main]
+depends = ["dep1", "dep2"]
+run = "test -f {} && test -f {} && touch {}"
+"#,
+                m1.display(),
+                m2.display(),
+                m1.display(),
+                m2.display(),
+                out.display()
+            ),
+        );
+        runner::run_task(&cfg, "main", dir.path(), false).unwrap();
+        assert!(m1.exists());
+        assert!(m2.exists());
+        assert!(out.exists());
+    }
+
+    #[test]
+    fn depends_group_task() {
+        let dir = TempDir::new().unwrap();
+        let marker = dir.path().join("grp_dep.txt");
+        let out = dir.path().join("main_out.txt");
+        let cfg = load_config(
+            &dir,
+            &format!(
+                r#"
+[tasks.main]
+depends = ["ui.build"]
+run = "test -f {} && touch {}"
+
+[taskgroup.ui.build]
+run = "touch {}"
+"#,
+                marker.display(),
+                out.display(),
+                marker.display()
+            ),
+        );
+        runner::run_task(&cfg, "main", dir.path(), false).unwrap();
+        assert!(marker.exists());
+        assert!(out.exists());
+    }
+
+    #[test]
+    fn depends_shared_dep_runs_once() {
+        let dir = TempDir::new().unwrap();
+        let counter = dir.path().join("counter.txt");
+        // setup appends "x" to counter file; both a and b depend on setup
+        // If dedup works, counter should contain exactly one "x"
+        let cfg = load_config(
+            &dir,
+            &format!(
+                r#"
+[tasks.setup]
+run = "printf x >> {}"
+
+[tasks.a]
+depends = "setup"
+run = "echo a"
+
+[tasks.b]
+depends = "setup"
+run = "echo b"
+
+[tasks.all]
+run_serial = ["plz:a", "plz:b"]
+"#,
+                counter.display()
+            ),
+        );
+        runner::run_task(&cfg, "all", dir.path(), false).unwrap();
+        let content = fs::read_to_string(&counter).unwrap();
+        assert_eq!(content, "x", "setup should run only once, got: {content}");
+    }
+
+    #[test]
+    fn run_parallel_with_task_ref() {
+        let dir = TempDir::new().unwrap();
+        let m1 = dir.path().join("p1.txt");
+        let m2 = dir.path().join("p2.txt");
+        let cfg = load_config(
+            &dir,
+            &format!(
+                r#"
+[tasks.main]
+run_parallel = ["touch {}", "plz:helper"]
+
+[tasks.helper]
+run = "touch {}"
+"#,
+                m1.display(),
+                m2.display()
+            ),
+        );
+        runner::run_task(&cfg, "main", dir.path(), false).unwrap();
+        assert!(m1.exists());
+        assert!(m2.exists());
+    }
+
+    #[test]
+    fn run_task_with_vars_substitution() {
+        let dir = TempDir::new().unwrap();
+        let marker = dir.path().join("vars_marker.txt");
+        let cfg = load_config(
+            &dir,
+            &format!(
+                r#"
+[vars]
+marker_path = "{}"
+
+[tasks.test]
+run = "touch {{{{marker_path}}}}"
+"#,
+                marker.display()
+            ),
+        );
+        runner::run_task(&cfg, "test", dir.path(), false).unwrap();
+        assert!(marker.exists());
+    }
+
+    #[test]
+    fn run_task_with_dynamic_var() {
+        let dir = TempDir::new().unwrap();
+        let cfg = load_config(
+            &dir,
+            r#"
+[vars]
+greeting = { sh = "echo hello" }
+
+[tasks.test]
+run = "echo {{greeting}} > out.txt"
+environment = { GREETING = "{{greeting}}" }
+
+[tasks.env]
+run = "echo $GREETING > env.txt"
+environment = { GREETING = "{{greeting}}" }
+"#,
+        );
+        runner::run_task(&cfg, "test", dir.path(), false).unwrap();
+        runner::run_task(&cfg, "env", dir.path(), false).unwrap();
+        let out = fs::read_to_string(dir.path().join("out.txt")).unwrap();
+        assert_eq!(out.trim(), "hello");
+        let env = fs::read_to_string(dir.path().join("env.txt")).unwrap();
+        assert_eq!(env.trim(), "hello");
+    }
+
+    #[test]
+    fn dynamic_var_evaluated_lazily_and_once() {
+        let dir = TempDir::new().unwrap();
+        let cfg = load_config(
+            &dir,
+            r#"
+[vars]
+count = { sh = "echo x >> evals.txt; wc -l < evals.txt" }
+unused = { sh = "touch unused.txt" }
+
+[tasks.a]
+run = "echo {{count}}"
+
+[tasks.b]
+run_serial = ["echo {{count}}", "echo {{count}}"]
+
+[tasks.other]
+run = "echo {{unused}}"
+"#,
+        );
+        runner::run_task(&cfg, "a", dir.path(), false).unwrap();
+        runner::run_task(&cfg, "b", dir.path(), false).unwrap();
+        let evals = fs::read_to_string(dir.path().join("evals.txt")).unwrap();
+        assert_eq!(evals.lines().count(), 1);
+        assert!(!dir.path().join("unused.txt").exists());
+    }
+
+    #[test]
+    fn dynamic_vars_evaluate_in_parallel() {
+        let dir = TempDir::new().unwrap();
+        let cfg = load_config(
+            &dir,
+            r#"
+[vars]
+waits = { sh = "while [ ! -f ready.txt ]; do sleep 0.05; done; echo waited" }
+ready = { sh = "touch ready.txt; echo ready" }
+
+[tasks.a]
+run = "echo {{waits}} > a.txt"
+timeout = "5s"
+
+[tasks.b]
+run = "echo {{ready}} > b.txt"
+timeout = "5s"
+
+[tasks.both]
+run_parallel = ["plz:a", "plz:b"]
+"#,
+        );
+        // `waits` only finishes once `ready` has run, whichever starts first
+        runner::run_task(&cfg, "both", dir.path(), false).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.path().join("a.txt")).unwrap(),
+            "waited\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("b.txt")).unwrap(),
+            "ready\n"
+        );
+    }
+
+    #[test]
+    fn dynamic_var_failure_names_the_var() {
+        let dir = TempDir::new().unwrap();
+        let cfg = load_config(
+            &dir,
+            r#"
+[vars]
+version = { sh = "echo nope >&2; exit 3" }
+
+[tasks.release]
+run = "echo {{version}}"
+"#,
+        );
+        let err = runner::run_task(&cfg, "release", dir.path(), false).unwrap_err();
+        let msg = format!("{err:#}");
+        assert!(
+            msg.contains("Failed to evaluate var \"version\""),
+            "got: {msg}"
+        );
+        assert!(msg.contains("exited with code 3"), "got: {msg}");
+        assert!(msg.contains("nope"), "got: {msg}");
+    }
+
+    #[test]
+    fn dynamic_vars_in_dir_and_fail_hook() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("build")).unwrap();
+        let mut cfg = load_config(
+            &dir,
+            r#"
+[vars]
+out = { sh = "echo build" }
+sha = { sh = "echo abc123" }
+bad = { sh = "exit 2" }
+
+[tasks.inside]
+run = "touch here.txt"
+dir = "{{out}}"
+
+[tasks.broken]
+run = "exit 1"
+fail_hook = { message = "failed at {{sha}}" }
+
+[tasks.check]
+run_parallel = ["plz:broken"]
+
+[tasks.bad_dir]
+run = "true"
+dir = "{{bad}}"
+
+[tasks.bad_hook]
+run = "exit 1"
+fail_hook = { message = "failed at {{bad}}" }
+"#,
+        );
+        let messages = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = messages.clone();
+        cfg.events = runner::Events(Some(std::sync::Arc::new(move |event: &runner::Event| {
+            if let runner::Event::FailHookMessage { message } = event {
+                seen.lock().unwrap().push(message.clone());
+            }
+        })));
+
+        runner::run_task(&cfg, "inside", dir.path(), false).unwrap();
+        assert!(dir.path().join("build/here.txt").exists());
+
+        runner::run_task(&cfg, "broken", dir.path(), false).unwrap_err();
+        runner::run_task(&cfg, "check", dir.path(), false).unwrap_err();
+        assert_eq!(
+            *messages.lock().unwrap(),
+            vec!["failed at abc123", "failed at abc123"]
+        );
+
+        for task in ["bad_dir", "bad_hook"] {
+            let err = runner::run_task(&cfg, task, dir.path(), false).unwrap_err();
+            let msg = format!("{err:#}");
+            assert!(msg.contains("Failed to evaluate var \"bad\""), "got: {msg}");
+        }
+    }
+
+    #[test]
+    fn vars_referencing_dynamic_vars_in_dir_and_fail_hook() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("out/linux")).unwrap();
+        let mut cfg = load_config(
+            &dir,
+            r#"
+[vars]
+target = { sh = "echo linux" }
+out = "out/{{target}}"
+sha = { sh = "echo abc123" }
+release = "v1-{{sha}}"
+
+[tasks.inside]
+run = "touch here.txt"
+dir = "{{out}}"
+
+[tasks.broken]
+run = "exit 1"
+fail_hook = { message = "{{release}} failed in {{plz.task}}" }
+"#,
+        );
+        let messages = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = messages.clone();
+        cfg.events = runner::Events(Some(std::sync::Arc::new(move |event: &runner::Event| {
+            if let runner::Event::FailHookMessage { message } = event {
+                seen.lock().unwrap().push(message.clone());
+            }
+        })));
+
+        runner::run_task(&cfg, "inside", dir.path(), false).unwrap();
+        assert!(dir.path().join("out/linux/here.txt").exists());
+
+        runner::run_task(&cfg, "broken", dir.path(), false).unwrap_err();
+        assert_eq!(
+            *messages.lock().unwrap(),
+            vec!["v1-abc123 failed in broken"]
+        );
+    }
+
+    #[test]
+    fn secret_vars_read_from_file_and_masked() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join(".token"), "s3cr3t-token\n").unwrap();
+        let cfg = load_config(
+            &dir,
+            r#"
+[vars]
+token = { file = ".token", secret = true }
+
+[tasks.upload]
+run = "echo {{token}} > out.txt && exit 4"
+"#,
+        );
+        let err = runner::run_task(&cfg, "upload", dir.path(), false).unwrap_err();
+        let msg = format!("{err:#}");
+        assert!(msg.contains("exit code 4"), "got: {msg}");
+        assert!(msg.contains("echo *** > out.txt"), "got: {msg}");
+        assert!(!msg.contains("s3cr3t"), "got: {msg}");
+        let out = fs::read_to_string(dir.path().join("out.txt")).unwrap();
+        assert_eq!(out.trim(), "s3cr3t-token");
+    }
+
+    #[test]
+    fn run_task_with_declared_args() {
+        let dir = TempDir::new().unwrap();
+        let cfg = load_config(
+            &dir,
+            r#"
+[tasks.deploy]
+args = [
+  { name = "env", choices = ["staging", "prod"], default = "staging" },
+  { name = "files", variadic = true },
+]
+run_serial = ["echo {{args.env}} > env.txt", "printf '%s\n' {{args.files}} > files.txt"]
+environment = { TARGET = "{{args.env}}" }
+"#,
+        );
+        let args = vec!["prod".to_string(), "a b".to_string(), "c".to_string()];
+        runner::run_task_with_args(&cfg, "deploy", dir.path(), false, &args).unwrap();
+        let env = fs::read_to_string(dir.path().join("env.txt")).unwrap();
+        assert_eq!(env.trim(), "prod");
+        let files = fs::read_to_string(dir.path().join("files.txt")).unwrap();
+        assert_eq!(files, "a b\nc\n");
+
+        runner::run_task(&cfg, "deploy", dir.path(), false).unwrap();
+        let env = fs::read_to_string(dir.path().join("env.txt")).unwrap();
+        assert_eq!(env.trim(), "staging");
+    }
+
+    #[test]
+    fn run_task_with_invalid_args() {
+        let dir = TempDir::new().unwrap();
+        let cfg = load_config(
+            &dir,
+            r#"
+[tasks.deploy]
+args = [{ name = "env", choices = ["staging", "prod"] }]
+run = "echo {{args.env}}"
+
+[tasks.multi]
+run_serial = ["echo a", "echo b"]
+"#,
+        );
+        let run = |task: &str, args: &[&str]| {
+            let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+            let err = runner::run_task_with_args(&cfg, task, dir.path(), false, &args);
+            format!("{:#}", err.unwrap_err())
+        };
+        let msg = run("deploy", &[]);
+        assert!(msg.contains("missing argument \"env\""), "got: {msg}");
+        assert!(msg.contains("plz deploy <env>"), "got: {msg}");
+        let msg = run("deploy", &["qa"]);
+        assert!(msg.contains("Choices: staging, prod"), "got: {msg}");
+        let msg = run("deploy", &["prod", "extra"]);
+        assert!(msg.contains("unexpected arguments: extra"), "got: {msg}");
+        let msg = run("multi", &["x"]);
+        assert!(msg.contains("runs more than one command"), "got: {msg}");
+    }
+
+    #[test]
+    fn run_task_on_other_platform_is_skipped() {
+        let dir = TempDir::new().unwrap();
+        let other = if std::env::consts::OS == "linux" {
+            "macos"
+        } else {
+            "linux"
+        };
+        let cfg = load_config(
+            &dir,
+            &format!(
+                r#"
+[tasks.build]
+depends = "mac"
+run = "touch built.txt"
+
+[tasks.mac]
+platforms = ["{other}"]
+run = "touch mac.txt"
+"#
+            ),
+        );
+        runner::run_task(&cfg, "build", dir.path(), false).unwrap();
+        assert!(dir.path().join("built.txt").exists());
+        assert!(!dir.path().join("mac.txt").exists());
+    }
+
+    #[test]
+    fn run_task_without_command_for_platform_fails() {
+        let dir = TempDir::new().unwrap();
+        let other = if std::env::consts::OS == "linux" {
+            "macos"
+        } else {
+            "linux"
+        };
+        let cfg = load_config(
+            &dir,
+            &format!(
+                r#"
+[tasks.open]
+run = {{ {other} = "touch opened.txt" }}
+
+[taskgroup.docs.open]
+run_parallel = {{ {other} = ["touch a.txt"] }}
+"#
+            ),
+        );
+        let os = std::env::consts::OS;
+        let err = runner::run_task(&cfg, "open", dir.path(), false).unwrap_err();
+        assert_eq!(
+            err.to_string().lines().next().unwrap(),
+            format!("Task \"open\" has no command for platform \"{os}\"")
+        );
+        let err = runner::run_group_task(&cfg, "docs", "open", dir.path(), false).unwrap_err();
+        assert!(
+            err.to_string().contains(&format!(
+                "Task \"docs:open\" has no command for platform \"{os}\""
+            )),
+            "{err}"
+        );
+        assert!(!dir.path().join("opened.txt").exists());
+    }
+
+    #[test]
+    fn run_if_conditions() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("present.txt"), "").unwrap();
+        let cfg = load_config(
+            &dir,
+            r#"
+[tasks.has_file]
+run_if = { file = "present.txt" }
+run = "touch has_file.txt"
+
+[tasks.missing_file]
+run_if = { file = "missing.txt" }
+run = "touch missing_file.txt"
+
+[tasks.env_unset]
+run_if = { env = "PLZ_TEST_UNSET_TOKEN" }
+run = "touch env_unset.txt"
+
+[tasks.env_and_sh]
+run_if = { env = "HOME", sh = "test -f present.txt" }
+run = "touch env_and_sh.txt"
+
+[tasks.sh_fails]
+run_if = { sh = "exit 1" }
+run = "touch sh_fails.txt"
+
+[tasks.skipped]
+skip_if = { file = "present.txt" }
+run = "touch skipped.txt"
+"#,
+        );
+        for name in [
+            "has_file",
+            "missing_file",
+            "env_unset",
+            "env_and_sh",
+            "sh_fails",
+            "skipped",
+        ] {
+            runner::run_task(&cfg, name, dir.path(), false).unwrap();
+        }
+        assert!(dir.path().join("has_file.txt").exists());
+        assert!(dir.path().join("env_and_sh.txt").exists());
+        assert!(!dir.path().join("missing_file.txt").exists());
+        assert!(!dir.path().join("env_unset.txt").exists());
+        assert!(!dir.path().join("sh_fails.txt").exists());
+        assert!(!dir.path().join("skipped.txt").exists());
+    }
+
+    #[test]
+    fn sh_conditions_use_task_env_and_shell() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join(".env"), "FROM_FILE=yes\n").unwrap();
+        let cfg = load_config(
+            &dir,
+            r#"
+[tasks.env]
+environment = { MODE = "ci" }
+run_if = { sh = "test \"$MODE\" = ci" }
+run = "touch env.txt"
+
+[tasks.env_file]
+env_file = ".env"
+run_if = { sh = "test \"$FROM_FILE\" = yes" }
+run = "touch env_file.txt"
+
+[tasks.direct]
+shell = false
+skip_if = { sh = "test -f 'present txt'" }
+run = "touch direct.txt"
+"#,
+        );
+        fs::write(dir.path().join("present txt"), "").unwrap();
+        for name in ["env", "env_file", "direct"] {
+            runner::run_task(&cfg, name, dir.path(), false).unwrap();
+        }
+        assert!(dir.path().join("env.txt").exists());
+        assert!(dir.path().join("env_file.txt").exists());
+        assert!(!dir.path().join("direct.txt").exists());
+    }
+
+    #[test]
+    fn run_if_ci() {
+        let dir = TempDir::new().unwrap();
+        let cfg = load_config(
+            &dir,
+            &format!(
+                r#"
+[tasks.here]
+run_if = {{ ci = {ci} }}
+run = "touch here.txt"
+
+[tasks.elsewhere]
+run_if = {{ ci = {not_ci} }}
+run = "touch elsewhere.txt"
+"#,
+                ci = is_ci::cached(),
+                not_ci = !is_ci::cached(),
+            ),
+        );
+        runner::run_task(&cfg, "here", dir.path(), false).unwrap();
+        runner::run_task(&cfg, "elsewhere", dir.path(), false).unwrap();
+        assert!(dir.path().join("here.txt").exists());
+        assert!(!dir.path().join("elsewhere.txt").exists());
+    }
+
+    #[test]
+    fn skipped_dependency_counts_as_satisfied() {
+        let dir = TempDir::new().unwrap();
+        let cfg = load_config(
+            &dir,
+            r#"
+[tasks.setup]
+run_if = { file = "missing.txt" }
+depends = "never"
+run = "touch setup.txt"
+
+[tasks.never]
+run = "touch never.txt"
+
+[tasks.build]
+depends = "setup"
+run = "touch build.txt"
+"#,
+        );
+        runner::run_task(&cfg, "build", dir.path(), false).unwrap();
+        assert!(dir.path().join("build.txt").exists());
+        assert!(!dir.path().join("setup.txt").exists());
+        assert!(!dir.path().join("never.txt").exists());
+    }
+}
+
+mod init_tests {
+    use super::*;
+    use plzplz::init;
+    use plzplz::templates;
+
+    #[test]
+    fn detect_environment_rust() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("Cargo.toml"), "[package]").unwrap();
+        let envs = templates::load_environments();
+        let detected = templates::detect_environments(dir.path(), &envs);
+        assert!(detected.contains(&"rust".to_string()));
+    }
+
+    #[test]
+    fn detect_environment_pnpm() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("pnpm-lock.yaml"), "").unwrap();
+        let envs = templates::load_environments();
+        let detected = templates::detect_environments(dir.path(), &envs);
+        assert!(detected.contains(&"pnpm".to_string()));
+    }
+
+    #[test]
+    fn detect_environment_npm() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("package-lock.json"), "{}").unwrap();
+        let envs = templates::load_environments();
+        let detected = templates::detect_environments(dir.path(), &envs);
+        assert!(detected.contains(&"npm".to_string()));
+    }
+
+    #[test]
+    fn detect_environment_npm_from_package_json() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("package.json"), "{}").unwrap();
+        let envs = templates::load_environments();
+        let detected = templates::detect_environments(dir.path(), &envs);
+        assert!(detected.contains(&"npm".to_string()));
+    }
+
+    #[test]
+    fn ignore_local_config_appends_to_gitignore() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join(".gitignore"), "target").unwrap();
+        assert!(!init::local_config_ignored(dir.path()));
+        init::ignore_local_config(dir.path()).unwrap();
+        assert!(init::local_config_ignored(dir.path()));
+        let content = fs::read_to_string(dir.path().join(".gitignore")).unwrap();
+        assert_eq!(content, "target\nplz.local.toml\n.plz.local.toml\n");
+    }
+
+    #[test]
+    fn detect_environment_uv() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("uv.lock"), "").unwrap();
+        let envs = templates::load_environments();
+        let detected = templates::detect_environments(dir.path(), &envs);
+        assert!(detected.contains(&"uv".to_string()));
+    }
 
     #[test]
     fn detect_environment_multiple() {
@@ -1939,106 +4610,345 @@ run_serial = ["cargo fmt", "cargo clippy --fix --allow-dirty"]
         let (_, tasks) = init::parse_default(toml).unwrap();
         let result = init::convert_to_taskgroup(toml, "rust", &tasks, "rust");
         assert!(
-            result.contains("plz rust fix"),
-            "should update plz references: {result}"
+            result.contains("plz rust fix"),
+            "should update plz references: {result}"
+        );
+    }
+
+    #[test]
+    fn embedded_templates_parse_correctly() {
+        let all = templates::load_templates(None);
+        for t in &all {
+            let stripped = templates::strip_template_section(&t.content);
+            let parsed = init::parse_default(&stripped);
+            assert!(parsed.is_some(), "Template {} failed to parse", t.name);
+            let (_, tasks) = parsed.unwrap();
+            assert!(!tasks.is_empty(), "Template {} has no tasks", t.name);
+        }
+    }
+
+    #[test]
+    fn user_template_listed_before_embedded_for_same_env() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("templates")).unwrap();
+        let user_template = dir.path().join("templates").join("user.plz.toml");
+        fs::write(
+            &user_template,
+            r#"[template]
+description = "My custom rust"
+env = "rust"
+
+[tasks.mybuild]
+run = "cargo build --release"
+"#,
+        )
+        .unwrap();
+
+        let all = templates::load_templates(Some(dir.path()));
+        let names: Vec<&str> = all.iter().map(|t| t.name.as_str()).collect();
+        let user_pos = names.iter().position(|n| *n == "user").unwrap();
+        let rust_pos = names.iter().position(|n| *n == "rust").unwrap();
+        assert!(
+            user_pos < rust_pos,
+            "user template should appear before embedded rust: {names:?}"
+        );
+        assert!(all[user_pos].is_user);
+        assert!(!all[rust_pos].is_user);
+    }
+
+    #[test]
+    fn user_template_appended_when_env_not_in_embedded() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("templates")).unwrap();
+        let user_template = dir.path().join("templates").join("user.plz.toml");
+        fs::write(
+            &user_template,
+            r#"[template]
+description = "My custom env"
+env = "custom"
+
+[tasks.hello]
+run = "echo hello"
+"#,
+        )
+        .unwrap();
+
+        let all = templates::load_templates(Some(dir.path()));
+        let last = all.last().unwrap();
+        assert_eq!(last.name, "user");
+        assert_eq!(last.env.as_deref(), Some("custom"));
+        assert!(last.is_user);
+    }
+
+    #[test]
+    fn embedded_templates_not_marked_as_user() {
+        let all = templates::load_templates(None);
+        for t in &all {
+            assert!(
+                !t.is_user,
+                "embedded template {} should not be user",
+                t.name
+            );
+        }
+    }
+}
+
+mod hooks_tests {
+    use super::*;
+    use plzplz::config;
+    use plzplz::hooks;
+
+    fn write_config(dir: &TempDir, content: &str) -> std::path::PathBuf {
+        let path = dir.path().join("plz.toml");
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn init_git_repo(dir: &TempDir) {
+        fs::create_dir_all(dir.path().join(".git/hooks")).unwrap();
+    }
+
+    #[test]
+    fn parse_git_hook_field() {
+        let dir = TempDir::new().unwrap();
+        let path = write_config(
+            &dir,
+            r#"
+[tasks.lint]
+run = "cargo clippy"
+git_hook = "pre-commit"
+"#,
+        );
+        let cfg = config::load(&path).unwrap();
+        assert_eq!(cfg.tasks["lint"].git_hook.as_deref(), Some("pre-commit"));
+    }
+
+    #[test]
+    fn reject_invalid_git_hook() {
+        let dir = TempDir::new().unwrap();
+        let path = write_config(
+            &dir,
+            r#"
+[tasks.lint]
+run = "cargo clippy"
+git_hook = "not-a-real-hook"
+"#,
+        );
+        let err = config::load(&path).unwrap_err();
+        assert!(err.to_string().contains("invalid git_hook"), "got: {err}");
+    }
+
+    #[test]
+    fn tasks_by_stage_groups_correctly() {
+        let dir = TempDir::new().unwrap();
+        let path = write_config(
+            &dir,
+            r#"
+[tasks.lint]
+run = "cargo clippy"
+git_hook = "pre-commit"
+
+[tasks.fmt]
+run = "cargo fmt --check"
+git_hook = "pre-commit"
+
+[tasks.test]
+run = "cargo test"
+git_hook = "pre-push"
+
+[tasks.build]
+run = "cargo build"
+"#,
+        );
+        let cfg = config::load(&path).unwrap();
+        let stages = hooks::tasks_by_stage(&cfg);
+        assert_eq!(stages.len(), 2);
+        assert_eq!(stages["pre-commit"].len(), 2);
+        assert!(stages["pre-commit"].contains(&"lint".to_string()));
+        assert!(stages["pre-commit"].contains(&"fmt".to_string()));
+        assert_eq!(stages["pre-push"], vec!["test"]);
+    }
+
+    #[test]
+    fn install_creates_hook_scripts() {
+        let dir = TempDir::new().unwrap();
+        init_git_repo(&dir);
+        let path = write_config(
+            &dir,
+            r#"
+[tasks.lint]
+run = "cargo clippy"
+git_hook = "pre-commit"
+
+[tasks.test]
+run = "cargo test"
+git_hook = "pre-push"
+"#,
+        );
+        let cfg = config::load(&path).unwrap();
+        hooks::install(&cfg, dir.path(), false, false).unwrap();
+
+        let pre_commit = dir.path().join(".git/hooks/pre-commit");
+        let pre_push = dir.path().join(".git/hooks/pre-push");
+        assert!(pre_commit.exists());
+        assert!(pre_push.exists());
+
+        let content = fs::read_to_string(&pre_commit).unwrap();
+        assert!(content.contains("plz:managed"));
+        assert!(content.contains("hooks run pre-commit"));
+
+        let content = fs::read_to_string(&pre_push).unwrap();
+        assert!(content.contains("hooks run pre-push"));
+    }
+
+    #[test]
+    fn install_skips_non_managed_hooks() {
+        let dir = TempDir::new().unwrap();
+        init_git_repo(&dir);
+
+        let existing_hook = dir.path().join(".git/hooks/pre-commit");
+        fs::write(&existing_hook, "#!/bin/sh\necho my custom hook\n").unwrap();
+
+        let path = write_config(
+            &dir,
+            r#"
+[tasks.lint]
+run = "cargo clippy"
+git_hook = "pre-commit"
+"#,
+        );
+        let cfg = config::load(&path).unwrap();
+        hooks::install(&cfg, dir.path(), false, false).unwrap();
+
+        let content = fs::read_to_string(&existing_hook).unwrap();
+        assert!(
+            content.contains("my custom hook"),
+            "Should not overwrite user hook"
+        );
+        assert!(!content.contains("plz:managed"));
+    }
+
+    #[test]
+    fn install_force_overwrites_non_managed_hooks() {
+        let dir = TempDir::new().unwrap();
+        init_git_repo(&dir);
+
+        let existing_hook = dir.path().join(".git/hooks/pre-commit");
+        fs::write(&existing_hook, "#!/bin/sh\necho my custom hook\n").unwrap();
+
+        let path = write_config(
+            &dir,
+            r#"
+[tasks.lint]
+run = "cargo clippy"
+git_hook = "pre-commit"
+"#,
+        );
+        let cfg = config::load(&path).unwrap();
+        hooks::install(&cfg, dir.path(), true, false).unwrap();
+
+        let content = fs::read_to_string(&existing_hook).unwrap();
+        assert!(
+            content.contains("plz:managed"),
+            "Should overwrite user hook with --force"
         );
+        assert!(!content.contains("my custom hook"));
     }
 
     #[test]
-    fn embedded_templates_parse_correctly() {
-        let all = templates::load_templates(None);
-        for t in &all {
-            let stripped = templates::strip_template_section(&t.content);
-            let parsed = init::parse_default(&stripped);
-            assert!(parsed.is_some(), "Template {} failed to parse", t.name);
-            let (_, tasks) = parsed.unwrap();
-            assert!(!tasks.is_empty(), "Template {} has no tasks", t.name);
-        }
-    }
-
-    #[test]
-    fn user_template_listed_before_embedded_for_same_env() {
+    fn install_overwrites_managed_hooks() {
         let dir = TempDir::new().unwrap();
-        fs::create_dir_all(dir.path().join("templates")).unwrap();
-        let user_template = dir.path().join("templates").join("user.plz.toml");
-        fs::write(
-            &user_template,
-            r#"[template]
-description = "My custom rust"
-env = "rust"
+        init_git_repo(&dir);
 
-[tasks.mybuild]
-run = "cargo build --release"
-"#,
+        let hook_path = dir.path().join(".git/hooks/pre-commit");
+        fs::write(
+            &hook_path,
+            "#!/bin/sh\n# plz:managed - do not edit\nplz hooks run pre-commit\n",
         )
         .unwrap();
 
-        let all = templates::load_templates(Some(dir.path()));
-        let names: Vec<&str> = all.iter().map(|t| t.name.as_str()).collect();
-        let user_pos = names.iter().position(|n| *n == "user").unwrap();
-        let rust_pos = names.iter().position(|n| *n == "rust").unwrap();
-        assert!(
-            user_pos < rust_pos,
-            "user template should appear before embedded rust: {names:?}"
+        let path = write_config(
+            &dir,
+            r#"
+[tasks.lint]
+run = "cargo clippy"
+git_hook = "pre-commit"
+"#,
         );
-        assert!(all[user_pos].is_user);
-        assert!(!all[rust_pos].is_user);
+        let cfg = config::load(&path).unwrap();
+        hooks::install(&cfg, dir.path(), false, false).unwrap();
+
+        let content = fs::read_to_string(&hook_path).unwrap();
+        assert!(content.contains("plz:managed"));
     }
 
     #[test]
-    fn user_template_appended_when_env_not_in_embedded() {
+    fn uninstall_removes_managed_hooks() {
         let dir = TempDir::new().unwrap();
-        fs::create_dir_all(dir.path().join("templates")).unwrap();
-        let user_template = dir.path().join("templates").join("user.plz.toml");
-        fs::write(
-            &user_template,
-            r#"[template]
-description = "My custom env"
-env = "custom"
+        init_git_repo(&dir);
 
-[tasks.hello]
-run = "echo hello"
-"#,
+        let hook_path = dir.path().join(".git/hooks/pre-commit");
+        fs::write(
+            &hook_path,
+            "#!/bin/sh\n# plz:managed - do not edit\nplz hooks run pre-commit\n",
         )
         .unwrap();
 
-        let all = templates::load_templates(Some(dir.path()));
-        let last = all.last().unwrap();
-        assert_eq!(last.name, "user");
-        assert_eq!(last.env.as_deref(), Some("custom"));
-        assert!(last.is_user);
+        let path = write_config(
+            &dir,
+            r#"
+[tasks.lint]
+run = "cargo clippy"
+git_hook = "pre-commit"
+"#,
+        );
+        let cfg = config::load(&path).unwrap();
+        hooks::uninstall(&cfg, dir.path()).unwrap();
+
+        assert!(!hook_path.exists());
     }
 
     #[test]
-    fn embedded_templates_not_marked_as_user() {
-        let all = templates::load_templates(None);
-        for t in &all {
-            assert!(
-                !t.is_user,
-                "embedded template {} should not be user",
-                t.name
-            );
-        }
-    }
-}
+    fn uninstall_skips_non_managed_hooks() {
+        let dir = TempDir::new().unwrap();
+        init_git_repo(&dir);
 
-mod hooks_tests {
-    use super::*;
-    use plzplz::config;
-    use plzplz::hooks;
+        let hook_path = dir.path().join(".git/hooks/pre-commit");
+        fs::write(&hook_path, "#!/bin/sh\necho custom\n").unwrap();
 
-    fn write_config(dir: &TempDir, content: &str) -> std::path::PathBuf {
-        let path = dir.path().join("plz.toml");
-        fs::write(&path, content).unwrap();
-        path
+        let path = write_config(
+            &dir,
+            r#"
+[tasks.lint]
+run = "cargo clippy"
+git_hook = "pre-commit"
+"#,
+        );
+        let cfg = config::load(&path).unwrap();
+        hooks::uninstall(&cfg, dir.path()).unwrap();
+
+        assert!(hook_path.exists(), "Should not remove non-managed hook");
     }
 
-    fn init_git_repo(dir: &TempDir) {
+    #[test]
+    fn find_git_hooks_dir_walks_up() {
+        let dir = TempDir::new().unwrap();
         fs::create_dir_all(dir.path().join(".git/hooks")).unwrap();
+        let subdir = dir.path().join("a/b/c");
+        fs::create_dir_all(&subdir).unwrap();
+
+        let result = hooks::find_git_hooks_dir(&subdir).unwrap();
+        assert_eq!(result, dir.path().join(".git/hooks"));
     }
 
     #[test]
-    fn parse_git_hook_field() {
+    fn find_git_hooks_dir_fails_without_git() {
+        let dir = TempDir::new().unwrap();
+        assert!(hooks::find_git_hooks_dir(dir.path()).is_err());
+    }
+
+    #[test]
+    fn tasks_by_stage_includes_group_tasks() {
         let dir = TempDir::new().unwrap();
         let path = write_config(
             &dir,
@@ -2046,60 +4956,150 @@ mod hooks_tests {
 [tasks.lint]
 run = "cargo clippy"
 git_hook = "pre-commit"
+
+[taskgroup.rust.fmt]
+run = "cargo fmt --check"
+git_hook = "pre-commit"
+
+[taskgroup.rust.test]
+run = "cargo test"
+git_hook = "pre-push"
 "#,
         );
         let cfg = config::load(&path).unwrap();
-        assert_eq!(cfg.tasks["lint"].git_hook.as_deref(), Some("pre-commit"));
+        let stages = hooks::tasks_by_stage(&cfg);
+        assert_eq!(stages.len(), 2);
+        let pre_commit = &stages["pre-commit"];
+        assert!(pre_commit.contains(&"lint".to_string()));
+        assert!(pre_commit.contains(&"rust:fmt".to_string()));
+        let pre_push = &stages["pre-push"];
+        assert!(pre_push.contains(&"rust:test".to_string()));
     }
 
     #[test]
-    fn reject_invalid_git_hook() {
+    fn run_stage_executes_group_tasks() {
+        let dir = TempDir::new().unwrap();
+        let marker = dir.path().join("group_hook_ran.txt");
+        let path = write_config(
+            &dir,
+            &format!(
+                r#"
+[tasks.hello]
+run = "echo hello"
+
+[taskgroup.rust.check]
+run = "touch {}"
+git_hook = "pre-commit"
+"#,
+                marker.display()
+            ),
+        );
+        let cfg = config::load(&path).unwrap();
+        hooks::run_stage(&cfg, "pre-commit", dir.path(), false).unwrap();
+        assert!(marker.exists());
+    }
+
+    #[test]
+    fn run_stage_executes_tasks() {
+        let dir = TempDir::new().unwrap();
+        let marker = dir.path().join("hook_ran.txt");
+        let path = write_config(
+            &dir,
+            &format!(
+                r#"
+[tasks.check]
+run = "touch {}"
+git_hook = "pre-commit"
+"#,
+                marker.display()
+            ),
+        );
+        let cfg = config::load(&path).unwrap();
+        hooks::run_stage(&cfg, "pre-commit", dir.path(), false).unwrap();
+        assert!(marker.exists());
+    }
+
+    #[test]
+    fn hook_script_contains_skip_and_fallback() {
         let dir = TempDir::new().unwrap();
+        init_git_repo(&dir);
         let path = write_config(
             &dir,
             r#"
 [tasks.lint]
 run = "cargo clippy"
-git_hook = "not-a-real-hook"
+git_hook = "pre-commit"
 "#,
         );
-        let err = config::load(&path).unwrap_err();
-        assert!(err.to_string().contains("invalid git_hook"), "got: {err}");
+        let cfg = config::load(&path).unwrap();
+        hooks::install(&cfg, dir.path(), false, false).unwrap();
+
+        let content = fs::read_to_string(dir.path().join(".git/hooks/pre-commit")).unwrap();
+        assert!(content.contains("PLZ_SKIP_HOOKS"), "missing skip env var");
+        assert!(
+            content.contains("command -v plz"),
+            "missing not-in-PATH fallback"
+        );
+        assert!(
+            !content.contains("\"$@\""),
+            "should not pass git args to tasks"
+        );
+        assert!(
+            content.contains("plz:hooks_version="),
+            "missing hooks version marker"
+        );
     }
 
     #[test]
-    fn tasks_by_stage_groups_correctly() {
+    fn install_upgrades_outdated_hooks() {
         let dir = TempDir::new().unwrap();
+        init_git_repo(&dir);
         let path = write_config(
             &dir,
             r#"
 [tasks.lint]
 run = "cargo clippy"
 git_hook = "pre-commit"
+"#,
+        );
+        let cfg = config::load(&path).unwrap();
 
-[tasks.fmt]
-run = "cargo fmt --check"
+        let hook_path = dir.path().join(".git/hooks/pre-commit");
+        fs::create_dir_all(hook_path.parent().unwrap()).unwrap();
+        fs::write(
+            &hook_path,
+            "#!/bin/sh\n# plz:managed - do not edit\nplz hooks run pre-commit \"$@\"\n",
+        )
+        .unwrap();
+
+        hooks::install(&cfg, dir.path(), false, false).unwrap();
+        let content = fs::read_to_string(&hook_path).unwrap();
+        assert!(
+            content.contains("plz:hooks_version="),
+            "should have been upgraded"
+        );
+        assert!(!content.contains("\"$@\""), "should not have $@");
+    }
+
+    #[test]
+    fn run_stage_unconfigured_stage_succeeds() {
+        let dir = TempDir::new().unwrap();
+        let path = write_config(
+            &dir,
+            r#"
+[tasks.check]
+run = "echo hi"
 git_hook = "pre-commit"
-
-[tasks.test]
-run = "cargo test"
-git_hook = "pre-push"
-
-[tasks.build]
-run = "cargo build"
 "#,
         );
         let cfg = config::load(&path).unwrap();
-        let stages = hooks::tasks_by_stage(&cfg);
-        assert_eq!(stages.len(), 2);
-        assert_eq!(stages["pre-commit"].len(), 2);
-        assert!(stages["pre-commit"].contains(&"lint".to_string()));
-        assert!(stages["pre-commit"].contains(&"fmt".to_string()));
-        assert_eq!(stages["pre-push"], vec!["test"]);
+        hooks::run_stage(&cfg, "pre-push", dir.path(), false).unwrap();
     }
 
+    #[cfg(unix)]
     #[test]
-    fn install_creates_hook_scripts() {
+    fn install_sets_executable_permission() {
+        use std::os::unix::fs::PermissionsExt;
         let dir = TempDir::new().unwrap();
         init_git_repo(&dir);
         let path = write_config(
@@ -2108,418 +5108,634 @@ run = "cargo build"
 [tasks.lint]
 run = "cargo clippy"
 git_hook = "pre-commit"
-
-[tasks.test]
-run = "cargo test"
-git_hook = "pre-push"
 "#,
         );
         let cfg = config::load(&path).unwrap();
         hooks::install(&cfg, dir.path(), false, false).unwrap();
 
-        let pre_commit = dir.path().join(".git/hooks/pre-commit");
-        let pre_push = dir.path().join(".git/hooks/pre-push");
-        assert!(pre_commit.exists());
-        assert!(pre_push.exists());
+        let hook_path = dir.path().join(".git/hooks/pre-commit");
+        let perms = fs::metadata(&hook_path).unwrap().permissions();
+        assert!(perms.mode() & 0o111 != 0, "Hook should be executable");
+    }
+}
 
-        let content = fs::read_to_string(&pre_commit).unwrap();
-        assert!(content.contains("plz:managed"));
-        assert!(content.contains("hooks run pre-commit"));
+mod cli_tests {
+    use assert_cmd::Command;
+    use predicates::prelude::*;
+    use std::fs;
+    use tempfile::TempDir;
 
-        let content = fs::read_to_string(&pre_push).unwrap();
-        assert!(content.contains("hooks run pre-push"));
+    #[allow(deprecated)]
+    fn plz() -> Command {
+        Command::cargo_bin("plz").unwrap()
+    }
+
+    /// Start plz with `args` in `dir`, wait until a command it runs has
+    /// written its pid to `pid.txt`, then send plz `signal`. Returns plz's
+    /// exit status and the pid.
+    #[allow(deprecated)]
+    fn signal_plz(
+        dir: &TempDir,
+        args: &[&str],
+        signal: &str,
+    ) -> (std::process::ExitStatus, String) {
+        let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin("plz"))
+            .args(args)
+            .current_dir(dir.path())
+            .stdin(std::process::Stdio::null())
+            .spawn()
+            .unwrap();
+        let pid_file = dir.path().join("pid.txt");
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        let pid = loop {
+            if let Ok(pid) = fs::read_to_string(&pid_file)
+                && pid.ends_with('\n')
+            {
+                break pid.trim().to_string();
+            }
+            assert!(std::time::Instant::now() < deadline, "no pid.txt");
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        };
+        std::process::Command::new("kill")
+            .args([signal, &child.id().to_string()])
+            .status()
+            .unwrap();
+        (child.wait().unwrap(), pid)
+    }
+
+    /// Whether `pid` exits within a few seconds. A zombie counts as exited,
+    /// since killed orphans wait for init to reap them.
+    fn exits_soon(pid: &str) -> bool {
+        let alive = || {
+            fs::read_to_string(format!("/proc/{pid}/stat")).is_ok_and(|stat| {
+                stat.rsplit(')')
+                    .next()
+                    .unwrap()
+                    .trim_start()
+                    .starts_with(|c| c != 'Z')
+            })
+        };
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while alive() && std::time::Instant::now() < deadline {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+        !alive()
     }
 
     #[test]
-    fn install_skips_non_managed_hooks() {
+    fn cli_no_args_no_config_shows_help() {
         let dir = TempDir::new().unwrap();
-        init_git_repo(&dir);
+        plz()
+            .current_dir(dir.path())
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("Run a task from plz.toml"));
+    }
 
-        let existing_hook = dir.path().join(".git/hooks/pre-commit");
-        fs::write(&existing_hook, "#!/bin/sh\necho my custom hook\n").unwrap();
+    #[test]
+    fn cli_version_flag() {
+        let dir = TempDir::new().unwrap();
+        for flag in ["--version", "-V"] {
+            plz()
+                .arg(flag)
+                .current_dir(dir.path())
+                .assert()
+                .success()
+                .stdout(predicate::str::contains(env!("CARGO_PKG_VERSION")));
+        }
+    }
 
-        let path = write_config(
-            &dir,
-            r#"
-[tasks.lint]
-run = "cargo clippy"
-git_hook = "pre-commit"
+    #[test]
+    fn cli_help_lists_all_commands() {
+        let dir = TempDir::new().unwrap();
+        let output = plz()
+            .arg("--help")
+            .current_dir(dir.path())
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        for expected in [
+            "init",
+            "add",
+            "hooks",
+            "schema",
+            "cheatsheet",
+            "update",
+            "--no-interactive",
+        ] {
+            assert!(
+                stdout.contains(expected),
+                "Help output missing '{expected}'"
+            );
+        }
+    }
+
+    #[test]
+    fn cli_run_task() {
+        let dir = TempDir::new().unwrap();
+        let marker = dir.path().join("ran.txt");
+        fs::write(
+            dir.path().join("plz.toml"),
+            format!(
+                r#"
+[tasks.hello]
+run = "touch {}"
 "#,
-        );
-        let cfg = config::load(&path).unwrap();
-        hooks::install(&cfg, dir.path(), false, false).unwrap();
+                marker.display()
+            ),
+        )
+        .unwrap();
 
-        let content = fs::read_to_string(&existing_hook).unwrap();
-        assert!(
-            content.contains("my custom hook"),
-            "Should not overwrite user hook"
+        plz()
+            .arg("hello")
+            .current_dir(dir.path())
+            .assert()
+            .success();
+        assert!(marker.exists());
+    }
+
+    #[test]
+    fn cli_run_task_from_subdirectory() {
+        let dir = TempDir::new().unwrap();
+        let root_out = dir.path().join("root.txt");
+        let invoked_out = dir.path().join("invoked.txt");
+        fs::write(
+            dir.path().join("plz.toml"),
+            format!(
+                r#"
+[tasks.where]
+run = "pwd > {} && echo $PLZ_INVOCATION_DIR > {}"
+"#,
+                root_out.display(),
+                invoked_out.display()
+            ),
+        )
+        .unwrap();
+        let sub = dir.path().join("src/nested");
+        fs::create_dir_all(&sub).unwrap();
+
+        plz().arg("where").current_dir(&sub).assert().success();
+
+        let root = fs::canonicalize(dir.path()).unwrap();
+        let ran_in = fs::read_to_string(&root_out).unwrap();
+        assert_eq!(fs::canonicalize(ran_in.trim()).unwrap(), root);
+        let invoked = fs::read_to_string(&invoked_out).unwrap();
+        assert_eq!(
+            fs::canonicalize(invoked.trim()).unwrap(),
+            fs::canonicalize(&sub).unwrap()
         );
-        assert!(!content.contains("plz:managed"));
     }
 
     #[test]
-    fn install_force_overwrites_non_managed_hooks() {
+    fn cli_run_group_task_with_colon() {
         let dir = TempDir::new().unwrap();
-        init_git_repo(&dir);
+        let out = dir.path().join("colon.txt");
+        fs::write(
+            dir.path().join("plz.toml"),
+            format!(
+                r#"
+[taskgroup.docs.build]
+run = "echo > {}"
+"#,
+                out.display()
+            ),
+        )
+        .unwrap();
 
-        let existing_hook = dir.path().join(".git/hooks/pre-commit");
-        fs::write(&existing_hook, "#!/bin/sh\necho my custom hook\n").unwrap();
+        plz()
+            .args(["docs:build", "extra"])
+            .current_dir(dir.path())
+            .assert()
+            .success()
+            .stderr(predicate::str::contains("extra"));
+        assert!(out.exists());
+    }
 
-        let path = write_config(
-            &dir,
+    #[test]
+    fn cli_task_help_shows_args() {
+        let dir = TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("plz.toml"),
             r#"
-[tasks.lint]
-run = "cargo clippy"
-git_hook = "pre-commit"
+# Deploy the app
+[tasks.deploy]
+args = [{ name = "env", choices = ["staging", "prod"], description = "Target" }]
+run = "echo {{args.env}}"
 "#,
-        );
-        let cfg = config::load(&path).unwrap();
-        hooks::install(&cfg, dir.path(), true, false).unwrap();
+        )
+        .unwrap();
 
-        let content = fs::read_to_string(&existing_hook).unwrap();
-        assert!(
-            content.contains("plz:managed"),
-            "Should overwrite user hook with --force"
-        );
-        assert!(!content.contains("my custom hook"));
+        plz()
+            .args(["deploy", "--help"])
+            .current_dir(dir.path())
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("Deploy the app"))
+            .stdout(predicate::str::contains("deploy <env>"))
+            .stdout(predicate::str::contains("choices: staging, prod"));
     }
 
     #[test]
-    fn install_overwrites_managed_hooks() {
+    fn cli_summary_reports_skipped_tasks() {
         let dir = TempDir::new().unwrap();
-        init_git_repo(&dir);
-
-        let hook_path = dir.path().join(".git/hooks/pre-commit");
         fs::write(
-            &hook_path,
-            "#!/bin/sh\n# plz:managed - do not edit\nplz hooks run pre-commit\n",
+            dir.path().join("plz.toml"),
+            r#"
+[tasks.all]
+run_serial = ["plz:optional", "plz:broken"]
+
+[tasks.optional]
+run_if = { file = "missing.txt" }
+run = "echo optional"
+
+[tasks.broken]
+run = "exit 1"
+"#,
         )
         .unwrap();
 
-        let path = write_config(
-            &dir,
+        plz()
+            .args(["all"])
+            .current_dir(dir.path())
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(
+                "Skipping optional: missing.txt doesn't exist",
+            ))
+            .stderr(predicate::str::contains("optional (skipped)"))
+            .stderr(predicate::str::contains("✗ broken"));
+    }
+
+    #[test]
+    fn cli_run_task_by_alias() {
+        let dir = TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("plz.toml"),
             r#"
-[tasks.lint]
-run = "cargo clippy"
-git_hook = "pre-commit"
+# Run the tests
+[tasks.test]
+aliases = ["t"]
+run = "touch test.txt"
+
+[taskgroup.docs.build]
+aliases = ["b"]
+run = "touch docs.txt"
 "#,
-        );
-        let cfg = config::load(&path).unwrap();
-        hooks::install(&cfg, dir.path(), false, false).unwrap();
+        )
+        .unwrap();
+
+        plz()
+            .args(["--no-interactive", "t"])
+            .current_dir(dir.path())
+            .assert()
+            .success();
+        assert!(dir.path().join("test.txt").exists());
+
+        for args in [["docs", "b"].as_slice(), ["docs:b"].as_slice()] {
+            fs::remove_file(dir.path().join("docs.txt")).ok();
+            plz()
+                .arg("--no-interactive")
+                .args(args)
+                .current_dir(dir.path())
+                .assert()
+                .success();
+            assert!(dir.path().join("docs.txt").exists());
+        }
+
+        plz()
+            .arg("--help")
+            .current_dir(dir.path())
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("(t) Run the tests"))
+            .stdout(predicate::str::contains("docs:build"));
+    }
+
+    #[test]
+    fn cli_run_all_workspace_members() {
+        let dir = TempDir::new().unwrap();
+        for member in ["web", "api"] {
+            let member_dir = dir.path().join("packages").join(member);
+            fs::create_dir_all(&member_dir).unwrap();
+            fs::write(
+                member_dir.join("plz.toml"),
+                "[tasks.build]\nrun = \"touch built.txt\"\n",
+            )
+            .unwrap();
+        }
+        fs::write(
+            dir.path().join("plz.toml"),
+            "[workspace]\nmembers = [\"packages/*\"]\nserial = true\n",
+        )
+        .unwrap();
 
-        let content = fs::read_to_string(&hook_path).unwrap();
-        assert!(content.contains("plz:managed"));
+        plz()
+            .args(["--all", "build"])
+            .current_dir(dir.path())
+            .assert()
+            .success();
+        assert!(dir.path().join("packages/web/built.txt").exists());
+        assert!(dir.path().join("packages/api/built.txt").exists());
+
+        plz()
+            .arg("web:build")
+            .current_dir(dir.path())
+            .assert()
+            .success();
     }
 
     #[test]
-    fn uninstall_removes_managed_hooks() {
+    fn cli_workspace_member_descriptions_have_one_prefix() {
         let dir = TempDir::new().unwrap();
-        init_git_repo(&dir);
-
-        let hook_path = dir.path().join(".git/hooks/pre-commit");
+        fs::create_dir_all(dir.path().join("web")).unwrap();
         fs::write(
-            &hook_path,
-            "#!/bin/sh\n# plz:managed - do not edit\nplz hooks run pre-commit\n",
+            dir.path().join("web/plz.toml"),
+            "[extends]\ndescription_prefix = \"[web] \"\n\n[tasks.build]\nrun = \"true\"\ndescription = \"Build web\"\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("plz.toml"),
+            "[workspace]\nmembers = [\"web\"]\n\n[extends]\ndescription_prefix = \"[root] \"\n",
         )
         .unwrap();
 
-        let path = write_config(
-            &dir,
-            r#"
-[tasks.lint]
-run = "cargo clippy"
-git_hook = "pre-commit"
-"#,
-        );
-        let cfg = config::load(&path).unwrap();
-        hooks::uninstall(&cfg, dir.path()).unwrap();
-
-        assert!(!hook_path.exists());
+        plz()
+            .arg("--help")
+            .current_dir(dir.path())
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("[web] Build web"))
+            .stdout(predicate::str::contains("[root]").not());
     }
 
     #[test]
-    fn uninstall_skips_non_managed_hooks() {
+    fn cli_run_unknown_task_errors() {
         let dir = TempDir::new().unwrap();
-        init_git_repo(&dir);
-
-        let hook_path = dir.path().join(".git/hooks/pre-commit");
-        fs::write(&hook_path, "#!/bin/sh\necho custom\n").unwrap();
-
-        let path = write_config(
-            &dir,
+        fs::write(
+            dir.path().join("plz.toml"),
             r#"
-[tasks.lint]
-run = "cargo clippy"
-git_hook = "pre-commit"
+[tasks.hello]
+run = "echo hello"
 "#,
-        );
-        let cfg = config::load(&path).unwrap();
-        hooks::uninstall(&cfg, dir.path()).unwrap();
+        )
+        .unwrap();
 
-        assert!(hook_path.exists(), "Should not remove non-managed hook");
+        plz()
+            .arg("nonexistent")
+            .current_dir(dir.path())
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("isn't a task"));
     }
 
     #[test]
-    fn find_git_hooks_dir_walks_up() {
+    fn cli_non_interactive_when_no_tty() {
         let dir = TempDir::new().unwrap();
-        fs::create_dir_all(dir.path().join(".git/hooks")).unwrap();
-        let subdir = dir.path().join("a/b/c");
-        fs::create_dir_all(&subdir).unwrap();
-
-        let result = hooks::find_git_hooks_dir(&subdir).unwrap();
-        assert_eq!(result, dir.path().join(".git/hooks"));
-    }
+        fs::write(
+            dir.path().join("plz.toml"),
+            r#"
+[tasks.hello]
+run = "echo hello"
+"#,
+        )
+        .unwrap();
 
-    #[test]
-    fn find_git_hooks_dir_fails_without_git() {
-        let dir = TempDir::new().unwrap();
-        assert!(hooks::find_git_hooks_dir(dir.path()).is_err());
+        plz()
+            .current_dir(dir.path())
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(
+                "No task specified (running in non-interactive mode)",
+            ));
     }
 
     #[test]
-    fn tasks_by_stage_includes_group_tasks() {
+    fn cli_non_interactive_flag() {
         let dir = TempDir::new().unwrap();
-        let path = write_config(
-            &dir,
+        fs::write(
+            dir.path().join("plz.toml"),
             r#"
-[tasks.lint]
-run = "cargo clippy"
-git_hook = "pre-commit"
-
-[taskgroup.rust.fmt]
-run = "cargo fmt --check"
-git_hook = "pre-commit"
-
-[taskgroup.rust.test]
-run = "cargo test"
-git_hook = "pre-push"
+[tasks.hello]
+run = "echo hello"
 "#,
-        );
-        let cfg = config::load(&path).unwrap();
-        let stages = hooks::tasks_by_stage(&cfg);
-        assert_eq!(stages.len(), 2);
-        let pre_commit = &stages["pre-commit"];
-        assert!(pre_commit.contains(&"lint".to_string()));
-        assert!(pre_commit.contains(&"rust:fmt".to_string()));
-        let pre_push = &stages["pre-push"];
-        assert!(pre_push.contains(&"rust:test".to_string()));
+        )
+        .unwrap();
+
+        plz()
+            .arg("--no-interactive")
+            .current_dir(dir.path())
+            .env_remove("PLZ_COMMAND")
+            .env_remove("CI")
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(
+                "No task specified (running in non-interactive mode)",
+            ));
     }
 
     #[test]
-    fn run_stage_executes_group_tasks() {
+    fn cli_run_group_task() {
         let dir = TempDir::new().unwrap();
-        let marker = dir.path().join("group_hook_ran.txt");
-        let path = write_config(
-            &dir,
-            &format!(
+        let marker = dir.path().join("grp_ran.txt");
+        fs::write(
+            dir.path().join("plz.toml"),
+            format!(
                 r#"
 [tasks.hello]
 run = "echo hello"
 
-[taskgroup.rust.check]
+[taskgroup.rust.test]
 run = "touch {}"
-git_hook = "pre-commit"
 "#,
                 marker.display()
             ),
-        );
-        let cfg = config::load(&path).unwrap();
-        hooks::run_stage(&cfg, "pre-commit", dir.path(), false).unwrap();
+        )
+        .unwrap();
+
+        plz()
+            .args(["rust", "test"])
+            .current_dir(dir.path())
+            .assert()
+            .success();
         assert!(marker.exists());
     }
 
     #[test]
-    fn run_stage_executes_tasks() {
+    fn cli_run_group_task_with_args() {
         let dir = TempDir::new().unwrap();
-        let marker = dir.path().join("hook_ran.txt");
-        let path = write_config(
-            &dir,
-            &format!(
+        let out = dir.path().join("grp_args.txt");
+        fs::write(
+            dir.path().join("plz.toml"),
+            format!(
                 r#"
-[tasks.check]
-run = "touch {}"
-git_hook = "pre-commit"
+[tasks.hello]
+run = "echo hello"
+
+[taskgroup.rust.echo]
+run = "echo > {}"
 "#,
-                marker.display()
+                out.display()
             ),
-        );
-        let cfg = config::load(&path).unwrap();
-        hooks::run_stage(&cfg, "pre-commit", dir.path(), false).unwrap();
-        assert!(marker.exists());
+        )
+        .unwrap();
+
+        plz()
+            .args(["rust", "echo", "--", "--nocapture"])
+            .current_dir(dir.path())
+            .assert()
+            .success();
+        assert!(out.exists());
     }
 
     #[test]
-    fn hook_script_contains_skip_and_fallback() {
+    fn cli_top_level_wins_over_group() {
         let dir = TempDir::new().unwrap();
-        init_git_repo(&dir);
-        let path = write_config(
-            &dir,
-            r#"
-[tasks.lint]
-run = "cargo clippy"
-git_hook = "pre-commit"
+        let top_marker = dir.path().join("top.txt");
+        fs::write(
+            dir.path().join("plz.toml"),
+            format!(
+                r#"
+[tasks.rust]
+run = "touch {}"
+
+[taskgroup.rust.test]
+run = "echo test"
 "#,
-        );
-        let cfg = config::load(&path).unwrap();
-        hooks::install(&cfg, dir.path(), false, false).unwrap();
+                top_marker.display()
+            ),
+        )
+        .unwrap();
 
-        let content = fs::read_to_string(dir.path().join(".git/hooks/pre-commit")).unwrap();
-        assert!(content.contains("PLZ_SKIP_HOOKS"), "missing skip env var");
-        assert!(
-            content.contains("command -v plz"),
-            "missing not-in-PATH fallback"
-        );
-        assert!(
-            !content.contains("\"$@\""),
-            "should not pass git args to tasks"
-        );
-        assert!(
-            content.contains("plz:hooks_version="),
-            "missing hooks version marker"
-        );
+        plz().arg("rust").current_dir(dir.path()).assert().success();
+        assert!(top_marker.exists());
     }
 
     #[test]
-    fn install_upgrades_outdated_hooks() {
+    fn cli_group_no_task_non_interactive_errors() {
         let dir = TempDir::new().unwrap();
-        init_git_repo(&dir);
-        let path = write_config(
-            &dir,
+        fs::write(
+            dir.path().join("plz.toml"),
             r#"
-[tasks.lint]
-run = "cargo clippy"
-git_hook = "pre-commit"
-"#,
-        );
-        let cfg = config::load(&path).unwrap();
+[tasks.hello]
+run = "echo hello"
 
-        let hook_path = dir.path().join(".git/hooks/pre-commit");
-        fs::create_dir_all(hook_path.parent().unwrap()).unwrap();
-        fs::write(
-            &hook_path,
-            "#!/bin/sh\n# plz:managed - do not edit\nplz hooks run pre-commit \"$@\"\n",
+[taskgroup.rust.test]
+run = "cargo test"
+"#,
         )
         .unwrap();
 
-        hooks::install(&cfg, dir.path(), false, false).unwrap();
-        let content = fs::read_to_string(&hook_path).unwrap();
-        assert!(
-            content.contains("plz:hooks_version="),
-            "should have been upgraded"
-        );
-        assert!(!content.contains("\"$@\""), "should not have $@");
+        plz()
+            .arg("rust")
+            .current_dir(dir.path())
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("No task specified for group"));
     }
 
     #[test]
-    fn run_stage_unconfigured_stage_succeeds() {
+    fn cli_group_unknown_task_errors() {
         let dir = TempDir::new().unwrap();
-        let path = write_config(
-            &dir,
+        fs::write(
+            dir.path().join("plz.toml"),
             r#"
-[tasks.check]
-run = "echo hi"
-git_hook = "pre-commit"
+[tasks.hello]
+run = "echo hello"
+
+[taskgroup.rust.test]
+run = "cargo test"
 "#,
-        );
-        let cfg = config::load(&path).unwrap();
-        hooks::run_stage(&cfg, "pre-push", dir.path(), false).unwrap();
+        )
+        .unwrap();
+
+        plz()
+            .args(["rust", "nonexistent"])
+            .current_dir(dir.path())
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("isn't a task"));
     }
 
-    #[cfg(unix)]
     #[test]
-    fn install_sets_executable_permission() {
-        use std::os::unix::fs::PermissionsExt;
+    fn cli_hooks_run_via_shorthand() {
         let dir = TempDir::new().unwrap();
-        init_git_repo(&dir);
-        let path = write_config(
-            &dir,
-            r#"
-[tasks.lint]
-run = "cargo clippy"
+        let marker = dir.path().join("hook_ran.txt");
+        fs::write(
+            dir.path().join("plz.toml"),
+            format!(
+                r#"
+[tasks.check]
+run = "touch {}"
 git_hook = "pre-commit"
 "#,
-        );
-        let cfg = config::load(&path).unwrap();
-        hooks::install(&cfg, dir.path(), false, false).unwrap();
-
-        let hook_path = dir.path().join(".git/hooks/pre-commit");
-        let perms = fs::metadata(&hook_path).unwrap().permissions();
-        assert!(perms.mode() & 0o111 != 0, "Hook should be executable");
-    }
-}
-
-mod cli_tests {
-    use assert_cmd::Command;
-    use predicates::prelude::*;
-    use std::fs;
-    use tempfile::TempDir;
+                marker.display()
+            ),
+        )
+        .unwrap();
 
-    #[allow(deprecated)]
-    fn plz() -> Command {
-        Command::cargo_bin("plz").unwrap()
+        // `plz hooks run pre-commit` goes through try_plz_subcommand
+        plz()
+            .args(["hooks", "run", "pre-commit"])
+            .current_dir(dir.path())
+            .assert()
+            .success();
+        assert!(marker.exists(), "hook task should have been executed");
     }
 
     #[test]
-    fn cli_no_args_no_config_shows_help() {
+    fn cli_init_already_exists() {
         let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("plz.toml"), "[tasks]").unwrap();
+
         plz()
+            .arg("init")
             .current_dir(dir.path())
             .assert()
             .success()
-            .stdout(predicate::str::contains("Run a task from plz.toml"));
+            .stderr(predicate::str::contains("already exists"))
+            .stderr(predicate::str::contains("plz"));
     }
 
     #[test]
-    fn cli_version_flag() {
+    fn cli_help_lists_healthcheck() {
         let dir = TempDir::new().unwrap();
-        for flag in ["--version", "-V"] {
-            plz()
-                .arg(flag)
-                .current_dir(dir.path())
-                .assert()
-                .success()
-                .stdout(predicate::str::contains(env!("CARGO_PKG_VERSION")));
-        }
+        let output = plz()
+            .arg("--help")
+            .current_dir(dir.path())
+            .assert()
+            .success();
+        let stdout = String::from_utf8(output.get_output().stdout.clone()).unwrap();
+        assert!(stdout.contains("healthcheck"));
     }
 
     #[test]
-    fn cli_help_lists_all_commands() {
+    fn cli_init_no_project_creates_hello_task() {
         let dir = TempDir::new().unwrap();
-        let output = plz()
-            .arg("--help")
+
+        plz()
+            .arg("init")
             .current_dir(dir.path())
-            .output()
-            .unwrap();
-        let stdout = String::from_utf8(output.stdout).unwrap();
-        for expected in [
-            "init",
-            "add",
-            "hooks",
-            "schema",
-            "cheatsheet",
-            "update",
-            "--no-interactive",
-        ] {
-            assert!(
-                stdout.contains(expected),
-                "Help output missing '{expected}'"
-            );
-        }
+            .assert()
+            .success()
+            .stderr(predicate::str::contains("Created plz.toml"));
+
+        let content = fs::read_to_string(dir.path().join("plz.toml")).unwrap();
+        assert!(content.contains("[tasks.hello]"));
+        assert!(content.contains("echo 'hello world'"));
     }
 
     #[test]
-    fn cli_run_task() {
+    fn cli_version_mismatch_shows_warning() {
         let dir = TempDir::new().unwrap();
-        let marker = dir.path().join("ran.txt");
         fs::write(
             dir.path().join("plz.toml"),
-            format!(
-                r#"
+            r#"
+[plz]
+version = ">=99.0.0"
+
 [tasks.hello]
-run = "touch {}"
+run = "echo hello"
 "#,
-                marker.display()
-            ),
         )
         .unwrap();
 
@@ -2527,289 +5743,466 @@ run = "touch {}"
             .arg("hello")
             .current_dir(dir.path())
             .assert()
-            .success();
-        assert!(marker.exists());
+            .success()
+            .stderr(predicate::str::contains(
+                "does not match version requirement",
+            ));
     }
 
     #[test]
-    fn cli_run_unknown_task_errors() {
+    fn cli_unknown_key_warning_points_at_key() {
         let dir = TempDir::new().unwrap();
         fs::write(
             dir.path().join("plz.toml"),
-            r#"
-[tasks.hello]
-run = "echo hello"
-"#,
+            "[tasks.hello]\nrun = \"echo hello\"\nrn = \"echo typo\"\n",
         )
         .unwrap();
 
         plz()
-            .arg("nonexistent")
+            .arg("hello")
+            .current_dir(dir.path())
+            .assert()
+            .success()
+            .stderr(predicate::str::contains(
+                "unknown key \"rn\" in [tasks.hello]\n --> plz.toml:3:1\n  |\n3 | rn = \"echo typo\"\n  | ^^",
+            ));
+    }
+
+    #[test]
+    fn cli_check_reports_problems_as_json() {
+        let dir = TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("plz.toml"),
+            "[tasks.test]\ndepends = \"nope\"\nrun = \"cargo test\"\n",
+        )
+        .unwrap();
+
+        let output = plz()
+            .args(["plz", "check", "--format", "json"])
             .current_dir(dir.path())
             .assert()
             .failure()
-            .stderr(predicate::str::contains("isn't a task"));
+            .stderr(predicate::str::contains("plz.toml is invalid"))
+            .get_output()
+            .stdout
+            .clone();
+        let report: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(report["valid"], false);
+        assert_eq!(report["errors"], 1);
+        let diagnostic = &report["diagnostics"][0];
+        assert_eq!(diagnostic["severity"], "error");
+        assert_eq!(diagnostic["file"], "plz.toml");
+        assert_eq!(diagnostic["line"], 2);
+        assert_eq!(diagnostic["column"], 11);
     }
 
     #[test]
-    fn cli_non_interactive_when_no_tty() {
+    fn cli_check_valid_config_succeeds() {
+        let dir = TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("plz.toml"),
+            "[tasks.test]\nrun = \"cargo test\"\nrn = \"typo\"\n",
+        )
+        .unwrap();
+
+        plz()
+            .args(["plz", "check"])
+            .current_dir(dir.path())
+            .assert()
+            .success()
+            .stderr(predicate::str::contains("unknown key \"rn\""))
+            .stderr(predicate::str::contains("plz.toml is valid"));
+    }
+
+    #[test]
+    fn cli_check_uses_selected_profile() {
         let dir = TempDir::new().unwrap();
         fs::write(
             dir.path().join("plz.toml"),
             r#"
-[tasks.hello]
-run = "echo hello"
+[vars]
+out = "."
+
+[profile.prod.vars]
+out = "missing"
+
+[tasks.build]
+dir = "{{out}}"
+run = "make"
 "#,
         )
         .unwrap();
 
         plz()
+            .args(["plz", "check"])
+            .current_dir(dir.path())
+            .assert()
+            .success();
+        plz()
+            .args(["--profile", "prod", "plz", "check"])
             .current_dir(dir.path())
             .assert()
             .failure()
             .stderr(predicate::str::contains(
-                "No task specified (running in non-interactive mode)",
+                "dir \"missing\", which doesn't exist",
             ));
     }
 
     #[test]
-    fn cli_non_interactive_flag() {
+    fn cli_profile_flag_and_env() {
         let dir = TempDir::new().unwrap();
         fs::write(
             dir.path().join("plz.toml"),
             r#"
-[tasks.hello]
-run = "echo hello"
+[vars]
+target = "dev"
+
+[profile.staging.vars]
+target = "staging"
+
+[tasks.show]
+run = "echo target={{target}} nested=$PLZ_PROFILE"
 "#,
         )
         .unwrap();
 
         plz()
-            .arg("--no-interactive")
+            .args(["--profile", "staging", "show"])
             .current_dir(dir.path())
-            .env_remove("PLZ_COMMAND")
-            .env_remove("CI")
             .assert()
-            .failure()
-            .stderr(predicate::str::contains(
-                "No task specified (running in non-interactive mode)",
-            ));
+            .success()
+            .stdout(predicate::str::contains("target=staging nested=staging"))
+            .stderr(predicate::str::contains("profile: staging"));
+
+        plz()
+            .arg("show")
+            .env("PLZ_PROFILE", "staging")
+            .current_dir(dir.path())
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("target=staging"));
+
+        plz()
+            .arg("show")
+            .current_dir(dir.path())
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("target=dev nested=\n"))
+            .stderr(predicate::str::contains("profile:").not());
     }
 
     #[test]
-    fn cli_run_group_task() {
+    fn cli_masks_secrets() {
         let dir = TempDir::new().unwrap();
-        let marker = dir.path().join("grp_ran.txt");
         fs::write(
             dir.path().join("plz.toml"),
-            format!(
-                r#"
-[tasks.hello]
-run = "echo hello"
+            r#"
+[vars]
+password = { value = "hunter2", secret = true }
 
-[taskgroup.rust.test]
-run = "touch {}"
+[tasks.show]
+run = "echo pw={{password}} env=$KEY"
+environment = { KEY = { sh = "echo from-sh", secret = true } }
 "#,
-                marker.display()
-            ),
         )
         .unwrap();
-
+
+        plz()
+            .arg("show")
+            .current_dir(dir.path())
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("pw=hunter2 env=from-sh"))
+            .stderr(predicate::str::contains("→ echo pw=*** env=$KEY"))
+            .stderr(predicate::str::contains("hunter2").not());
+
+        let config = fs::read_to_string(dir.path().join("plz.toml")).unwrap();
+        fs::write(
+            dir.path().join("plz.toml"),
+            format!("[plz]\nmask_output = true\n{config}"),
+        )
+        .unwrap();
         plz()
-            .args(["rust", "test"])
+            .arg("show")
             .current_dir(dir.path())
             .assert()
-            .success();
-        assert!(marker.exists());
+            .success()
+            .stdout(predicate::str::contains("pw=*** env=***"))
+            .stdout(predicate::str::contains("hunter2").not());
     }
 
     #[test]
-    fn cli_run_group_task_with_args() {
+    fn cli_timeout_flag() {
         let dir = TempDir::new().unwrap();
-        let out = dir.path().join("grp_args.txt");
         fs::write(
             dir.path().join("plz.toml"),
-            format!(
-                r#"
-[tasks.hello]
-run = "echo hello"
-
-[taskgroup.rust.echo]
-run = "echo > {}"
-"#,
-                out.display()
-            ),
+            "[tasks.slow]\nrun = \"sleep 30\"\ntimeout = \"1h\"\n",
         )
         .unwrap();
 
         plz()
-            .args(["rust", "echo", "--", "--nocapture"])
+            .args(["--timeout", "200ms", "slow"])
             .current_dir(dir.path())
             .assert()
-            .success();
-        assert!(out.exists());
+            .failure()
+            .stderr(predicate::str::contains(
+                "Command timed out after 200ms: sleep 30",
+            ));
+
+        plz()
+            .args(["--timeout", "soon", "slow"])
+            .current_dir(dir.path())
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("invalid duration \"soon\""));
     }
 
     #[test]
-    fn cli_top_level_wins_over_group() {
+    fn cli_sigint_reaches_commands_with_a_timeout() {
+        use std::os::unix::process::ExitStatusExt;
+
         let dir = TempDir::new().unwrap();
-        let top_marker = dir.path().join("top.txt");
         fs::write(
             dir.path().join("plz.toml"),
-            format!(
-                r#"
-[tasks.rust]
-run = "touch {}"
-
-[taskgroup.rust.test]
-run = "echo test"
-"#,
-                top_marker.display()
-            ),
+            "[tasks.slow]\nrun = \"sh -c 'echo $$ > pid.txt; exec sleep 30'; true\"\ntimeout = \"1m\"\n",
         )
         .unwrap();
 
-        plz().arg("rust").current_dir(dir.path()).assert().success();
-        assert!(top_marker.exists());
+        let (status, pid) = signal_plz(&dir, &["slow"], "-INT");
+        assert_eq!(status.signal(), Some(2), "{status}");
+        assert!(exits_soon(&pid), "sleep {pid} outlived plz");
     }
 
     #[test]
-    fn cli_group_no_task_non_interactive_errors() {
+    #[allow(deprecated)]
+    fn cli_timeout_in_a_terminal_kills_every_process() {
         let dir = TempDir::new().unwrap();
         fs::write(
             dir.path().join("plz.toml"),
-            r#"
-[tasks.hello]
-run = "echo hello"
-
-[taskgroup.rust.test]
-run = "cargo test"
+            r#"[tasks.slow]
+run = "read answer; echo $answer > answer.txt; sh -c 'trap \"\" HUP; echo $$ >> pids.txt; exec sleep 30' & sh -c 'trap \"\" HUP; echo $$ >> pids.txt; exec sleep 30'"
+timeout = "2s"
 "#,
         )
         .unwrap();
 
-        plz()
-            .arg("rust")
+        // `script` gives plz a pseudo-terminal on stdin, as in a git hook run
+        // from an interactive shell. The sleeps ignore the hangup when it
+        // closes, so only the timeout can stop them.
+        let plz = assert_cmd::cargo::cargo_bin("plz");
+        let output = std::process::Command::new("script")
+            .args(["-qec", &format!("'{}' slow", plz.display()), "/dev/null"])
             .current_dir(dir.path())
-            .assert()
-            .failure()
-            .stderr(predicate::str::contains("No task specified for group"));
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .and_then(|mut script| {
+                use std::io::Write;
+                script.stdin.take().unwrap().write_all(b"yes\n")?;
+                script.wait_with_output()
+            })
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("timed out after 2s"), "{stdout}");
+
+        // The command still read from the terminal
+        assert_eq!(
+            fs::read_to_string(dir.path().join("answer.txt")).unwrap(),
+            "yes\n"
+        );
+        let pids = fs::read_to_string(dir.path().join("pids.txt")).unwrap();
+        assert_eq!(pids.lines().count(), 2);
+        for pid in pids.lines() {
+            assert!(exits_soon(pid), "sleep {pid} outlived the timeout");
+        }
     }
 
     #[test]
-    fn cli_group_unknown_task_errors() {
+    fn cli_sigterm_reaches_fail_fast_commands() {
+        use std::os::unix::process::ExitStatusExt;
+
         let dir = TempDir::new().unwrap();
         fs::write(
             dir.path().join("plz.toml"),
-            r#"
-[tasks.hello]
-run = "echo hello"
+            "[tasks.check]\nrun_parallel = [\"sh -c 'echo $$ > pid.txt; exec sleep 30'; true\", \"sleep 30\"]\nfail_fast = true\n",
+        )
+        .unwrap();
 
-[taskgroup.rust.test]
-run = "cargo test"
-"#,
+        let (status, pid) = signal_plz(&dir, &["check"], "-TERM");
+        assert_eq!(status.signal(), Some(15), "{status}");
+        assert!(exits_soon(&pid), "sleep {pid} outlived plz");
+    }
+
+    #[test]
+    fn cli_reports_a_task_that_passed_on_retry() {
+        let dir = TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("plz.toml"),
+            "[tasks.flaky]\nrun = \"echo x >> tries.txt; test $(wc -l < tries.txt) -ge 2\"\nretry = { attempts = 3 }\n",
         )
         .unwrap();
 
         plz()
-            .args(["rust", "nonexistent"])
+            .arg("flaky")
             .current_dir(dir.path())
             .assert()
-            .failure()
-            .stderr(predicate::str::contains("isn't a task"));
+            .success()
+            .stderr(predicate::str::contains("✓ flaky passed on attempt 2/3"));
     }
 
     #[test]
-    fn cli_hooks_run_via_shorthand() {
+    fn cli_jobs_flag_and_env() {
         let dir = TempDir::new().unwrap();
-        let marker = dir.path().join("hook_ran.txt");
         fs::write(
             dir.path().join("plz.toml"),
-            format!(
-                r#"
+            r#"
 [tasks.check]
-run = "touch {}"
-git_hook = "pre-commit"
+run_parallel = ["plz:a", "plz:b"]
+
+[tasks.a]
+run_parallel = ["echo + >> log; sleep 0.2; echo - >> log", "echo + >> log; sleep 0.2; echo - >> log"]
+
+[tasks.b]
+run = "echo + >> log; sleep 0.2; echo - >> log"
 "#,
-                marker.display()
-            ),
         )
         .unwrap();
+        let log = dir.path().join("log");
 
-        // `plz hooks run pre-commit` goes through try_plz_subcommand
         plz()
-            .args(["hooks", "run", "pre-commit"])
+            .args(["-j", "1", "check"])
             .current_dir(dir.path())
             .assert()
             .success();
-        assert!(marker.exists(), "hook task should have been executed");
-    }
+        let ran = fs::read_to_string(&log).unwrap();
+        assert_eq!(ran, "+\n-\n+\n-\n+\n-\n");
 
-    #[test]
-    fn cli_init_already_exists() {
-        let dir = TempDir::new().unwrap();
-        fs::write(dir.path().join("plz.toml"), "[tasks]").unwrap();
+        fs::remove_file(&log).unwrap();
+        plz()
+            .arg("check")
+            .env("PLZ_JOBS", "1")
+            .current_dir(dir.path())
+            .assert()
+            .success();
+        assert_eq!(fs::read_to_string(&log).unwrap(), ran);
 
         plz()
-            .arg("init")
+            .args(["-j", "0", "check"])
             .current_dir(dir.path())
             .assert()
-            .success()
-            .stderr(predicate::str::contains("already exists"))
-            .stderr(predicate::str::contains("plz"));
-    }
+            .failure()
+            .stderr(predicate::str::contains("expected a number above 0"));
+        plz()
+            .arg("check")
+            .env("PLZ_JOBS", "lots")
+            .current_dir(dir.path())
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(
+                "PLZ_JOBS must be a number above 0, got \"lots\"",
+            ));
 
-    #[test]
-    fn cli_help_lists_healthcheck() {
-        let dir = TempDir::new().unwrap();
-        let output = plz()
-            .arg("--help")
+        // Only running tasks without -j reads PLZ_JOBS
+        plz()
+            .args(["-j", "2", "check"])
+            .env("PLZ_JOBS", "lots")
+            .current_dir(dir.path())
+            .assert()
+            .success();
+        plz()
+            .args(["plz", "check"])
+            .env("PLZ_JOBS", "lots")
             .current_dir(dir.path())
             .assert()
             .success();
-        let stdout = String::from_utf8(output.get_output().stdout.clone()).unwrap();
-        assert!(stdout.contains("healthcheck"));
     }
 
     #[test]
-    fn cli_init_no_project_creates_hello_task() {
+    fn cli_fail_fast_flag() {
         let dir = TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("plz.toml"),
+            "[tasks.check]\nrun_parallel = [\"sleep 30\", \"false\"]\n",
+        )
+        .unwrap();
 
         plz()
-            .arg("init")
+            .args(["--fail-fast", "check"])
             .current_dir(dir.path())
+            .timeout(std::time::Duration::from_secs(10))
             .assert()
-            .success()
-            .stderr(predicate::str::contains("Created plz.toml"));
-
-        let content = fs::read_to_string(dir.path().join("plz.toml")).unwrap();
-        assert!(content.contains("[tasks.hello]"));
-        assert!(content.contains("echo 'hello world'"));
+            .failure()
+            .stderr(predicate::str::contains("sleep 30 (cancelled)"))
+            .stderr(predicate::str::contains(
+                "Task failed:\u{1b}[0m false: Command failed with exit code 1",
+            ));
     }
 
     #[test]
-    fn cli_version_mismatch_shows_warning() {
+    fn cli_parallel_output_is_labelled() {
         let dir = TempDir::new().unwrap();
         fs::write(
             dir.path().join("plz.toml"),
             r#"
-[plz]
-version = ">=99.0.0"
+[tasks.check]
+run_parallel = ["plz:lint", "sh -c 'sleep 0.2; echo late'", "sh -c 'echo err >&2'"]
 
-[tasks.hello]
-run = "echo hello"
+[tasks.grouped]
+run_parallel = ["plz:lint", "sh -c 'echo a; sleep 0.3; echo b'", "sh -c 'sleep 0.1; echo quick'"]
+output = "grouped"
+
+[tasks.raw]
+run_parallel = ["echo plain", "true"]
+output = "raw"
+
+[tasks.lint]
+run = "echo linting"
 "#,
         )
         .unwrap();
 
+        let output = plz().arg("check").current_dir(dir.path()).output().unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        // Labels tell the two `sh` commands apart and are padded to line up
+        assert!(
+            stdout.contains("\x1b[36m[lint]                        \x1b[0m linting\n"),
+            "got: {stdout}"
+        );
+        assert!(
+            stdout.contains("\x1b[35m[sh -c 'sleep 0.2; echo late']\x1b[0m late\n"),
+            "got: {stdout}"
+        );
+        assert!(
+            stderr.contains("\x1b[33m[sh -c 'echo err >&2']        \x1b[0m err\n"),
+            "got: {stderr}"
+        );
+        // What plz prints for a referenced task gets its label too
+        assert!(
+            stderr.contains("[lint]                        \x1b[0m \u{2192} echo linting"),
+            "got: {stderr}"
+        );
+
+        let output = plz()
+            .arg("grouped")
+            .current_dir(dir.path())
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let lines: Vec<&str> = stdout.lines().collect();
+        let a = lines.iter().position(|l| l.ends_with(" a")).unwrap();
+        assert!(lines[a + 1].ends_with(" b"), "got: {stdout}");
+        assert!(
+            lines.iter().position(|l| l.ends_with(" quick")).unwrap() < a,
+            "got: {stdout}"
+        );
+
         plz()
-            .arg("hello")
+            .arg("raw")
             .current_dir(dir.path())
             .assert()
             .success()
-            .stderr(predicate::str::contains(
-                "does not match version requirement",
-            ));
+            .stdout("plain\n");
     }
 
     #[test]
@@ -3371,6 +6764,17 @@ mod healthcheck_tests {
             .stderr(predicate::str::contains("✗"));
     }
 
+    #[test]
+    fn run_healthcheck_returns_an_error_on_issues() {
+        let dir = TempDir::new().unwrap();
+        git_init(&dir);
+        fs::write(dir.path().join("file.txt"), "<<<<<<< HEAD\nfoo\n").unwrap();
+        git_add_commit(&dir);
+
+        let err = plzplz::healthcheck::run_healthcheck(dir.path(), false, &[], &[]).unwrap_err();
+        assert_eq!(err.to_string(), "Healthcheck failed: merge-conflict");
+    }
+
     #[test]
     fn cli_healthcheck_works_without_plz_toml() {
         let dir = TempDir::new().unwrap();
@@ -3809,3 +7213,177 @@ mod healthcheck_tests {
             .stderr(predicate::str::contains("bad.txt"));
     }
 }
+
+mod api_tests {
+    use plzplz::{Event, HealthcheckOptions, Outcome, Plz, RunOptions, Severity, Stream};
+    use std::fs;
+    use std::process;
+    use std::sync::{Arc, Mutex};
+    use tempfile::TempDir;
+
+    struct Recorder(Arc<Mutex<Vec<Event>>>);
+
+    impl plzplz::EventHandler for Recorder {
+        fn event(&self, event: &Event) {
+            self.0.lock().unwrap().push(event.clone());
+        }
+
+        fn captures_output(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn discover_and_run_with_events() {
+        let dir = TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("plz.toml"),
+            r#"
+[tasks.greet]
+args = [{ name = "who", default = "world" }]
+run = "echo hello {{args.who}} && echo oops >&2"
+
+[tasks.check]
+run_serial = ["plz:greet", "plz:broken"]
+
+[tasks.broken]
+run = "exit 3"
+
+[tasks.never]
+run_if = { file = "missing.txt" }
+run = "echo never"
+
+[taskgroup.docs.build]
+run = "echo docs"
+"#,
+        )
+        .unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let plz = Plz::discover(dir.path().join("sub"))
+            .unwrap()
+            .with_events(Recorder(events.clone()));
+        assert_eq!(plz.root(), dir.path());
+        assert_eq!(
+            plz.task_names(),
+            vec!["broken", "check", "docs:build", "greet", "never"]
+        );
+
+        let outcome = plz
+            .task("greet")
+            .unwrap()
+            .run(RunOptions {
+                args: vec!["plz".to_string()],
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(outcome, Outcome::Passed);
+        {
+            let events = events.lock().unwrap();
+            assert!(matches!(
+                &events[0],
+                Event::CommandStarted { command, background: false }
+                    if command == "echo hello plz && echo oops >&2"
+            ));
+            assert!(events.iter().any(|e| matches!(
+                e,
+                Event::Output { stream: Stream::Stdout, line, .. } if line == "hello plz"
+            )));
+            assert!(events.iter().any(|e| matches!(
+                e,
+                Event::Output { stream: Stream::Stderr, line, .. } if line == "oops"
+            )));
+            assert!(matches!(
+                events.last(),
+                Some(Event::CommandFinished {
+                    exit_code: Some(0),
+                    ..
+                })
+            ));
+        }
+
+        let docs = plz.task("docs:build").unwrap();
+        assert_eq!(docs.name(), "docs:build");
+        assert_eq!(docs.run(RunOptions::default()).unwrap(), Outcome::Passed);
+        assert_eq!(
+            plz.task("never")
+                .unwrap()
+                .run(RunOptions::default())
+                .unwrap(),
+            Outcome::Skipped
+        );
+
+        events.lock().unwrap().clear();
+        assert!(
+            plz.task("check")
+                .unwrap()
+                .run(RunOptions::default())
+                .is_err()
+        );
+        let events = events.lock().unwrap();
+        assert!(events.iter().any(|e| matches!(
+            e,
+            Event::Summary { results } if results == &[
+                ("greet".to_string(), Outcome::Passed),
+                ("broken".to_string(), Outcome::Failed),
+            ]
+        )));
+        assert!(events.iter().any(|e| matches!(
+            e,
+            Event::TaskFailed { task: Some(task), .. } if task == "broken"
+        )));
+
+        let err = plz.task("nope").unwrap_err();
+        assert_eq!(err.to_string(), "\"nope\" isn't a task");
+    }
+
+    #[test]
+    fn load_keeps_warnings() {
+        let dir = TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("plz.toml"),
+            "[plz]\nversion = \"<0.0.1\"\n\n[tasks.build]\nrun = \"true\"\nrn = \"typo\"\n",
+        )
+        .unwrap();
+
+        let plz = Plz::discover(dir.path()).unwrap();
+        let warnings: Vec<&str> = plz.warnings().iter().map(|w| w.message.as_str()).collect();
+        assert_eq!(warnings.len(), 2, "{warnings:?}");
+        assert_eq!(warnings[0], "unknown key \"rn\" in [tasks.build]");
+        assert!(warnings[1].contains("does not match version requirement"));
+        assert!(
+            plz.warnings()
+                .iter()
+                .all(|w| w.severity == Severity::Warning)
+        );
+    }
+
+    #[test]
+    fn healthcheck_returns_results() {
+        let dir = TempDir::new().unwrap();
+        process::Command::new("git")
+            .arg("init")
+            .current_dir(dir.path())
+            .output()
+            .unwrap();
+        fs::write(dir.path().join("plz.toml"), "").unwrap();
+        fs::write(dir.path().join("bad.txt"), "trailing \n").unwrap();
+        process::Command::new("git")
+            .args(["add", "bad.txt"])
+            .current_dir(dir.path())
+            .output()
+            .unwrap();
+
+        let plz = Plz::discover(dir.path()).unwrap();
+        let results = plz
+            .healthcheck(&HealthcheckOptions {
+                only: vec!["trailing-whitespace".to_string()],
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(!results[0].passed);
+        assert_eq!(results[0].findings[0].file, "bad.txt");
+    }
+}